@@ -0,0 +1,91 @@
+//! Parsing templates dense with placeholders, with `parser::TemplateIter` borrowing raw text from
+//! the template, against the parser it replaced, which collected raw text into an owned
+//! `String` for every placeholder.
+//!
+//! Run with `cargo bench --bench parse`.
+
+extern crate combine;
+extern crate rig;
+
+use std::time::{Duration, Instant};
+
+use combine::*;
+use combine::char::{alpha_num, char, spaces, string};
+use combine::primitives::Consumed;
+use rig::parser::{Chunk, TemplateIter};
+use rig::template::{Placeholder, Style};
+
+const ROUNDS: u32 = 20;
+
+/// `StringTemplate` parser as it was before raw text was borrowed.
+fn parse_owned(input: &str) -> Result<(String, Option<Placeholder>, &str), ParseError<&str>> {
+    let ident = || many1::<String, _>(alpha_num().or(one_of("_-".chars())).skip(spaces()));
+    let lex_char = |c| char(c).skip(spaces());
+
+    let escape_ph = many::<String, _>(satisfy(|c| c != '$').then(|c| {
+        parser(move |input| if c == '\\' {
+            any().parse_stream(input)
+        } else {
+            Ok((c, Consumed::Empty(input)))
+        })
+    }));
+
+    let string_literal = between(char('"'), char('"'), many::<String, _>(satisfy(|c| c != '"')));
+    let fmt_args = string("format").skip(spaces()).with(lex_char('=').with(string_literal));
+    let placeholder = between(lex_char('$'),
+                              char('$'),
+                              ident().and(optional(char(';').with(fmt_args))))
+        .map(|parsed| Placeholder::new(&parsed.0, parsed.1, Style::ST));
+
+    let mut parser = escape_ph.and(optional(placeholder));
+    parser.parse(input).map(|(result, rest)| (result.0, result.1, rest))
+}
+
+fn count_owned(tpl: &str) -> usize {
+    let mut count = 0;
+    let mut rest = tpl;
+    while !rest.is_empty() {
+        let (raw, ph, next) = parse_owned(rest).expect("template is well-formed");
+        count += raw.len() + ph.map_or(0, |_| 1);
+        rest = next;
+    }
+    count
+}
+
+fn count_borrowed(tpl: &str) -> usize {
+    TemplateIter::new(tpl, &Style::ST)
+        .map(|chunk| match chunk.expect("template is well-formed") {
+            Chunk::Raw(_, raw) => raw.len(),
+            Chunk::Tag(..) => 1,
+        })
+        .sum()
+}
+
+/// Template of `lines` lines, each with two placeholders.
+fn template(lines: usize) -> String {
+    (0..lines)
+        .map(|i| format!("pub const C{}: &str = \"$name;format=\"snake\"$-$version$\";\n", i))
+        .collect()
+}
+
+fn time<F: Fn(&str) -> usize>(tpl: &str, f: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        assert!(f(tpl) > 0);
+    }
+    start.elapsed() / ROUNDS
+}
+
+fn main() {
+    println!("{:>8} {:>14} {:>14}", "lines", "owned (us)", "borrowed (us)");
+    for &lines in &[100, 1_000, 10_000] {
+        let tpl = template(lines);
+        assert_eq!(count_owned(&tpl), count_borrowed(&tpl));
+        let owned = time(&tpl, count_owned);
+        let borrowed = time(&tpl, count_borrowed);
+        println!("{:>8} {:>14} {:>14}",
+                 lines,
+                 owned.as_micros(),
+                 borrowed.as_micros());
+    }
+}
@@ -0,0 +1,176 @@
+//! `#[derive(VtolParams)]` for structs whose fields are template parameters of rig.
+//!
+//! Re-exported by rig with `derive` feature, so depend on that rather than this crate.
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+#[macro_use]
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use syn::{Data, DeriveInput, Fields, Ident, Lit, Meta, NestedMeta, Type};
+
+/// Implement `VtolParams`, and `From<T> for Params`, for a struct with named fields.
+///
+/// Each field is a parameter named after it, with its value formatted by `Display`. `Option`
+/// fields are left out of `Params` where they are `None`.
+///
+/// Doc comments of fields become descriptions of the parameters. `#[vtol(default = "...")]`
+/// gives a default value, and `#[vtol(rename = "...")]` a name other than that of the field.
+#[proc_macro_derive(VtolParams, attributes(vtol))]
+pub fn derive_vtol_params(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Field of the struct, as the parameter it declares.
+struct Param {
+    ident: Ident,
+    name: String,
+    description: Option<String>,
+    default: Option<String>,
+    optional: bool,
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match input.data {
+        Data::Struct(ref data) => {
+            match data.fields {
+                Fields::Named(ref fields) => &fields.named,
+                _ => {
+                    return Err(syn::Error::new_spanned(input,
+                                                       "VtolParams needs named fields"))
+                }
+            }
+        }
+        _ => return Err(syn::Error::new_spanned(input, "VtolParams can only derive structs")),
+    };
+    let params = fields.iter().map(parse_field).collect::<syn::Result<Vec<_>>>()?;
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let inserts = params.iter().map(|p| {
+        let (ident, name) = (&p.ident, &p.name);
+        if p.optional {
+            quote! {
+                if let ::std::option::Option::Some(ref v) = value.#ident {
+                    map.insert(#name.to_owned(), ::std::string::ToString::to_string(v));
+                }
+            }
+        } else {
+            quote! {
+                map.insert(#name.to_owned(), ::std::string::ToString::to_string(&value.#ident));
+            }
+        }
+    });
+    let specs = params.iter().map(|p| {
+        let name = &p.name;
+        let description = to_option(&p.description);
+        let default = to_option(&p.default);
+        quote! {
+            ::rig::template::ParamSpec {
+                name: #name.to_owned(),
+                description: #description,
+                default: #default,
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::std::convert::From<#ident #ty_generics> for ::rig::template::Params
+            #where_clause
+        {
+            fn from(value: #ident #ty_generics) -> ::rig::template::Params {
+                let mut map = ::std::collections::BTreeMap::new();
+                #(#inserts)*
+                ::rig::template::Params::from_map(map)
+            }
+        }
+
+        impl #impl_generics ::rig::template::VtolParams for #ident #ty_generics #where_clause {
+            fn param_specs() -> ::std::vec::Vec<::rig::template::ParamSpec> {
+                vec![#(#specs),*]
+            }
+        }
+    })
+}
+
+fn parse_field(field: &syn::Field) -> syn::Result<Param> {
+    let ident = match field.ident {
+        Some(ref ident) => ident.clone(),
+        None => return Err(syn::Error::new_spanned(field, "VtolParams needs named fields")),
+    };
+    let mut name = ident.to_string();
+    let mut docs = Vec::new();
+    let mut default = None;
+
+    for attr in &field.attrs {
+        if attr.path.is_ident("doc") {
+            if let Ok(Meta::NameValue(ref nv)) = attr.parse_meta() {
+                if let Lit::Str(ref s) = nv.lit {
+                    docs.push(s.value().trim().to_owned());
+                }
+            }
+        } else if attr.path.is_ident("vtol") {
+            let list = match attr.parse_meta()? {
+                Meta::List(list) => list,
+                meta => return Err(syn::Error::new_spanned(meta, "expected `#[vtol(...)]`")),
+            };
+            for nested in &list.nested {
+                match *nested {
+                    NestedMeta::Meta(Meta::NameValue(ref nv)) if nv.path.is_ident("default") => {
+                        default = Some(lit_str(&nv.lit)?);
+                    }
+                    NestedMeta::Meta(Meta::NameValue(ref nv)) if nv.path.is_ident("rename") => {
+                        name = lit_str(&nv.lit)?;
+                    }
+                    ref other => {
+                        return Err(syn::Error::new_spanned(other,
+                                                           "expected `default = \"...\"` or \
+                                                            `rename = \"...\"`"))
+                    }
+                }
+            }
+        }
+    }
+
+    // lines of doc comment are joined, as descriptions are shown on a line
+    let description = docs.join(" ").trim().to_owned();
+    Ok(Param {
+        ident: ident,
+        name: name,
+        description: if description.is_empty() { None } else { Some(description) },
+        default: default,
+        optional: is_option(&field.ty),
+    })
+}
+
+fn lit_str(lit: &Lit) -> syn::Result<String> {
+    match *lit {
+        Lit::Str(ref s) => Ok(s.value()),
+        _ => Err(syn::Error::new_spanned(lit, "expected string literal")),
+    }
+}
+
+fn is_option(ty: &Type) -> bool {
+    match *ty {
+        Type::Path(ref ty) => {
+            ty.qself.is_none() &&
+            ty.path.segments.last().map_or(false, |segment| segment.ident == "Option")
+        }
+        _ => false,
+    }
+}
+
+fn to_option(s: &Option<String>) -> TokenStream2 {
+    match *s {
+        Some(ref s) => quote! { ::std::option::Option::Some(#s.to_owned()) },
+        None => quote! { ::std::option::Option::None },
+    }
+}
@@ -0,0 +1,36 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate rig;
+
+use std::collections::HashMap;
+use std::str;
+
+use rig::parser::parse_template;
+use rig::template::{Style, Template};
+
+fuzz_target!(|data: &[u8]| {
+    let input = match str::from_utf8(data) {
+        Ok(s) => s,
+        Err(_) => return,
+    };
+    let mut params = HashMap::new();
+    params.insert("name".to_owned(), "value".to_owned());
+
+    for style in &[Style::ST, Style::Path, Style::Tera, Style::Handlebars, Style::Liquid] {
+        // each step must consume input, so that parsing terminates
+        let mut rest = input;
+        while let Ok((_, _, next)) = parse_template(rest, style) {
+            if rest.is_empty() {
+                break;
+            }
+            assert!(next.len() < rest.len());
+            rest = next;
+        }
+
+        let scan = Template::read_str(style.clone(), input).scan();
+        let mut out = Vec::new();
+        let rendered = Template::write_once(&mut out, style.clone(), input, &params);
+        assert_eq!(rendered.is_err(), scan.error.is_some());
+    }
+});
@@ -0,0 +1,186 @@
+//! Zip and gzipped tar archives, which templates can be read from and projects can be generated
+//! into.
+//!
+//! Templates in archives are extracted into temporal directories, and generated projects are
+//! packed from them, so that every other part of generation works on file system as it does for
+//! templates cloned from git repositories.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Component, Path, PathBuf};
+
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use tar;
+use tempdir::TempDir;
+use walkdir::WalkDir;
+use zip::{self, ZipArchive, ZipWriter};
+
+use super::errors::*;
+use super::fsutils;
+use super::source::Checkout;
+
+/// Format of archive, told by file extension.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Format {
+    /// `.zip`
+    Zip,
+    /// `.tar.gz` or `.tgz`
+    TarGz,
+}
+
+impl Format {
+    /// Format of archive at `path`, if it is named as an archive.
+    pub fn of(path: &Path) -> Option<Format> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        if name.ends_with(".zip") {
+            Some(Format::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Format::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+/// Extract template `archive` into a temporal directory, removed when the checkout is dropped.
+///
+/// Where the archive has a single directory at the top, as archives of repositories usually do,
+/// the template is checked out at that directory.
+pub fn open(archive: &Path) -> Result<Checkout> {
+    let temp_dir = TempDir::new("rig__archive")
+        .chain_err(|| "Failed to create temporal directory")?;
+    extract(archive, temp_dir.path())?;
+
+    let mut entries = fs::read_dir(temp_dir.path())
+        .chain_err(|| ErrorKind::FsFailure("reading directory", temp_dir.path().to_path_buf()))?
+        .collect::<io::Result<Vec<_>>>()?;
+    let root = match entries.pop() {
+        Some(ref entry) if entries.is_empty() && entry.path().is_dir() => entry.path(),
+        _ => temp_dir.path().to_path_buf(),
+    };
+    Ok(Checkout::in_temp_dir(temp_dir, root))
+}
+
+/// Extract every entry of `archive` into `dir`, failing on entries which would be extracted out
+/// of `dir`.
+pub fn extract(archive: &Path, dir: &Path) -> Result<()> {
+    let format = match Format::of(archive) {
+        Some(format) => format,
+        None => bail!("{:?} is not named as a zip nor tar.gz archive", archive),
+    };
+    let file = File::open(archive)
+        .chain_err(|| ErrorKind::FsFailure("reading file", archive.to_path_buf()))?;
+    match format {
+        Format::Zip => {
+            let mut zip = ZipArchive::new(BufReader::new(file)).map_err(zip_error)?;
+            for i in 0..zip.len() {
+                let mut entry = zip.by_index(i).map_err(zip_error)?;
+                let dest = entry_dest(dir, Path::new(entry.name()))?;
+                if entry.name().ends_with('/') {
+                    fsutils::create_dir_all(&dest)?;
+                    continue;
+                }
+                fsutils::create_dir_all(dest.parent().unwrap_or(dir))?;
+                fsutils::write_with(&dest, |f| {
+                    io::copy(&mut entry, f)
+                        .map(|_| ())
+                        .chain_err(|| ErrorKind::FsFailure("writing file", dest.clone()))
+                })?;
+            }
+        }
+        Format::TarGz => {
+            let gz = GzDecoder::new(BufReader::new(file))
+                .chain_err(|| ErrorKind::FsFailure("reading file", archive.to_path_buf()))?;
+            let mut tar = tar::Archive::new(gz);
+            for entry in tar.entries()? {
+                let mut entry = entry?;
+                let dest = entry_dest(dir, &entry.path()?)?;
+                fsutils::create_dir_all(dest.parent().unwrap_or(dir))?;
+                entry.unpack(&dest)
+                    .map(|_| ())
+                    .chain_err(|| ErrorKind::FsFailure("writing file", dest.clone()))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Pack every entry under `dir` into `archive` of `format`, with paths relative to `dir`.
+pub fn pack(dir: &Path, archive: &Path, format: Format) -> Result<()> {
+    let file = File::create(archive)
+        .chain_err(|| ErrorKind::FsFailure("creating file", archive.to_path_buf()))?;
+    let entries = WalkDir::new(dir)
+        .sort_by(|a, b| a.cmp(b))
+        .into_iter()
+        .collect::<::std::result::Result<Vec<_>, _>>()
+        .map_err(|e| Error::from(e.to_string()))?;
+    let entries = entries.iter()
+        .filter_map(|e| {
+            let rel_path = e.path().strip_prefix(dir).ok()?;
+            if rel_path.as_os_str().is_empty() { None } else { Some((e, rel_path)) }
+        })
+        .collect::<Vec<_>>();
+
+    match format {
+        Format::Zip => {
+            let mut zip = ZipWriter::new(BufWriter::new(file));
+            for &(entry, rel_path) in &entries {
+                let name = entry_name(rel_path);
+                let options = zip::write::FileOptions::default();
+                if entry.file_type().is_dir() {
+                    zip.start_file(format!("{}/", name), options).map_err(zip_error)?;
+                } else {
+                    zip.start_file(name, options).map_err(zip_error)?;
+                    let mut src = File::open(entry.path()).chain_err(|| {
+                            ErrorKind::FsFailure("reading file", entry.path().to_path_buf())
+                        })?;
+                    io::copy(&mut src, &mut zip)?;
+                }
+            }
+            zip.finish().map_err(zip_error)?;
+        }
+        Format::TarGz => {
+            let gz = GzEncoder::new(BufWriter::new(file), Compression::Default);
+            let mut tar = tar::Builder::new(gz);
+            for &(entry, rel_path) in &entries {
+                let name = entry_name(rel_path);
+                if entry.file_type().is_dir() {
+                    tar.append_dir(&name, entry.path())?;
+                } else {
+                    let mut src = File::open(entry.path()).chain_err(|| {
+                            ErrorKind::FsFailure("reading file", entry.path().to_path_buf())
+                        })?;
+                    tar.append_file(&name, &mut src)?;
+                }
+            }
+            tar.into_inner()?.finish()?;
+        }
+    }
+    Ok(())
+}
+
+/// Where archive entry at `path` is extracted in `dir`, unless it would go out of `dir`.
+fn entry_dest(dir: &Path, path: &Path) -> Result<PathBuf> {
+    let escapes = path.components().any(|c| match c {
+        Component::Normal(_) | Component::CurDir => false,
+        _ => true,
+    });
+    if escapes {
+        bail!("Archive entry {:?} would be extracted out of the directory", path);
+    }
+    Ok(dir.join(path))
+}
+
+/// Name of entry at `rel_path` in archive, its components joined with `/`.
+fn entry_name(rel_path: &Path) -> String {
+    rel_path.components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn zip_error(e: zip::result::ZipError) -> Error {
+    Error::from(e.to_string())
+}
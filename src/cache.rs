@@ -0,0 +1,184 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::NaiveDateTime;
+use git2::{Direction, Repository};
+use url::Url;
+
+use super::errors::*;
+use super::fsutils;
+use super::source::Source;
+
+/// A remote template repository cloned into local cache.
+#[derive(Clone, Debug)]
+pub struct CacheEntry {
+    /// Path of the entry relative to cache directory, e.g. `github.com/lettenj61/rig`.
+    pub name: String,
+    pub path: PathBuf,
+    /// URL of `origin` remote.
+    pub url: Option<String>,
+    /// Branch checked out.
+    pub branch: Option<String>,
+    /// Commit id of checked out `HEAD`.
+    pub revision: Option<String>,
+    /// When the repository was last cloned or fetched.
+    pub updated: Option<SystemTime>,
+}
+
+impl CacheEntry {
+    /// Read metadata of git repository cached at `path`.
+    pub fn open(cache_dir: &Path, path: &Path) -> Result<CacheEntry> {
+        let repo = Repository::open(path)?;
+        let url = repo.find_remote("origin").ok().and_then(|r| r.url().map(|s| s.to_owned()));
+        let (branch, revision) = match repo.head() {
+            Ok(head) => {
+                (head.shorthand().map(|s| s.to_owned()), head.target().map(|id| id.to_string()))
+            }
+            Err(_) => (None, None),
+        };
+
+        let git_dir = path.join(".git");
+        let updated = ["FETCH_HEAD", "HEAD"]
+            .iter()
+            .filter_map(|f| fs::metadata(git_dir.join(f)).and_then(|m| m.modified()).ok())
+            .max();
+
+        let name = path.strip_prefix(templates_dir(cache_dir))
+            .unwrap_or(path)
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        Ok(CacheEntry {
+            name: name,
+            path: path.to_path_buf(),
+            url: url,
+            branch: branch,
+            revision: revision,
+            updated: updated,
+        })
+    }
+
+    /// Abbreviated commit id, like `git log --oneline` shows.
+    pub fn short_revision(&self) -> Option<&str> {
+        self.revision.as_ref().map(|r| &r[..r.len().min(7)])
+    }
+
+    /// Branch the entry is on, or `None` if `HEAD` is detached at a tag.
+    fn tracked_branch(&self) -> Option<&str> {
+        match self.branch {
+            Some(ref branch) if branch != "HEAD" => Some(branch),
+            _ => None,
+        }
+    }
+
+    /// Commit id the checked out branch points to in `origin`.
+    ///
+    /// This connects to the remote, but fetches nothing.
+    pub fn remote_revision(&self) -> Result<Option<String>> {
+        let branch = match self.tracked_branch() {
+            Some(branch) => format!("refs/heads/{}", branch),
+            None => return Ok(None),
+        };
+        let repo = Repository::open(&self.path)?;
+        let mut origin = repo.find_remote("origin")?;
+        origin.connect(Direction::Fetch)?;
+        let revision = origin.list()?
+            .iter()
+            .find(|head| head.name() == branch)
+            .map(|head| head.oid().to_string());
+        Ok(revision)
+    }
+
+    /// Whether the branch checked out has moved in `origin` since the entry was last fetched.
+    ///
+    /// Entries checked out at a tag are never stale.
+    pub fn is_stale(&self) -> Result<bool> {
+        if self.tracked_branch().is_none() {
+            return Ok(false);
+        }
+        Ok(self.remote_revision()? != self.revision)
+    }
+
+    /// Fetch the entry again from `origin`, keeping the branch checked out.
+    ///
+    /// Entries checked out at a tag are left as they are.
+    pub fn refresh(&self) -> Result<CacheEntry> {
+        let branch = match self.tracked_branch() {
+            Some(branch) => branch,
+            None => return Ok(self.clone()),
+        };
+        let url = match self.url {
+            Some(ref url) => url,
+            None => bail!("Cached template `{}` has no `origin` to fetch from", self.name),
+        };
+        Source::parse(url, Some(branch))?.fetch_into(&self.path)?;
+        let mut entry = CacheEntry::open(&self.path, &self.path)?;
+        entry.name = self.name.clone();
+        Ok(entry)
+    }
+}
+
+/// Directory to cache remote templates.
+///
+/// Looks up `RIG_CACHE_DIR`, then `$XDG_CACHE_HOME/rig`, then falls back to `~/.cache/rig`.
+pub fn cache_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("RIG_CACHE_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    if let Some(dir) = env::var_os("XDG_CACHE_HOME") {
+        return Some(PathBuf::from(dir).join("rig"));
+    }
+    fsutils::home_dir().map(|home| home.join(".cache").join("rig"))
+}
+
+fn templates_dir(cache_dir: &Path) -> PathBuf {
+    cache_dir.join("templates")
+}
+
+/// Location in cache to clone the repository at `url`, e.g. `templates/github.com/owner/repo`.
+pub fn entry_path(cache_dir: &Path, url: &Url) -> PathBuf {
+    let mut path = templates_dir(cache_dir);
+    path.push(url.host_str().unwrap_or("localhost"));
+    for segment in url.path_segments().into_iter().flat_map(|s| s) {
+        if !segment.is_empty() && segment != "." && segment != ".." {
+            path.push(segment);
+        }
+    }
+    path
+}
+
+/// List every template repository cached under `cache_dir`, sorted by name.
+pub fn list(cache_dir: &Path) -> Result<Vec<CacheEntry>> {
+    let mut entries = Vec::new();
+    let root = templates_dir(cache_dir);
+    if fsutils::is_directory(&root) {
+        find_repositories(cache_dir, &root, &mut entries)?;
+    }
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(entries)
+}
+
+fn find_repositories(cache_dir: &Path, dir: &Path, entries: &mut Vec<CacheEntry>) -> Result<()> {
+    if fsutils::is_directory(dir.join(".git")) {
+        entries.push(CacheEntry::open(cache_dir, dir)?);
+        return Ok(());
+    }
+    let failed = || ErrorKind::FsFailure("reading directory", dir.to_path_buf());
+    for entry in fs::read_dir(dir).chain_err(&failed)? {
+        let path = entry.chain_err(&failed)?.path();
+        if fsutils::is_directory(&path) {
+            find_repositories(cache_dir, &path, entries)?;
+        }
+    }
+    Ok(())
+}
+
+/// Format system time as `YYYY-MM-DD HH:MM` in UTC.
+pub fn format_time(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    NaiveDateTime::from_timestamp(secs as i64, 0).format("%Y-%m-%d %H:%M").to_string()
+}
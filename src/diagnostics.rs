@@ -0,0 +1,81 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use super::errors::*;
+use super::i18n::{self, Lang};
+
+/// Characters in file names which are not portable across platforms.
+const SUSPICIOUS_CHARS: &'static [char] = &[':', '*', '?', '"', '<', '>', '|'];
+
+/// Non-fatal problem found while reading parameters or generating project.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Warning {
+    /// Config entry whose value is an array of non-scalars, which cannot be used as a parameter.
+    DroppedParam(String),
+    /// Template file whose rendered name has characters not portable across platforms.
+    SuspiciousPath { file: PathBuf, rendered: String },
+    /// Parameter referenced in the template, but its value is empty.
+    EmptyValue(String),
+}
+
+impl Warning {
+    /// Stable identifier of the kind of warning, for machine readable output.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            Warning::DroppedParam(_) => "dropped-param",
+            Warning::SuspiciousPath { .. } => "suspicious-path",
+            Warning::EmptyValue(_) => "empty-value",
+        }
+    }
+}
+
+/// Warnings are described in English. See `i18n::warning` for other languages.
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&i18n::warning(Lang::En, self))
+    }
+}
+
+/// Collects warnings through reading parameters and generating project.
+///
+/// Callers decide whether to print or ignore them. With `deny` set, generation stops before
+/// writing any file if there are warnings.
+#[derive(Clone, Debug, Default)]
+pub struct Diagnostics {
+    warnings: Vec<Warning>,
+    pub deny: bool,
+}
+
+impl Diagnostics {
+    pub fn new() -> Diagnostics {
+        Diagnostics::default()
+    }
+
+    pub fn warn(&mut self, warning: Warning) {
+        if !self.warnings.contains(&warning) {
+            self.warnings.push(warning);
+        }
+    }
+
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.warnings.is_empty()
+    }
+
+    /// Fail if any warning is collected and `deny` is set.
+    pub fn check(&self) -> Result<()> {
+        if self.deny && !self.is_empty() {
+            bail!(ErrorKind::DeniedWarnings(self.warnings.len()));
+        }
+        Ok(())
+    }
+}
+
+/// Whether rendered file `name` has characters not portable across platforms.
+pub fn is_suspicious_name(name: &str) -> bool {
+    name.contains(SUSPICIOUS_CHARS) || name.chars().any(|c| c.is_control()) ||
+    name.trim() != name
+}
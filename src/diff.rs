@@ -0,0 +1,208 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str;
+
+use walkdir::WalkDir;
+
+use super::errors::*;
+
+/// Lines of context shown around each change.
+const CONTEXT: usize = 3;
+
+/// Difference between a rendered file and the one in existing project.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FileChange {
+    /// File is rendered, but does not exist in the project.
+    Added(PathBuf),
+    /// File exists in both but contents differ, with unified diff of them.
+    Modified(PathBuf, String),
+    /// Contents differ and at least one of them is not a text.
+    BinaryModified(PathBuf),
+}
+
+impl FileChange {
+    pub fn path(&self) -> &Path {
+        match *self {
+            FileChange::Added(ref p) |
+            FileChange::Modified(ref p, _) |
+            FileChange::BinaryModified(ref p) => p,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Inserted(&'a str),
+}
+
+/// Compute line based diff between `old` and `new`, based on longest common subsequence.
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+    let (n, m) = (old.len(), new.len());
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if old[i] == new[j] {
+            lines.push(Line::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            lines.push(Line::Removed(old[i]));
+            i += 1;
+        } else {
+            lines.push(Line::Inserted(new[j]));
+            j += 1;
+        }
+    }
+    lines.extend(old[i..].iter().map(|l| Line::Removed(l)));
+    lines.extend(new[j..].iter().map(|l| Line::Inserted(l)));
+    lines
+}
+
+/// Format differences of two texts in unified diff format, or `None` if they are identical.
+pub fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> Option<String> {
+    if old == new {
+        return None;
+    }
+
+    let old_lines = old.lines().collect::<Vec<_>>();
+    let new_lines = new.lines().collect::<Vec<_>>();
+    let lines = diff_lines(&old_lines, &new_lines);
+
+    let mut out = format!("--- {}\n+++ {}\n", old_name, new_name);
+
+    // find ranges of `lines` to print, merging changes close to each other
+    let changed = lines.iter()
+        .enumerate()
+        .filter(|&(_, l)| !is_same(l))
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for i in changed {
+        let start = i.saturating_sub(CONTEXT);
+        let end = (i + CONTEXT + 1).min(lines.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    for (start, end) in hunks {
+        // line numbers where the hunk begins in both texts
+        let old_start = lines[..start].iter().filter(|l| !is_inserted(l)).count();
+        let new_start = lines[..start].iter().filter(|l| !is_removed(l)).count();
+        let old_len = lines[start..end].iter().filter(|l| !is_inserted(l)).count();
+        let new_len = lines[start..end].iter().filter(|l| !is_removed(l)).count();
+
+        out.push_str(&format!("@@ -{},{} +{},{} @@\n",
+                              hunk_origin(old_start, old_len),
+                              old_len,
+                              hunk_origin(new_start, new_len),
+                              new_len));
+        for line in &lines[start..end] {
+            match *line {
+                Line::Same(s) => out.push_str(&format!(" {}\n", s)),
+                Line::Removed(s) => out.push_str(&format!("-{}\n", s)),
+                Line::Inserted(s) => out.push_str(&format!("+{}\n", s)),
+            }
+        }
+    }
+
+    Some(out)
+}
+
+fn hunk_origin(start: usize, len: usize) -> usize {
+    if len == 0 { start } else { start + 1 }
+}
+
+fn is_same(line: &Line) -> bool {
+    match *line {
+        Line::Same(_) => true,
+        _ => false,
+    }
+}
+
+fn is_inserted(line: &Line) -> bool {
+    match *line {
+        Line::Inserted(_) => true,
+        _ => false,
+    }
+}
+
+fn is_removed(line: &Line) -> bool {
+    match *line {
+        Line::Removed(_) => true,
+        _ => false,
+    }
+}
+
+/// Compare every file under `rendered` with the same path under `existing`.
+///
+/// Files only exist in `existing` are not reported, as they are not managed by the template.
+pub fn diff_trees(rendered: &Path, existing: &Path) -> Result<Vec<FileChange>> {
+    let mut changes = Vec::new();
+
+    for entry in WalkDir::new(rendered).sort_by(|a, b| a.cmp(b)) {
+        let entry = entry.map_err(|e| Error::from(e.to_string()))?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let rel_path = entry.path().strip_prefix(rendered).unwrap_or(entry.path()).to_path_buf();
+        let target = existing.join(&rel_path);
+        if !target.is_file() {
+            changes.push(FileChange::Added(rel_path));
+            continue;
+        }
+
+        let new = fs::read(entry.path())
+            .chain_err(|| ErrorKind::FsFailure("reading file", entry.path().to_path_buf()))?;
+        let old = fs::read(&target)
+            .chain_err(|| ErrorKind::FsFailure("reading file", target.clone()))?;
+        if new == old {
+            continue;
+        }
+
+        match (str::from_utf8(&old), str::from_utf8(&new)) {
+            (Ok(old), Ok(new)) => {
+                let name = rel_path.to_string_lossy();
+                let diff = unified_diff(old, new, &format!("a/{}", name), &format!("b/{}", name));
+                changes.push(FileChange::Modified(rel_path.clone(), diff.unwrap_or_default()));
+            }
+            _ => changes.push(FileChange::BinaryModified(rel_path)),
+        }
+    }
+
+    Ok(changes)
+}
+
+/// Files under `expected` which don't exist under `rendered`, relative to both roots.
+pub fn missing_files(rendered: &Path, expected: &Path) -> Result<Vec<PathBuf>> {
+    let mut missing = Vec::new();
+
+    for entry in WalkDir::new(expected).sort_by(|a, b| a.cmp(b)) {
+        let entry = entry.map_err(|e| Error::from(e.to_string()))?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let rel_path = entry.path().strip_prefix(expected).unwrap_or(entry.path()).to_path_buf();
+        if !rendered.join(&rel_path).is_file() {
+            missing.push(rel_path);
+        }
+    }
+
+    Ok(missing)
+}
@@ -1,11 +1,41 @@
 use std::io;
+use std::path::PathBuf;
+
+use super::template::Position;
+#[cfg(feature = "fs")]
+use git2;
+use glob;
+use regex;
+#[cfg(feature = "json")]
+use serde_json;
+#[cfg(feature = "yaml")]
+use serde_yaml;
+#[cfg(feature = "handlebars")]
+use handlebars;
+#[cfg(feature = "liquid")]
+use liquid;
+#[cfg(feature = "tera")]
+use tera;
+use toml;
+#[cfg(feature = "fs")]
 use url;
 
 error_chain! {
 
     foreign_links {
+        Git(git2::Error) #[cfg(feature = "fs")];
+        HandlebarsRender(handlebars::RenderError) #[cfg(feature = "handlebars")];
+        HandlebarsTemplate(handlebars::TemplateError) #[cfg(feature = "handlebars")];
+        Glob(glob::PatternError);
         Io(io::Error);
-        ParseUrl(url::ParseError);
+        Json(serde_json::Error) #[cfg(feature = "json")];
+        Liquid(liquid::Error) #[cfg(feature = "liquid")];
+        ParseUrl(url::ParseError) #[cfg(feature = "fs")];
+        Regex(regex::Error);
+        Tera(tera::Error) #[cfg(feature = "tera")];
+        Toml(toml::de::Error);
+        TomlSer(toml::ser::Error);
+        Yaml(serde_yaml::Error) #[cfg(feature = "yaml")];
     }
 
     errors {
@@ -17,5 +47,87 @@ error_chain! {
             description("Failured decoding Toml string")
             display("Failured decoding Toml string")
         }
+        PropertiesDecodeFailure {
+            description("Failured decoding Java properties")
+            display("Failured decoding Java properties")
+        }
+        Parse(line: usize, column: usize) {
+            description("Malformed placeholder")
+            display("Malformed placeholder at line {}, column {}", line, column)
+        }
+        Formatter(param: String, value: String, formatter: String, reason: String) {
+            description("Formatter cannot be applied")
+            display("Formatter `{}` cannot be applied to `{}` = {:?}: {}", formatter, param, value, reason)
+        }
+        RenderFailure(file: PathBuf, position: Option<Position>) {
+            description("Failed to render template")
+            display("Failed to render {}{}",
+                    file.display(),
+                    position.map(|p| format!(":{}:{}", p.line, p.column)).unwrap_or_default())
+        }
+        IllegalPath(file: PathBuf, rendered: String, reason: &'static str) {
+            description("Rendered path is not allowed")
+            display("{} is rendered as {:?}, which {}", file.display(), rendered, reason)
+        }
+        TemplateNotFound(name: String) {
+            description("Template not found")
+            display("Template not found: {}", name)
+        }
+        NotCached(url: String) {
+            description("Template is not in local cache")
+            display("{} is not in local cache, fetch it once without --offline", url)
+        }
+        FsFailure(operation: &'static str, path: PathBuf) {
+            description("File system operation failed")
+            display("Failed {} `{}`", operation, path.display())
+        }
+        InvalidUtf8(path: PathBuf, offset: usize) {
+            description("File is not valid UTF-8")
+            display("`{}` is not valid UTF-8 at byte {}. Mark it with --verbatim if it is not a text",
+                    path.display(),
+                    offset)
+        }
+        DeniedWarnings(count: usize) {
+            description("Warnings are treated as errors")
+            display("{} warning(s) treated as errors", count)
+        }
+        UnsupportedValue(key: String) {
+            description("Config entry cannot be used as parameter")
+            display("Value of `{}` is neither a scalar nor an array of scalars", key)
+        }
+        InvalidValue(param: String, value: String, reason: String) {
+            description("Value is not allowed by template manifest")
+            display("Value {:?} of `{}` is not allowed: it {}", value, param, reason)
+        }
+        MissingParams(names: Vec<String>) {
+            description("No value given for parameters")
+            display("No value given for parameters: {}", names.join(", "))
+        }
+        InvalidExpression(expr: String, reason: String) {
+            description("Invalid expression deriving parameter")
+            display("Invalid expression `{}`: {}", expr, reason)
+        }
+        CyclicParams(names: Vec<String>) {
+            description("Parameters refer to each other in a cycle")
+            display("Parameters refer to each other in a cycle: {}", names.join(" -> "))
+        }
+        IncludeCycle(names: Vec<String>) {
+            description("Templates include each other in a cycle")
+            display("Templates include each other in a cycle: {}", names.join(" -> "))
+        }
+        FileExists(path: PathBuf) {
+            description("File already exists")
+            display("File already exists: {}", path.display())
+        }
+        HookFailed(command: String, code: Option<i32>) {
+            description("Hook failed")
+            display("Hook `{}` failed{}",
+                    command,
+                    code.map(|c| format!(" with exit code {}", c)).unwrap_or_default())
+        }
+        DirectoryNotEmpty(path: PathBuf) {
+            description("Directory is not empty")
+            display("Directory is not empty: {}", path.display())
+        }
     }
 }
@@ -0,0 +1,290 @@
+//! Expressions deriving parameters from others, declared in `[derived]` table of TOML config
+//! for what formatters alone cannot do.
+//!
+//! ```toml
+//! [derived]
+//! module_name = "lower(replace(name, '-', '_'))"
+//! artifact = "concat(group, ':', kebab-case(name))"
+//! ```
+//!
+//! An expression is a parameter name, a literal quoted with `'` or `"`, a number, or a call of
+//! formatter taking the value to format first and its own arguments after it, e.g.
+//! `truncate(name, 8)`. `concat` joins any number of values.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::errors::*;
+use super::format::{self, Formatter};
+use super::template::{ParamMap, Params};
+
+/// Function joining its arguments, besides formatters.
+const CONCAT: &'static str = "concat";
+
+/// Parsed expression.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Expr {
+    /// Value of parameter of the name.
+    Param(String),
+    Literal(String),
+    /// Formatter or `concat` applied to the arguments.
+    Call(String, Vec<Expr>),
+}
+
+impl Expr {
+    /// Parse `s` as a whole, failing with `ErrorKind::InvalidExpression` on malformed one or
+    /// unknown function.
+    pub fn parse(s: &str) -> Result<Expr> {
+        let mut parser = Parser { s: s, pos: 0 };
+        let expr = parser.expr()?;
+        parser.skip_spaces();
+        if parser.pos < s.len() {
+            return Err(parser.error("unexpected characters after the expression"));
+        }
+        expr.check_calls().map_err(|reason| ErrorKind::InvalidExpression(s.to_owned(), reason))?;
+        Ok(expr)
+    }
+
+    /// Names of parameters the expression refers to, in order of appearance.
+    pub fn params(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        self.collect_params(&mut names);
+        names
+    }
+
+    fn collect_params<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match *self {
+            Expr::Param(ref name) => names.push(name),
+            Expr::Literal(_) => {}
+            Expr::Call(_, ref args) => {
+                for arg in args {
+                    arg.collect_params(names);
+                }
+            }
+        }
+    }
+
+    /// Evaluate the expression over `params`, failing with `ErrorKind::InvalidExpression` if it
+    /// refers to a parameter not given, or a formatter cannot be applied to the value.
+    pub fn eval<M: ParamMap>(&self, params: &M) -> Result<String> {
+        let fail = |reason: String| -> Error {
+            ErrorKind::InvalidExpression(self.to_string(), reason).into()
+        };
+        match *self {
+            Expr::Param(ref name) => {
+                params.lookup(name)
+                    .cloned()
+                    .ok_or_else(|| fail(format!("parameter `{}` is not given", name)))
+            }
+            Expr::Literal(ref value) => Ok(value.clone()),
+            Expr::Call(ref name, ref args) => {
+                let args = args.iter().map(|arg| arg.eval(params)).collect::<Result<Vec<_>>>()?;
+                if name == CONCAT {
+                    return Ok(args.concat());
+                }
+                let mut args = args.into_iter();
+                let value = args.next().unwrap_or_default();
+                let given = args.collect::<Vec<_>>();
+                let formatter = formatter(name, given.clone())
+                    .ok_or_else(|| fail(format!("`{}` does not take {:?}", name, given)))?;
+                if let Some(reason) = format::validate(&value, formatter.clone()) {
+                    return Err(fail(format!("`{}` cannot be applied to {:?}: {}",
+                                            name,
+                                            value,
+                                            reason)));
+                }
+                Ok(format::format(&value, formatter).into_owned())
+            }
+        }
+    }
+
+    /// Tell why any call of the expression would fail whatever the parameters are.
+    fn check_calls(&self) -> ::std::result::Result<(), String> {
+        if let Expr::Call(ref name, ref args) = *self {
+            if name != CONCAT {
+                if args.is_empty() {
+                    return Err(format!("`{}` takes the value to format", name));
+                }
+                // literal arguments tell whether the formatter takes them
+                let given = args[1..]
+                    .iter()
+                    .map(|arg| match *arg {
+                        Expr::Literal(ref s) => s.clone(),
+                        _ => "0".to_owned(),
+                    })
+                    .collect();
+                if formatter(name, given).is_none() {
+                    return Err(format!("unknown formatter `{}` of {} argument(s)",
+                                       name,
+                                       args.len() - 1));
+                }
+            }
+            for arg in args {
+                arg.check_calls()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Written back as it is parsed, with literals quoted.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Expr::Param(ref name) => write!(f, "{}", name),
+            Expr::Literal(ref value) => write!(f, "{:?}", value),
+            Expr::Call(ref name, ref args) => {
+                write!(f, "{}(", name)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+/// Formatter of `name` taking `args`, if it exists and the arguments suit it.
+fn formatter(name: &str, args: Vec<String>) -> Option<Formatter> {
+    if !args.is_empty() {
+        return format::with_args(name, args);
+    }
+    match Formatter::from(name) {
+        Formatter::Ident if name != "ident" => None,
+        f => Some(f),
+    }
+}
+
+/// Give every parameter in `derived` the value of its expression in `params`, after the
+/// parameters it refers to are derived. Parameters given values already, e.g. by `--define`,
+/// are kept as they are.
+///
+/// Fails with `ErrorKind::CyclicParams` before changing anything if expressions refer to each
+/// other in a cycle.
+pub fn derive(derived: &BTreeMap<String, Expr>, params: &mut Params) -> Result<()> {
+    let pending = derived.iter()
+        .filter(|&(name, _)| !params.param_map.contains_key(name))
+        .map(|(name, expr)| (name.as_str(), expr))
+        .collect::<BTreeMap<_, _>>();
+    let mut order = Vec::with_capacity(pending.len());
+    for name in pending.keys() {
+        derivation_order(*name, &pending, &mut Vec::new(), &mut order)?;
+    }
+    for name in order {
+        let value = pending[name].eval(&*params)?;
+        params.param_map.insert(name.to_owned(), value);
+    }
+    Ok(())
+}
+
+/// Push `name` into `order` after every parameter its expression refers to, failing on a cycle
+/// back to any of `path`.
+fn derivation_order<'a>(name: &'a str,
+                        pending: &BTreeMap<&'a str, &'a Expr>,
+                        path: &mut Vec<&'a str>,
+                        order: &mut Vec<&'a str>)
+                        -> Result<()> {
+    if order.contains(&name) {
+        return Ok(());
+    }
+    if let Some(start) = path.iter().position(|&n| n == name) {
+        let mut cycle = path[start..].iter().map(|&n| n.to_owned()).collect::<Vec<_>>();
+        cycle.push(name.to_owned());
+        bail!(ErrorKind::CyclicParams(cycle));
+    }
+    path.push(name);
+    let expr: &'a Expr = pending[name];
+    for dep in expr.params() {
+        if pending.contains_key(dep) {
+            derivation_order(dep, pending, path, order)?;
+        }
+    }
+    path.pop();
+    order.push(name);
+    Ok(())
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn expr(&mut self) -> Result<Expr> {
+        self.skip_spaces();
+        match self.peek() {
+            Some(quote) if quote == '\'' || quote == '"' => {
+                self.pos += 1;
+                match self.rest().find(quote) {
+                    Some(end) => {
+                        let value = self.rest()[..end].to_owned();
+                        self.pos += end + 1;
+                        Ok(Expr::Literal(value))
+                    }
+                    None => Err(self.error("unterminated literal")),
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == '-' => {
+                let number = self.take_while(|c| c.is_ascii_digit() || c == '.' || c == '-');
+                Ok(Expr::Literal(number.to_owned()))
+            }
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                let name = self.take_while(|c| c.is_alphanumeric() || "_-.".contains(c))
+                    .to_owned();
+                self.skip_spaces();
+                if self.peek() != Some('(') {
+                    return Ok(Expr::Param(name));
+                }
+                self.pos += 1;
+                let mut args = Vec::new();
+                self.skip_spaces();
+                if self.peek() == Some(')') {
+                    self.pos += 1;
+                    return Ok(Expr::Call(name, args));
+                }
+                loop {
+                    args.push(self.expr()?);
+                    self.skip_spaces();
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        Some(')') => {
+                            self.pos += 1;
+                            return Ok(Expr::Call(name, args));
+                        }
+                        _ => return Err(self.error("expected `,` or `)`")),
+                    }
+                }
+            }
+            Some(_) => Err(self.error("expected parameter, literal or call")),
+            None => Err(self.error("unexpected end")),
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.s[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, f: F) -> &'a str {
+        let rest = self.rest();
+        let end = rest.find(|c| !f(c)).unwrap_or(rest.len());
+        self.pos += end;
+        &rest[..end]
+    }
+
+    fn skip_spaces(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+
+    fn error(&self, reason: &str) -> Error {
+        ErrorKind::InvalidExpression(self.s.to_owned(),
+                                     format!("{} at column {}", reason, self.pos + 1))
+            .into()
+    }
+}
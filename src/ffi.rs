@@ -0,0 +1,169 @@
+//! C interface to compile templates, give them parameters and render them into buffers, for
+//! editors and tools embedding the engine.
+//!
+//! Only built with `ffi` feature. Every string is NUL terminated UTF-8, and every object is
+//! owned by the caller until given back to its `*_free` function. Functions failing return null
+//! or a negative number, leaving the reason to `rig_last_error`.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+use super::errors::*;
+use super::template::{Style, Template};
+
+/// Parameters to render templates with, as names to values.
+pub struct RigParams(HashMap<String, String>);
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+/// Keep the message of `e` for `rig_last_error` on this thread.
+fn set_last_error(e: &Error) {
+    let msg = e.iter().map(|e| e.to_string()).collect::<Vec<_>>().join(": ");
+    // C strings cannot hold NUL, so drop it rather than the whole message
+    let msg = CString::new(msg.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(msg));
+}
+
+unsafe fn to_str<'a>(s: *const c_char, what: &str) -> Result<&'a str> {
+    if s.is_null() {
+        bail!("{} is null", what);
+    }
+    CStr::from_ptr(s).to_str().map_err(|e| format!("{} is not UTF-8: {}", what, e).into())
+}
+
+/// Message of the last failure on calling thread, or null if nothing failed yet.
+///
+/// The message is valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn rig_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |msg| msg.as_ptr()))
+}
+
+/// Compile `body` written in `style`, e.g. `tera` or `giter8`. Returns null on failure.
+///
+/// Malformed placeholders are reported by `rig_template_render`, not here.
+///
+/// # Safety
+///
+/// `style` and `body` must be null or valid C strings.
+#[no_mangle]
+pub unsafe extern "C" fn rig_template_compile(style: *const c_char,
+                                              body: *const c_char)
+                                              -> *mut Template {
+    let compiled = to_str(style, "style").and_then(|name| {
+        let style = match Style::from_name(name) {
+            Some(style) => style,
+            None => bail!("Unknown style {:?}", name),
+        };
+        Ok(Template::read_str(style, to_str(body, "body")?))
+    });
+    match compiled {
+        Ok(tpl) => Box::into_raw(Box::new(tpl)),
+        Err(e) => {
+            set_last_error(&e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Free template from `rig_template_compile`. Null is ignored.
+///
+/// # Safety
+///
+/// `tpl` must not be used after this call.
+#[no_mangle]
+pub unsafe extern "C" fn rig_template_free(tpl: *mut Template) {
+    if !tpl.is_null() {
+        drop(Box::from_raw(tpl));
+    }
+}
+
+/// Create empty parameters.
+#[no_mangle]
+pub extern "C" fn rig_params_new() -> *mut RigParams {
+    Box::into_raw(Box::new(RigParams(HashMap::new())))
+}
+
+/// Set parameter `name` to `value`, replacing the old value. Returns 0, or -1 on failure.
+///
+/// # Safety
+///
+/// `params` must be null or from `rig_params_new`, and strings null or valid C strings.
+#[no_mangle]
+pub unsafe extern "C" fn rig_params_set(params: *mut RigParams,
+                                        name: *const c_char,
+                                        value: *const c_char)
+                                        -> c_int {
+    let set = to_str(name, "name").and_then(|name| {
+        let value = to_str(value, "value")?;
+        match params.as_mut() {
+            Some(params) => params.0.insert(name.to_owned(), value.to_owned()),
+            None => bail!("params is null"),
+        };
+        Ok(())
+    });
+    match set {
+        Ok(()) => 0,
+        Err(e) => {
+            set_last_error(&e);
+            -1
+        }
+    }
+}
+
+/// Free parameters from `rig_params_new`. Null is ignored.
+///
+/// # Safety
+///
+/// `params` must not be used after this call.
+#[no_mangle]
+pub unsafe extern "C" fn rig_params_free(params: *mut RigParams) {
+    if !params.is_null() {
+        drop(Box::from_raw(params));
+    }
+}
+
+/// Render `tpl` with `params` into `buf` of `len` bytes, NUL terminated.
+///
+/// Returns length of the whole output without NUL, or -1 on failure. Output not fitting in `buf`
+/// is truncated like `snprintf` does, so call again with a larger buffer if the result is not
+/// less than `len`. `buf` may be null where `len` is 0, to find the length needed.
+///
+/// # Safety
+///
+/// `tpl` and `params` must be null or live objects from this interface, and `buf` must be null
+/// or have room for `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rig_template_render(tpl: *const Template,
+                                             params: *const RigParams,
+                                             buf: *mut c_char,
+                                             len: usize)
+                                             -> isize {
+    let rendered = match (tpl.as_ref(), params.as_ref()) {
+        (Some(tpl), Some(params)) => {
+            let mut out = Vec::with_capacity(tpl.size_hint());
+            let written = tpl.write_to(&mut out, &params.0).map(|_| ());
+            written.map(|()| out)
+        }
+        _ => Err("template or params is null".into()),
+    };
+    let out = match rendered {
+        Ok(out) => out,
+        Err(e) => {
+            set_last_error(&e);
+            return -1;
+        }
+    };
+
+    if !buf.is_null() && len > 0 {
+        let n = out.len().min(len - 1);
+        ptr::copy_nonoverlapping(out.as_ptr(), buf as *mut u8, n);
+        *buf.offset(n as isize) = 0;
+    }
+    out.len() as isize
+}
@@ -3,13 +3,16 @@ use std::collections::HashMap;
 use serde_json::value::{Value, to_value};
 use tera::{ErrorKind, Result};
 
-use super::format::{format, Formatter};
+use super::format::{format, validate, Formatter};
 
 macro_rules! convert_tera_filter {
     ( $($fn_name:ident, $name:expr, $fv:ident);+ ) => {
         $(
             pub fn $fn_name(value: Value, _: HashMap<String, Value>) -> Result<Value> {
                 let s = try_get_value!($name, "value", String, value);
+                if let Some(reason) = validate(&s, Formatter::$fv) {
+                    bail!("Filter `{}` cannot be applied to {:?}: {}", $name, s, reason);
+                }
                 to_value(format(&s, Formatter::$fv)).map_err(|e| ErrorKind::Json(e).into())
             }
         )*
@@ -19,6 +22,7 @@ macro_rules! convert_tera_filter {
 convert_tera_filter! {
     decap, "decap", Decapitalize;
     word, "word", WordChar;
+    space, "space", WordSpace;
     hyphen, "hyphen", Hyphenate;
     start, "start", StartCase;
     upper_camel, "Camel", UpperCamel;
@@ -26,5 +30,11 @@ convert_tera_filter! {
     norm, "norm", Normalize;
     snake, "snake", SnakeCase;
     packaged, "packaged", DirectoryPath;
-    random, "random", AddRandom
+    random, "random", AddRandom;
+    kebab_case, "kebab", KebabCase;
+    snake_case, "snake_case", LowerSnakeCase;
+    camel_case, "camelCase", CamelCase;
+    pascal_case, "PascalCase", PascalCase;
+    screaming_snake_case, "SCREAMING_SNAKE_CASE", ScreamingSnakeCase;
+    train_case, "train", TrainCase
 }
\ No newline at end of file
@@ -1,9 +1,18 @@
 use std::ascii::AsciiExt;
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::convert::From;
+use std::fmt;
 
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use chrono::format::{Item, StrftimeItems};
 use rand::{thread_rng, Rng};
+use unicode_normalization::{is_nfc, is_nfd, UnicodeNormalization};
+use unicode_normalization::char::is_combining_mark;
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+/// Formatter applied to values, written as its name in templates, followed by arguments in
+/// parentheses if it takes any, e.g. `truncate(8)` or `replace("-SNAPSHOT", "")`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Ord, PartialOrd)]
 pub enum Formatter {
     Ident,
     UpperCase,
@@ -13,23 +22,64 @@ pub enum Formatter {
     StartCase,
     Hyphenate,
     WordChar,
+    WordSpace,
     UpperCamel,
     LowerCamel,
     Normalize,
     SnakeCase,
     DirectoryPath,
     AddRandom,
+    /// `kebab-case`, lowercase words joined with hyphens.
+    KebabCase,
+    /// `snake_case`, lowercase words joined with underscores. Unlike `SnakeCase` of giter8, the
+    /// value is split at case changes as well as delimiters.
+    LowerSnakeCase,
+    /// `camelCase`, the first word in lowercase and the rest capitalized, joined as is.
+    CamelCase,
+    /// `PascalCase`, capitalized words joined as is.
+    PascalCase,
+    /// `SCREAMING_SNAKE_CASE`, uppercase words joined with underscores.
+    ScreamingSnakeCase,
+    /// `Train-Case`, capitalized words joined with hyphens.
+    TrainCase,
+    /// Unicode Normalization Form C, composing letters and their accents into single characters
+    /// where they can be, e.g. before identifier-style formatters.
+    Nfc,
+    /// Unicode Normalization Form D, decomposing letters and their accents.
+    Nfd,
+    /// Keep at most the number of characters, counting accents with their letters.
+    Truncate(usize),
+    /// Append spaces until the value has the number of characters, counted as `Truncate` does.
+    PadRight(usize),
+    /// Prepend spaces until the value has the number of characters, counted as `Truncate` does.
+    PadLeft(usize),
+    /// Replace every occurrence of the first string with the second.
+    Replace(String, String),
+    /// Format date or datetime, e.g. TOML datetime or `now` parameter, with `strftime`-like
+    /// specifiers of `chrono`, e.g. `%Y-%m-%d`.
+    Date(String),
 }
 
+/// Formatters of unknown names, or of wrong arguments, are `Formatter::Ident`.
 impl<'a> From<&'a str> for Formatter {
     fn from(s: &str) -> Formatter {
+        if let Some(open) = s.find('(') {
+            let (name, args) = (s[..open].trim(), &s[open + 1..]);
+            if !args.ends_with(')') {
+                return Formatter::Ident;
+            }
+            return parse_args(&args[..args.len() - 1])
+                .and_then(|args| with_args(name, args))
+                .unwrap_or(Formatter::Ident);
+        }
         match s {
             "lower" | "lowercase" => Formatter::LowerCase,
             "upper" | "uppercase" => Formatter::UpperCase,
             "cap" | "capitalize" => Formatter::Capitalize,
             "decap" | "decapitalize" => Formatter::Decapitalize,
             "word" | "word-only" => Formatter::WordChar,
-            "hyphen" | "hyphnate" => Formatter::Hyphenate,
+            "space" | "word-space" => Formatter::WordSpace,
+            "hyphen" | "hyphenate" | "hyphnate" => Formatter::Hyphenate,
             "start" | "start-case" => Formatter::StartCase,
 
             "Camel" | "upper-camel" => Formatter::UpperCamel,
@@ -42,11 +92,182 @@ impl<'a> From<&'a str> for Formatter {
             "random" |
             "generate-random" => Formatter::AddRandom,
 
+            "kebab" | "kebab-case" => Formatter::KebabCase,
+            "snake_case" => Formatter::LowerSnakeCase,
+            "camelCase" => Formatter::CamelCase,
+            "pascal" | "PascalCase" => Formatter::PascalCase,
+            "screaming-snake" | "SCREAMING_SNAKE_CASE" => Formatter::ScreamingSnakeCase,
+            "train" | "Train-Case" => Formatter::TrainCase,
+
+            "nfc" => Formatter::Nfc,
+            "nfd" => Formatter::Nfd,
+
             _ => Formatter::Ident,
         }
     }
 }
 
+/// Formatter of `name` taking `args`, if it exists and the arguments suit it.
+pub(crate) fn with_args(name: &str, args: Vec<String>) -> Option<Formatter> {
+    let width = if args.len() == 1 { args[0].parse::<usize>().ok() } else { None };
+    Some(match (name, width) {
+        ("truncate", Some(width)) => Formatter::Truncate(width),
+        ("pad", Some(width)) |
+        ("pad-right", Some(width)) => Formatter::PadRight(width),
+        ("pad-left", Some(width)) => Formatter::PadLeft(width),
+        ("replace", _) if args.len() == 2 => {
+            let mut args = args.into_iter();
+            Formatter::Replace(args.next()?, args.next()?)
+        }
+        ("date", _) if args.len() == 1 => Formatter::Date(args.into_iter().next()?),
+        _ => return None,
+    })
+}
+
+/// Split arguments of formatter by commas, unquoting strings and dropping the names of named
+/// arguments, e.g. `8` of `length=8`.
+fn parse_args(s: &str) -> Option<Vec<String>> {
+    let mut args = Vec::new();
+    let mut rest = s.trim();
+    while !rest.is_empty() {
+        if let Some(eq) = rest.find('=') {
+            if rest[..eq].chars().all(|c| c.is_alphanumeric() || c == '_' || c == ' ') {
+                rest = rest[eq + 1..].trim_start();
+            }
+        }
+        let (arg, after) = match rest.chars().next() {
+            Some(quote) if quote == '"' || quote == '\'' => {
+                let end = rest[1..].find(quote)? + 1;
+                (&rest[1..end], &rest[end + 1..])
+            }
+            _ => {
+                let end = rest.find(',').unwrap_or(rest.len());
+                (rest[..end].trim(), &rest[end..])
+            }
+        };
+        args.push(arg.to_owned());
+        let after = after.trim_start();
+        rest = if after.starts_with(',') {
+            after[1..].trim_start()
+        } else if after.is_empty() {
+            after
+        } else {
+            return None;
+        };
+    }
+    Some(args)
+}
+
+impl Formatter {
+    /// Every formatter applicable to values without arguments, in their short names.
+    pub fn all() -> &'static [Formatter] {
+        &[Formatter::LowerCase,
+          Formatter::UpperCase,
+          Formatter::Capitalize,
+          Formatter::Decapitalize,
+          Formatter::WordChar,
+          Formatter::WordSpace,
+          Formatter::Hyphenate,
+          Formatter::StartCase,
+          Formatter::UpperCamel,
+          Formatter::LowerCamel,
+          Formatter::Normalize,
+          Formatter::SnakeCase,
+          Formatter::DirectoryPath,
+          Formatter::AddRandom,
+          Formatter::KebabCase,
+          Formatter::LowerSnakeCase,
+          Formatter::CamelCase,
+          Formatter::PascalCase,
+          Formatter::ScreamingSnakeCase,
+          Formatter::TrainCase,
+          Formatter::Nfc,
+          Formatter::Nfd]
+    }
+
+    /// Short name of the formatter, as written in templates.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Formatter::Ident => "ident",
+            Formatter::LowerCase => "lower",
+            Formatter::UpperCase => "upper",
+            Formatter::Capitalize => "cap",
+            Formatter::Decapitalize => "decap",
+            Formatter::WordChar => "word",
+            Formatter::WordSpace => "space",
+            Formatter::Hyphenate => "hyphen",
+            Formatter::StartCase => "start",
+            Formatter::UpperCamel => "Camel",
+            Formatter::LowerCamel => "camel",
+            Formatter::Normalize => "norm",
+            Formatter::SnakeCase => "snake",
+            Formatter::DirectoryPath => "packaged",
+            Formatter::AddRandom => "random",
+            Formatter::KebabCase => "kebab-case",
+            Formatter::LowerSnakeCase => "snake_case",
+            Formatter::CamelCase => "camelCase",
+            Formatter::PascalCase => "PascalCase",
+            Formatter::ScreamingSnakeCase => "SCREAMING_SNAKE_CASE",
+            Formatter::TrainCase => "Train-Case",
+            Formatter::Nfc => "nfc",
+            Formatter::Nfd => "nfd",
+            Formatter::Truncate(_) => "truncate",
+            Formatter::PadRight(_) => "pad",
+            Formatter::PadLeft(_) => "pad-left",
+            Formatter::Replace(..) => "replace",
+            Formatter::Date(_) => "date",
+        }
+    }
+}
+
+/// Formatters defined by users, applied to values under the names they are registered with,
+/// e.g. `include-guard` turning `my-crate` into `MY_CRATE_H`.
+#[derive(Default)]
+pub struct FormatterRegistry {
+    formatters: HashMap<String, Box<Fn(&str) -> String + Send + Sync>>,
+}
+
+impl fmt::Debug for FormatterRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.formatters.keys()).finish()
+    }
+}
+
+impl FormatterRegistry {
+    pub fn new() -> FormatterRegistry {
+        FormatterRegistry::default()
+    }
+
+    /// Register `f` under `name`, replacing the formatter registered under the same name.
+    ///
+    /// Registered formatters take precedence over built-in ones of the same name.
+    pub fn register<S, F>(&mut self, name: S, f: F)
+        where S: Into<String>,
+              F: Fn(&str) -> String + Send + Sync + 'static
+    {
+        self.formatters.insert(name.into(), Box::new(f));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.formatters.is_empty()
+    }
+
+    /// Whether any formatter is registered under `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.formatters.contains_key(name)
+    }
+
+    /// Names formatters are registered under, in no particular order.
+    pub fn names(&self) -> Vec<&str> {
+        self.formatters.keys().map(|k| k.as_str()).collect()
+    }
+
+    /// Apply the formatter registered under `name` to `s`, if any.
+    pub fn apply(&self, name: &str, s: &str) -> Option<String> {
+        self.formatters.get(name).map(|f| f(s))
+    }
+}
+
 fn process_words<F>(s: &str, f: F) -> String
     where F: FnMut(&str) -> String
 {
@@ -60,10 +281,10 @@ fn process_words<F>(s: &str, f: F) -> String
 fn join_camel_case(s: &str, cap: bool) -> String {
     if let Some(_) = s.find(' ') {
         let mut words = s.split_whitespace();
-        let head = if cap {
-            capitalize(words.next().unwrap())
-        } else {
-            (words.next().unwrap()).to_lowercase()
+        let head = match words.next() {
+            Some(w) if cap => capitalize(w),
+            Some(w) => w.to_lowercase(),
+            None => String::new(),
         };
         let tail = words.map(capitalize).collect::<Vec<_>>().concat();
         word_chars_only(&(head + &tail))
@@ -76,7 +297,23 @@ fn capitalize(s: &str) -> String {
     let mut chars = s.chars();
     match chars.next() {
         None => "".into(),
-        Some(f) => f.to_uppercase().collect::<String>() + &chars.as_str().to_lowercase(),
+        Some(f) => titlecase(f) + &chars.as_str().to_lowercase(),
+    }
+}
+
+/// Titlecase of `c`, which differs from its uppercase for digraphs, e.g. `ǅ` of `ǆ`, and for
+/// letters uppercased into several, e.g. `Ss` of `ß`.
+fn titlecase(c: char) -> String {
+    match c {
+        '\u{1C4}'..='\u{1C6}' => '\u{1C5}'.to_string(),
+        '\u{1C7}'..='\u{1C9}' => '\u{1C8}'.to_string(),
+        '\u{1CA}'..='\u{1CC}' => '\u{1CB}'.to_string(),
+        '\u{1F1}'..='\u{1F3}' => '\u{1F2}'.to_string(),
+        _ => {
+            let mut upper = c.to_uppercase();
+            let first = upper.next().map(|u| u.to_string()).unwrap_or_default();
+            first + &upper.collect::<String>().to_lowercase()
+        }
     }
 }
 
@@ -88,10 +325,93 @@ fn decapitalize(s: &str) -> String {
     }
 }
 
+/// Letters, digits and `_`, with accents following them.
+fn is_word_char(c: char) -> bool {
+    c == '_' || c.is_alphanumeric() || is_combining_mark(c)
+}
+
+/// Characters of `s`, each with the accents following it as combining marks, e.g. `e` and
+/// U+0301 of `é` in NFD, so formatters never separate letters from their accents.
+fn clusters(s: &str) -> Vec<&str> {
+    let mut clusters = Vec::new();
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        if i > start && !is_combining_mark(c) {
+            clusters.push(&s[start..i]);
+            start = i;
+        }
+    }
+    if start < s.len() {
+        clusters.push(&s[start..]);
+    }
+    clusters
+}
+
 fn word_chars_only(s: &str) -> String {
-    s.chars()
-        .filter(|c| c.is_ascii() && (*c == '_' || c.is_alphanumeric()))
-        .collect::<String>()
+    s.chars().filter(|c| is_word_char(*c)).collect::<String>()
+}
+
+/// Words of `s` for case conversion, split at characters but letters, digits and accents, where
+/// lowercase letter or digit is followed by uppercase one, and before the last letter of
+/// uppercase run followed by lowercase one, e.g. `http`, `Server` and `V2` of `httpServer_V2`
+/// or `HTTP` and `Server` of `HTTPServer`.
+fn split_words(s: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let delimiter = |c: char| c == '_' || !is_word_char(c);
+    for part in s.split(delimiter).filter(|p| !p.is_empty()) {
+        // accents never start words, nor tell case changes
+        let chars = part.char_indices()
+            .filter(|&(_, c)| !is_combining_mark(c))
+            .collect::<Vec<_>>();
+        let mut start = 0;
+        for i in 1..chars.len() {
+            let (at, c) = chars[i];
+            let prev = chars[i - 1].1;
+            let next_lower = chars.get(i + 1).map_or(false, |&(_, n)| n.is_lowercase());
+            if c.is_uppercase() && (!prev.is_uppercase() || next_lower) {
+                words.push(&part[start..at]);
+                start = at;
+            }
+        }
+        words.push(&part[start..]);
+    }
+    words
+}
+
+/// Join words of `s` with `sep`, after converting each with `first` for the first word and
+/// with `rest` for the others.
+fn join_words(s: &str, sep: &str, first: fn(&str) -> String, rest: fn(&str) -> String) -> String {
+    let words = split_words(s);
+    let mut joined = String::with_capacity(s.len());
+    for (i, w) in words.into_iter().enumerate() {
+        if i > 0 {
+            joined.push_str(sep);
+            joined.push_str(&rest(w));
+        } else {
+            joined.push_str(&first(w));
+        }
+    }
+    joined
+}
+
+/// Convert `s` with case formatter `f`, e.g. `Formatter::KebabCase`.
+fn convert_case(s: &str, f: &Formatter) -> String {
+    match *f {
+        Formatter::KebabCase => join_words(s, "-", str::to_lowercase, str::to_lowercase),
+        Formatter::LowerSnakeCase => join_words(s, "_", str::to_lowercase, str::to_lowercase),
+        Formatter::CamelCase => join_words(s, "", str::to_lowercase, capitalize),
+        Formatter::PascalCase => join_words(s, "", capitalize, capitalize),
+        Formatter::ScreamingSnakeCase => {
+            join_words(s, "_", str::to_uppercase, str::to_uppercase)
+        }
+        Formatter::TrainCase => join_words(s, "-", capitalize, capitalize),
+        _ => s.to_owned(),
+    }
+}
+
+/// Replace every non-word character with a space, as `word-space` of giter8 does.
+fn word_space(s: &str) -> String {
+    s.chars().map(|c| if is_word_char(c) { c } else { ' ' }).collect()
 }
 
 fn normalize(s: &str) -> String {
@@ -132,6 +452,24 @@ fn dedup_whitespace(s: &str) -> String {
     s.split_whitespace().collect::<Vec<_>>().join(" ")
 }
 
+/// `s` read as RFC 3339 datetime, local datetime or date, formatted with `fmt`, if it is one and
+/// `fmt` has no unknown specifiers.
+fn format_date(s: &str, fmt: &str) -> Option<String> {
+    let items = StrftimeItems::new(fmt);
+    if items.clone().any(|item| match item { Item::Error => true, _ => false }) {
+        return None;
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Some(dt.format_with_items(items).to_string());
+    }
+    let local = NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f")
+        .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f"));
+    if let Ok(dt) = local {
+        return Some(dt.format_with_items(items).to_string());
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").ok().map(|d| d.format_with_items(items).to_string())
+}
+
 fn add_random(s: &str) -> String {
     let mut r = thread_rng();
     let mut s = s.to_string();
@@ -140,15 +478,111 @@ fn add_random(s: &str) -> String {
     s
 }
 
-/// format a `&str` sentence to `String`.
-pub fn format(s: &str, f: Formatter) -> String {
+/// Check whether `f` can be meaningfully applied on `s`, telling the reason if not.
+///
+/// Empty value is always accepted.
+pub fn validate(s: &str, f: Formatter) -> Option<&'static str> {
+    if s.is_empty() {
+        return None;
+    }
     match f {
+        Formatter::WordChar | Formatter::UpperCamel | Formatter::LowerCamel
+            if word_chars_only(s).is_empty() => {
+            Some("value has no word characters, so the result would be empty")
+        }
+        Formatter::KebabCase | Formatter::LowerSnakeCase | Formatter::CamelCase |
+        Formatter::PascalCase | Formatter::ScreamingSnakeCase | Formatter::TrainCase
+            if split_words(s).is_empty() => {
+            Some("value has no letters nor digits, so the result would be empty")
+        }
+        Formatter::DirectoryPath if s.contains('/') || s.contains('\\') => {
+            Some("package name must be separated with `.`, not with path separators")
+        }
+        Formatter::DirectoryPath if s.starts_with('.') || s.ends_with('.') ||
+                                    s.split('.').any(|seg| !seg.is_empty() && seg.trim().is_empty()) => {
+            Some("package name has empty segment")
+        }
+        Formatter::Date(ref fmt) if format_date(s, fmt).is_none() => {
+            Some("value is not a date nor a datetime, or format has unknown specifiers")
+        }
+        _ => None,
+    }
+}
+
+fn is_lowercase(s: &str) -> bool {
+    s.chars().all(|c| c.to_lowercase().eq(Some(c)))
+}
+
+fn is_uppercase(s: &str) -> bool {
+    s.chars().all(|c| c.to_uppercase().eq(Some(c)))
+}
+
+fn is_capitalized(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().map(|c| titlecase(c) == c.to_string()).unwrap_or(true) &&
+    is_lowercase(chars.as_str())
+}
+
+fn is_decapitalized(s: &str) -> bool {
+    s.chars().next().map(|c| c.to_lowercase().eq(Some(c))).unwrap_or(true)
+}
+
+/// Words separated by exactly one space, without leading nor trailing whitespaces.
+fn is_single_spaced(s: &str) -> bool {
+    s.split(' ').all(|w| !w.is_empty() && !w.contains(char::is_whitespace))
+}
+
+/// Tell whether applying `f` would give `s` back as is, without formatting it.
+fn is_unchanged(s: &str, f: &Formatter) -> bool {
+    match *f {
+        Formatter::Ident => true,
+        Formatter::LowerCase => is_lowercase(s),
+        Formatter::UpperCase => is_uppercase(s),
+        Formatter::Capitalize => is_capitalized(s),
+        Formatter::Decapitalize => is_decapitalized(s),
+        Formatter::StartCase => is_single_spaced(s) && s.split(' ').all(is_capitalized),
+        Formatter::WordChar => {
+            is_single_spaced(s) && s.chars().all(|c| c == ' ' || is_word_char(c))
+        }
+        Formatter::WordSpace => s.chars().all(|c| c == ' ' || is_word_char(c)),
+        Formatter::Hyphenate => !s.contains(char::is_whitespace),
+        Formatter::UpperCamel | Formatter::LowerCamel => s.chars().all(is_word_char),
+        Formatter::Normalize => !s.contains(char::is_whitespace) && is_lowercase(s),
+        Formatter::SnakeCase => !s.contains(|c: char| c == '.' || c == '-' || c.is_whitespace()),
+        Formatter::DirectoryPath => !s.contains('.'),
+        Formatter::AddRandom => false,
+        Formatter::KebabCase | Formatter::LowerSnakeCase | Formatter::CamelCase |
+        Formatter::PascalCase | Formatter::ScreamingSnakeCase | Formatter::TrainCase => {
+            convert_case(s, f) == s
+        }
+        Formatter::Nfc => is_nfc(s),
+        Formatter::Nfd => is_nfd(s),
+        Formatter::Truncate(width) => clusters(s).len() <= width,
+        Formatter::PadRight(width) | Formatter::PadLeft(width) => clusters(s).len() >= width,
+        Formatter::Replace(ref from, _) => from.is_empty() || !s.contains(from.as_str()),
+        Formatter::Date(_) => false,
+    }
+}
+
+/// format a `&str` sentence, borrowing `s` as is if the formatter doesn't change it.
+///
+/// Empty value is formatted into empty value, unless the formatter pads it.
+pub fn format(s: &str, f: Formatter) -> Cow<str> {
+    let pads = match f {
+        Formatter::PadRight(_) | Formatter::PadLeft(_) => true,
+        _ => false,
+    };
+    if (s.is_empty() && !pads) || is_unchanged(s, &f) {
+        return Cow::Borrowed(s);
+    }
+    Cow::Owned(match f {
         Formatter::LowerCase => s.to_lowercase(),
         Formatter::UpperCase => s.to_uppercase(),
         Formatter::Capitalize => capitalize(s),
         Formatter::Decapitalize => decapitalize(s),
         Formatter::StartCase => process_words(s, capitalize),
         Formatter::WordChar => process_words(s, word_chars_only),
+        Formatter::WordSpace => word_space(s),
         Formatter::Hyphenate => dedup_whitespace(s).replace(" ", "-"),
         Formatter::UpperCamel => join_camel_case(s, true),
         Formatter::LowerCamel => join_camel_case(s, false),
@@ -156,6 +590,17 @@ pub fn format(s: &str, f: Formatter) -> String {
         Formatter::SnakeCase => snake_case(s),
         Formatter::DirectoryPath => directory_path(s),
         Formatter::AddRandom => add_random(s),
-        _ => s.into(),
-    }
+        Formatter::KebabCase | Formatter::LowerSnakeCase | Formatter::CamelCase |
+        Formatter::PascalCase | Formatter::ScreamingSnakeCase | Formatter::TrainCase => {
+            convert_case(s, &f)
+        }
+        Formatter::Nfc => s.nfc().collect(),
+        Formatter::Nfd => s.nfd().collect(),
+        Formatter::Truncate(width) => clusters(s).into_iter().take(width).collect(),
+        Formatter::PadRight(width) => s.to_owned() + &" ".repeat(width - clusters(s).len()),
+        Formatter::PadLeft(width) => " ".repeat(width - clusters(s).len()) + s,
+        Formatter::Replace(ref from, ref to) => s.replace(from.as_str(), to),
+        Formatter::Date(ref fmt) => format_date(s, fmt).unwrap_or_else(|| s.to_owned()),
+        Formatter::Ident => s.into(),
+    })
 }
@@ -1,12 +1,43 @@
 /// File system utilities copied from rustup-utils crate.
 /// https://github.com/rust-lang-nursery/rustup.rs/tree/master/src/rustup-utils
 
+use std::env;
+use std::fmt;
 use std::fs;
-use std::io;
-use std::path::Path;
+use std::io::{self, Seek, SeekFrom};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "mmap")]
+use std::str;
+#[cfg(feature = "mmap")]
+use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
+
+use super::errors::*;
+
+/// Files at least this large are mapped into memory by `read_text` instead of being read.
+#[cfg(feature = "mmap")]
+pub const MMAP_THRESHOLD: u64 = 1 << 20;
+
+/// Extensions of files copied as they are without being read, such as images, fonts and
+/// archives.
+pub const BINARY_EXTENSIONS: &'static [&'static str] = &["png", "jpg", "jpeg", "gif", "bmp",
+                                                         "ico", "webp", "ttf", "otf", "woff",
+                                                         "woff2", "eot", "jar", "class", "zip",
+                                                         "gz", "tgz", "xz", "7z", "pdf", "so",
+                                                         "dll", "dylib", "exe"];
+
+/// Bytes at the head of file `looks_binary` inspects, as many as git does.
+const BINARY_PROBE_LEN: u64 = 8000;
+
+pub fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME").or_else(|| env::var_os("USERPROFILE")).map(PathBuf::from)
+}
+
 pub fn exists<P: AsRef<Path>>(path: P) -> bool {
     fs::metadata(path).is_ok()
 }
@@ -15,50 +46,256 @@ pub fn is_directory<P: AsRef<Path>>(path: P) -> bool {
     fs::metadata(path).ok().as_ref().map(fs::Metadata::is_dir) == Some(true)
 }
 
-pub fn read_file(path: &Path) -> io::Result<String> {
-    let mut file = try!(fs::OpenOptions::new()
+/// Whether an operation made a new entry on file system, or found it already there.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Outcome {
+    Created,
+    /// Entry existed, and file contents are overwritten.
+    Existed,
+    /// File existed, and is left as it is.
+    Skipped,
+    /// File existed, and is moved to `backup_path` before written.
+    BackedUp,
+}
+
+/// File written by `write_file`, `write_with` or `copy_dir`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Written {
+    pub path: PathBuf,
+    pub bytes: u64,
+    pub outcome: Outcome,
+}
+
+/// Error describing the `operation` failed on `path`, to be chained on the cause.
+fn failed(operation: &'static str, path: &Path) -> ErrorKind {
+    ErrorKind::FsFailure(operation, path.to_path_buf())
+}
+
+/// Open file at `path` for writing, truncating it if already exists.
+fn create_file(path: &Path) -> Result<(fs::File, Outcome)> {
+    match fs::OpenOptions::new().write(true).create_new(true).open(path) {
+        Ok(file) => Ok((file, Outcome::Created)),
+        Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            let file = try!(fs::OpenOptions::new()
+                .write(true)
+                .truncate(true)
+                .open(path)
+                .chain_err(|| failed("creating file", path)));
+            Ok((file, Outcome::Existed))
+        }
+        Err(e) => Err(e).chain_err(|| failed("creating file", path)),
+    }
+}
+
+/// Text contents of a file, either read into memory or mapped with `mmap` feature.
+#[derive(Clone)]
+pub struct Text(Contents);
+
+#[derive(Clone)]
+enum Contents {
+    Heap(String),
+    /// Validated as UTF-8 when mapped.
+    #[cfg(feature = "mmap")]
+    Mapped(Arc<Mmap>),
+}
+
+impl Deref for Text {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        match self.0 {
+            Contents::Heap(ref s) => s,
+            #[cfg(feature = "mmap")]
+            Contents::Mapped(ref map) => unsafe { str::from_utf8_unchecked(map) },
+        }
+    }
+}
+
+impl From<String> for Text {
+    fn from(s: String) -> Text {
+        Text(Contents::Heap(s))
+    }
+}
+
+impl PartialEq for Text {
+    fn eq(&self, other: &Text) -> bool {
+        **self == **other
+    }
+}
+
+impl fmt::Debug for Text {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+pub fn read_file(path: &Path) -> Result<String> {
+    let file = try!(open_file(path));
+    read_opened(file, path)
+}
+
+fn open_file(path: &Path) -> Result<fs::File> {
+    fs::OpenOptions::new()
         .read(true)
-        .open(path));
+        .open(path)
+        .chain_err(|| failed("opening file", path))
+}
+
+fn read_opened(mut file: fs::File, path: &Path) -> Result<String> {
+    let mut contents = Vec::new();
+    try!(io::Read::read_to_end(&mut file, &mut contents)
+        .chain_err(|| failed("reading file", path)));
+    String::from_utf8(contents).map_err(|e| {
+        ErrorKind::InvalidUtf8(path.to_path_buf(), e.utf8_error().valid_up_to()).into()
+    })
+}
+
+/// Same as `read_file`, but maps files larger than `MMAP_THRESHOLD` with `mmap` feature.
+///
+/// Mapped file must not be modified while the `Text` is alive.
+pub fn read_text(path: &Path) -> Result<Text> {
+    let file = try!(open_file(path));
+    #[cfg(feature = "mmap")]
+    {
+        let len = try!(file.metadata().chain_err(|| failed("reading metadata of", path))).len();
+        if len >= MMAP_THRESHOLD {
+            let map = try!(unsafe { Mmap::map(&file) }.chain_err(|| failed("mapping file", path)));
+            if let Err(e) = str::from_utf8(&map) {
+                bail!(ErrorKind::InvalidUtf8(path.to_path_buf(), e.valid_up_to()));
+            }
+            return Ok(Text(Contents::Mapped(Arc::new(map))));
+        }
+    }
+    read_opened(file, path).map(Text::from)
+}
 
-    let mut contents = String::new();
-    try!(io::Read::read_to_string(&mut file, &mut contents));
-    Ok(contents)
+/// Whether `bytes` seem not a text, having NUL in their first 8000 bytes.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.iter().take(BINARY_PROBE_LEN as usize).any(|&b| b == 0)
 }
 
-pub fn write_file(path: &Path, contents: &str) -> io::Result<()> {
-    let mut file = try!(fs::OpenOptions::new()
-        .write(true)
-        .truncate(true)
-        .create(true)
-        .open(path));
+/// Whether the file at `path` seems not a text, reading only its head as `is_binary` inspects.
+pub fn looks_binary(path: &Path) -> Result<bool> {
+    let file = try!(open_file(path));
+    let mut head = Vec::new();
+    try!(io::Read::read_to_end(&mut io::Read::take(file, BINARY_PROBE_LEN), &mut head)
+        .chain_err(|| failed("reading file", path)));
+    Ok(is_binary(&head))
+}
+
+/// Copy file at `src` to `dest` byte for byte, overwriting it if already exists.
+pub fn copy_file(src: &Path, dest: &Path) -> Result<Written> {
+    let outcome = if exists(dest) { Outcome::Existed } else { Outcome::Created };
+    let bytes = try!(fs::copy(src, dest).chain_err(|| failed("copying file to", dest)));
+    Ok(Written {
+        path: dest.to_path_buf(),
+        bytes: bytes,
+        outcome: outcome,
+    })
+}
 
-    try!(io::Write::write_all(&mut file, contents.as_bytes()));
-    try!(file.sync_data());
-    Ok(())
+pub fn write_file(path: &Path, contents: &str) -> Result<Written> {
+    write_with(path, |file| {
+        io::Write::write_all(file, contents.as_bytes()).chain_err(|| failed("writing file", path))
+    })
+}
+
+/// Create or truncate file at `path`, and let `write` fill it.
+///
+/// Errors from `write` are returned as is. Bytes written are counted from the file offset after
+/// `write` returns, so it should not seek backwards.
+pub fn write_with<F>(path: &Path, write: F) -> Result<Written>
+    where F: FnOnce(&mut fs::File) -> Result<()>
+{
+    let (mut file, outcome) = try!(create_file(path));
+    try!(write(&mut file));
+    let bytes = try!(file.seek(SeekFrom::Current(0)).chain_err(|| failed("writing file", path)));
+    try!(file.sync_data().chain_err(|| failed("writing file", path)));
+    Ok(Written {
+        path: path.to_path_buf(),
+        bytes: bytes,
+        outcome: outcome,
+    })
+}
+
+/// Give file at `dest` the permissions of `src`, e.g. executable bits of scripts.
+pub fn copy_permissions(src: &Path, dest: &Path) -> Result<()> {
+    let permissions = try!(fs::metadata(src).chain_err(|| failed("reading metadata of", src)))
+        .permissions();
+    fs::set_permissions(dest, permissions).chain_err(|| failed("changing permissions of", dest))
+}
+
+/// Where `backup` moves the file at `path`, e.g. `README.md.bak`.
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map(|s| s.to_os_string()).unwrap_or_default();
+    name.push(".bak");
+    path.with_file_name(name)
+}
+
+/// Move the file at `path` to `backup_path`, replacing any previous backup.
+pub fn backup(path: &Path) -> Result<PathBuf> {
+    let backup = backup_path(path);
+    try!(fs::rename(path, &backup).chain_err(|| failed("backing up", path)));
+    Ok(backup)
+}
+
+/// Create directory at `path` along with its parents, telling whether it was already there.
+pub fn create_dir_all(path: &Path) -> Result<Outcome> {
+    if path.as_os_str().is_empty() {
+        return Ok(Outcome::Existed);
+    }
+    match fs::create_dir(path) {
+        Ok(()) => Ok(Outcome::Created),
+        Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists && is_directory(path) => {
+            Ok(Outcome::Existed)
+        }
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound && path.parent().is_some() => {
+            try!(create_dir_all(path.parent().unwrap_or(path)));
+            match fs::create_dir(path) {
+                Ok(()) => Ok(Outcome::Created),
+                // created by someone else in the meantime
+                Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists && is_directory(path) => {
+                    Ok(Outcome::Existed)
+                }
+                Err(e) => Err(e).chain_err(|| failed("creating directory", path)),
+            }
+        }
+        Err(e) => Err(e).chain_err(|| failed("creating directory", path)),
+    }
 }
 
-pub fn copy_dir(src: &Path, dest: &Path) -> io::Result<()> {
-    try!(fs::create_dir(dest));
-    for entry in try!(src.read_dir()) {
-        let entry = try!(entry);
-        let kind = try!(entry.file_type());
+/// Copy `src` recursively into `dest`, which must not exist. Returns every file copied.
+pub fn copy_dir(src: &Path, dest: &Path) -> Result<Vec<Written>> {
+    try!(fs::create_dir(dest).chain_err(|| failed("creating directory", dest)));
+    let mut copied = Vec::new();
+    for entry in try!(src.read_dir().chain_err(|| failed("reading directory", src))) {
+        let entry = try!(entry.chain_err(|| failed("reading directory", src)));
+        let kind = try!(entry.file_type().chain_err(|| failed("reading metadata of", &entry.path())));
         let src = entry.path();
         let dest = dest.join(entry.file_name());
         if kind.is_dir() {
-            try!(copy_dir(&src, &dest));
+            copied.extend(try!(copy_dir(&src, &dest)));
         } else {
-            try!(fs::copy(&src, &dest));
+            let bytes = try!(fs::copy(&src, &dest).chain_err(|| failed("copying file to", &dest)));
+            copied.push(Written {
+                path: dest,
+                bytes: bytes,
+                outcome: Outcome::Created,
+            });
         }
     }
-    Ok(())
+    Ok(copied)
 }
 
-pub fn remove_dir(path: &Path) -> io::Result<()> {
-    if try!(fs::symlink_metadata(path)).file_type().is_symlink() {
+pub fn remove_dir(path: &Path) -> Result<()> {
+    let metadata = try!(fs::symlink_metadata(path).chain_err(|| failed("reading metadata of", path)));
+    if metadata.file_type().is_symlink() {
         if cfg!(windows) {
-            fs::remove_dir(path)
+            fs::remove_dir(path).chain_err(|| failed("removing symlink", path))
         } else {
-            fs::remove_file(path)
+            fs::remove_file(path).chain_err(|| failed("removing symlink", path))
         }
     } else {
         let mut result = Ok(());
@@ -74,11 +311,12 @@ pub fn remove_dir(path: &Path) -> io::Result<()> {
     }
 }
 
-fn rm_rf(path: &Path) -> io::Result<()> {
+fn rm_rf(path: &Path) -> Result<()> {
     if path.exists() {
-        for file in fs::read_dir(path).unwrap() {
-            let file = try!(file);
-            let is_dir = try!(file.file_type()).is_dir();
+        for file in try!(fs::read_dir(path).chain_err(|| failed("reading directory", path))) {
+            let file = try!(file.chain_err(|| failed("reading directory", path)));
+            let is_dir = try!(file.file_type().chain_err(|| failed("reading metadata of", &file.path())))
+                .is_dir();
             let ref file = file.path();
 
             if is_dir {
@@ -88,16 +326,18 @@ fn rm_rf(path: &Path) -> io::Result<()> {
                 match fs::remove_file(file) {
                     Ok(()) => {}
                     Err(ref e) if cfg!(windows) && e.kind() == io::ErrorKind::PermissionDenied => {
-                        let mut p = file.metadata().unwrap().permissions();
+                        let mut p = try!(file.metadata().chain_err(|| failed("reading metadata of", file)))
+                            .permissions();
                         p.set_readonly(false);
-                        fs::set_permissions(file, p).unwrap();
-                        try!(fs::remove_file(file));
+                        try!(fs::set_permissions(file, p)
+                            .chain_err(|| failed("changing permissions of", file)));
+                        try!(fs::remove_file(file).chain_err(|| failed("removing file", file)));
                     }
-                    Err(e) => return Err(e),
+                    Err(e) => return Err(e).chain_err(|| failed("removing file", file)),
                 }
             }
         }
-        fs::remove_dir(path)
+        fs::remove_dir(path).chain_err(|| failed("removing directory", path))
     } else {
         Ok(())
     }
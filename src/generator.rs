@@ -0,0 +1,193 @@
+//! Project generation on `tokio`, for async services scaffolding trees without blocking their
+//! runtime.
+//!
+//! Only built with `tokio` feature.
+
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use futures::future::{self, Either, FutureExt, TryFutureExt};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use futures::Future;
+use tokio::fs;
+
+use super::diagnostics::Diagnostics;
+use super::errors::*;
+use super::fsutils::{self, Outcome, Written};
+use super::project::Project;
+use super::template::{FormatCache, Params, Style};
+
+/// Number of files a `Generator` has in flight by default.
+pub const DEFAULT_CONCURRENCY: usize = 16;
+
+/// Generates projects from templates as `Project::generate` does, with `tokio::fs`.
+///
+/// Each file is rendered on its own as `Project::render_file` does, so Handlebars partials are
+/// not resolved.
+#[derive(Clone, Debug)]
+pub struct Generator {
+    project: Arc<Project>,
+    /// Number of files read, rendered or written at once.
+    pub concurrency: usize,
+}
+
+impl Generator {
+    pub fn new(project: Project) -> Generator {
+        Generator {
+            project: Arc::new(project),
+            concurrency: DEFAULT_CONCURRENCY,
+        }
+    }
+
+    pub fn project(&self) -> &Project {
+        &self.project
+    }
+
+    /// Generate files from the template at `clone_root` into `dest`, resolving to every file
+    /// written in the order `Project::generate` reports them.
+    ///
+    /// Nothing is written with `dry_run`, and files are rendered in memory as `Project::preview`
+    /// does instead.
+    pub fn generate(&self,
+                    params: &Params,
+                    clone_root: &Path,
+                    dest: &Path,
+                    dry_run: bool)
+                    -> impl Future<Output = Result<Vec<Written>>> + Send + 'static {
+        self.generate_with(params, clone_root, dest, dry_run, &mut Diagnostics::new())
+    }
+
+    /// Same as `generate`, collecting warnings into `diag`.
+    ///
+    /// The template tree is walked and checked when this is called, so warnings are collected
+    /// and the call fails before returning the future. Files are read and written by the future.
+    pub fn generate_with(&self,
+                         params: &Params,
+                         clone_root: &Path,
+                         dest: &Path,
+                         dry_run: bool,
+                         diag: &mut Diagnostics)
+                         -> impl Future<Output = Result<Vec<Written>>> + Send + 'static {
+
+        if dry_run {
+            let previewed = self.project.preview_with(params, clone_root, dest, diag);
+            return Either::Left(future::ready(previewed));
+        }
+        let planned = self.project.engines(clone_root).and_then(|engines| {
+            let cache = self.project.format_cache();
+            let plan = self.project.plan(params, clone_root, dest, diag, &cache)?;
+            Ok((engines, plan))
+        });
+        let (engines, mut plan) = match planned {
+            Ok(planned) => planned,
+            Err(e) => return Either::Left(future::ready(Err(e))),
+        };
+
+        let mut dirs = vec![dest.to_path_buf()];
+        let mut files = Vec::new();
+        for (src, target) in mem::replace(&mut plan.tree, Vec::new()) {
+            if src.file_type().is_file() {
+                let file = src.path().strip_prefix(&plan.root).unwrap_or(src.path()).to_path_buf();
+                let style = engines.style_of(&file, &self.project.style);
+                files.push((src.path().to_path_buf(), file, target, style));
+            } else if src.file_type().is_dir() {
+                dirs.push(target);
+            }
+        }
+
+        let project = self.project.clone();
+        let params = Arc::new(params.clone());
+        let concurrency = self.concurrency.max(1);
+        // directories are created one by one in order, so that every parent is there first
+        let created = stream::iter(dirs)
+            .then(|dir| {
+                fs::create_dir_all(dir.clone()).map(move |r| {
+                    r.chain_err(|| ErrorKind::FsFailure("creating directory", dir))
+                })
+            })
+            .try_collect::<Vec<()>>();
+        let backed_up = stream::iter(plan.backups.clone())
+            .then(|file| {
+                fs::rename(file.clone(), fsutils::backup_path(&file)).map(move |r| {
+                    r.chain_err(|| ErrorKind::FsFailure("backing up", file))
+                })
+            })
+            .try_collect::<Vec<()>>();
+        Either::Right(created.and_then(move |_| backed_up).and_then(move |_| {
+            stream::iter(files)
+                .map(move |(src, file, target, style)| {
+                    generate_file(project.clone(), params.clone(), src, file, target, style)
+                })
+                .buffered(concurrency)
+                .try_collect::<Vec<Written>>()
+                .map_ok(move |written| plan.report(written))
+        }))
+    }
+}
+
+/// Read template file at `src`, named `file` from template root, and write it rendered to `dest`.
+///
+/// Files `Project::is_verbatim` would judge so are written as they are read.
+fn generate_file(project: Arc<Project>,
+                 params: Arc<Params>,
+                 src: PathBuf,
+                 file: PathBuf,
+                 dest: PathBuf,
+                 style: Style)
+                 -> impl Future<Output = Result<Written>> + Send + 'static {
+    let template = src.clone();
+    fs::read(src.clone())
+        .map({
+            let src = src.clone();
+            move |r| r.chain_err(|| ErrorKind::FsFailure("reading file", src))
+        })
+        .and_then(move |body| {
+            if project.has_verbatim_extension(&file) || fsutils::is_binary(&body) {
+                return future::ready(Ok(body));
+            }
+            let root = src.ancestors()
+                .nth(file.components().count())
+                .unwrap_or(&src)
+                .to_path_buf();
+            let rendered = String::from_utf8(body)
+                .map_err(|e| ErrorKind::InvalidUtf8(src, e.utf8_error().valid_up_to()).into())
+                .and_then(|body| project.render_file(&root, style, &file, &body, &params))
+                .map(String::into_bytes);
+            future::ready(rendered)
+        })
+        .and_then(move |rendered| {
+            fs::metadata(dest.clone()).then(move |existing| {
+                let outcome = if existing.is_ok() {
+                    Outcome::Existed
+                } else {
+                    Outcome::Created
+                };
+                let bytes = rendered.len() as u64;
+                fs::write(dest.clone(), rendered).map(move |r| -> Result<Written> {
+                    r.chain_err(|| ErrorKind::FsFailure("writing file", dest.clone()))?;
+                    Ok(Written {
+                        path: dest,
+                        bytes: bytes,
+                        outcome: outcome,
+                    })
+                })
+            })
+        })
+        .and_then(move |written| keep_permissions(template, written))
+}
+
+/// Give the file `written` the permissions of template file `src`, as `Project::generate` does.
+fn keep_permissions(src: PathBuf,
+                    written: Written)
+                    -> impl Future<Output = Result<Written>> + Send + 'static {
+    fs::metadata(src.clone())
+        .map(move |r| r.chain_err(|| ErrorKind::FsFailure("reading metadata of", src)))
+        .and_then(move |metadata| {
+            let dest = written.path.clone();
+            fs::set_permissions(dest.clone(), metadata.permissions()).map(move |r| {
+                r.chain_err(|| ErrorKind::FsFailure("changing permissions of", dest))?;
+                Ok(written)
+            })
+        })
+}
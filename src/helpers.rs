@@ -0,0 +1,70 @@
+use std::io::Write;
+use std::sync::Arc;
+
+use handlebars::{Handlebars, Helper, HelperDef, RenderContext, RenderError};
+use rustc_serialize::json::Json;
+
+use super::format::{format, validate, Formatter, FormatterRegistry};
+
+/// Handlebars helper applying a formatter to its parameter, e.g. `{{ snake name }}`.
+pub struct FormatHelper(pub Formatter);
+
+impl HelperDef for FormatHelper {
+    fn call(&self, h: &Helper, _: &Handlebars, rc: &mut RenderContext) -> Result<(), RenderError> {
+        let name = self.0.name();
+        let value = match h.param(0).map(|p| p.value()) {
+            Some(&Json::String(ref s)) => s,
+            _ => {
+                return Err(RenderError::new(format!("Helper `{}` needs a string parameter", name)))
+            }
+        };
+        if let Some(reason) = validate(value, self.0.clone()) {
+            return Err(RenderError::new(format!("Helper `{}` cannot be applied to {:?}: {}",
+                                                name,
+                                                value,
+                                                reason)));
+        }
+        rc.writer.write_all(format(value, self.0.clone()).as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Register every formatter as a helper of the same name.
+pub fn register(hbs: &mut Handlebars) {
+    for f in Formatter::all() {
+        hbs.register_helper(f.name(), Box::new(FormatHelper(f.clone())));
+    }
+}
+
+/// Handlebars helper applying the formatter registered under its name.
+struct RegisteredHelper {
+    name: String,
+    registry: Arc<FormatterRegistry>,
+}
+
+impl HelperDef for RegisteredHelper {
+    fn call(&self, h: &Helper, _: &Handlebars, rc: &mut RenderContext) -> Result<(), RenderError> {
+        let value = match h.param(0).map(|p| p.value()) {
+            Some(&Json::String(ref s)) => s,
+            _ => {
+                return Err(RenderError::new(format!("Helper `{}` needs a string parameter",
+                                                    self.name)))
+            }
+        };
+        let formatted = self.registry.apply(&self.name, value).unwrap_or_else(|| value.clone());
+        rc.writer.write_all(formatted.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Register every formatter of `registry` as a helper of its name, replacing the built-in one of
+/// the same name.
+pub fn register_all(hbs: &mut Handlebars, registry: &Arc<FormatterRegistry>) {
+    for name in registry.names() {
+        let helper = RegisteredHelper {
+            name: name.to_owned(),
+            registry: registry.clone(),
+        };
+        hbs.register_helper(name, Box::new(helper));
+    }
+}
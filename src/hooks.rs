@@ -0,0 +1,92 @@
+//! Commands run in generated project after generation, declared in `[hooks]` table of TOML config.
+//!
+//! ```toml
+//! [hooks]
+//! post = ["git init", "cargo fmt"]
+//! ```
+//!
+//! Commands run through the shell in the output directory, with every parameter given as
+//! environment variable named by `params::param_env_name`, e.g. `RIG_PARAM_name`.
+
+use std::path::Path;
+use std::process::Command;
+
+use toml;
+use toml::value::{Table, Value};
+
+use super::errors::*;
+use super::fsutils;
+use super::params::param_env_name;
+use super::template::{Params, HOOKS_TABLE};
+
+/// Hooks declared by a template.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Hooks {
+    /// Commands run after generation, in order.
+    pub post: Vec<String>,
+}
+
+impl Hooks {
+    /// Read `[hooks]` table of TOML config `file`, if it has one.
+    pub fn read(file: &Path) -> Result<Hooks> {
+        let s = fsutils::read_file(file)?;
+        let toml = toml::from_str::<Table>(&s).chain_err(|| ErrorKind::TomlDecodeFailure)?;
+        Hooks::from_toml(&toml)
+    }
+
+    pub fn from_toml(toml: &Table) -> Result<Hooks> {
+        let table = match toml.get(HOOKS_TABLE) {
+            Some(value) => {
+                match value.as_table() {
+                    Some(table) => table,
+                    None => bail!("`{}` must be a table of commands", HOOKS_TABLE),
+                }
+            }
+            None => return Ok(Hooks::default()),
+        };
+        let mut hooks = Hooks::default();
+        for (key, value) in table {
+            let commands: Option<Vec<String>> = match *value {
+                Value::Array(ref items) => {
+                    items.iter().map(|item| item.as_str().map(|s| s.to_owned())).collect()
+                }
+                _ => None,
+            };
+            match (key.as_str(), commands) {
+                ("post", Some(commands)) => hooks.post = commands,
+                ("post", None) => bail!("`post` of `{}` must be an array of strings", HOOKS_TABLE),
+                _ => bail!("Unknown key `{}` in `{}`", key, HOOKS_TABLE),
+            }
+        }
+        Ok(hooks)
+    }
+
+    /// Run `post` hooks in `dir` one by one with `params`, stopping at the first one failed with
+    /// `ErrorKind::HookFailed`.
+    pub fn run_post(&self, dir: &Path, params: &Params) -> Result<()> {
+        for command in &self.post {
+            info!("Running hook in {:?}: {}", dir, command);
+            let status = shell(command)
+                .current_dir(dir)
+                .envs(params.param_map.iter().map(|(k, v)| (param_env_name(k), v)))
+                .status()
+                .chain_err(|| ErrorKind::HookFailed(command.clone(), None))?;
+            if !status.success() {
+                bail!(ErrorKind::HookFailed(command.clone(), status.code()));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn shell(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    }
+}
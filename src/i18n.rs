@@ -0,0 +1,155 @@
+//! Catalogs of prompts, warnings and CLI messages, in the language of user's locale.
+//!
+//! Messages have positional arguments written `{0}`, `{1}` and so on, as word order differs
+//! between languages.
+
+use std::env;
+
+use super::diagnostics::Warning;
+
+/// Language messages are shown in.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Lang {
+    En,
+    Ja,
+}
+
+impl Default for Lang {
+    fn default() -> Lang {
+        Lang::En
+    }
+}
+
+impl Lang {
+    /// Language of `RIG_LANG` if set, or of the locale in `LC_ALL`, `LC_MESSAGES` and `LANG` in
+    /// that order, falling back to English.
+    pub fn from_env() -> Lang {
+        ["RIG_LANG", "LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|locale| !locale.is_empty())
+            .and_then(|locale| Lang::from_locale(&locale))
+            .unwrap_or_default()
+    }
+
+    /// Language of locale name like `ja_JP.UTF-8` or `en`, if it has a catalog.
+    pub fn from_locale(locale: &str) -> Option<Lang> {
+        let lang = locale.split(|c: char| c == '_' || c == '-' || c == '.').next().unwrap_or("");
+        match &*lang.to_lowercase() {
+            "en" | "c" | "posix" => Some(Lang::En),
+            "ja" => Some(Lang::Ja),
+            _ => None,
+        }
+    }
+}
+
+/// Message in catalogs.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Message {
+    /// Asking value of parameter `{0}`, with its default `{1}`.
+    Prompt,
+    /// Asking what to do with existing file `{0}`.
+    OverwritePrompt,
+    /// Project is generated into `{0}`.
+    Generated,
+    /// File `{0}` is created.
+    Created,
+    /// Warning `{0}`, as printed.
+    WarningLine,
+    /// Error `{0}`, as printed.
+    ErrorLine,
+    /// Cause `{0}` of the error above, as printed.
+    CausedBy,
+    DroppedParam,
+    SuspiciousPath,
+    EmptyValue,
+}
+
+impl Message {
+    /// Text of the message in `lang`, with its placeholders.
+    pub fn text(&self, lang: Lang) -> &'static str {
+        match lang {
+            Lang::En => {
+                match *self {
+                    Message::Prompt => "{0} [{1}]:",
+                    Message::OverwritePrompt => {
+                        "{0} already exists. Overwrite, skip or back it up? [o/S/b]:"
+                    }
+                    Message::Generated => "Project successfully generated: {0}",
+                    Message::Created => "Created {0}",
+                    Message::WarningLine => "warning: {0}",
+                    Message::ErrorLine => "error: {0}",
+                    Message::CausedBy => "caused by: {0}",
+                    Message::DroppedParam => {
+                        "value of `{0}` is neither a scalar nor an array of scalars, so it is \
+                         ignored"
+                    }
+                    Message::SuspiciousPath => {
+                        "{0} is rendered as `{1}`, which has characters not portable across \
+                         platforms"
+                    }
+                    Message::EmptyValue => "parameter `{0}` has empty value",
+                }
+            }
+            Lang::Ja => {
+                match *self {
+                    Message::Prompt => "{0} [{1}]:",
+                    Message::OverwritePrompt => {
+                        "{0} は既に存在します。上書き、スキップ、バックアップのどれにしますか? \
+                         [o/S/b]:"
+                    }
+                    Message::Generated => "プロジェクトを生成しました: {0}",
+                    Message::Created => "{0} を作成しました",
+                    Message::WarningLine => "警告: {0}",
+                    Message::ErrorLine => "エラー: {0}",
+                    Message::CausedBy => "原因: {0}",
+                    Message::DroppedParam => "`{0}` の値はスカラーでもスカラーの配列でもないため無視されます",
+                    Message::SuspiciousPath => {
+                        "{0} は `{1}` として出力されますが、プラットフォーム間で使えない文字を\
+                         含んでいます"
+                    }
+                    Message::EmptyValue => "パラメータ `{0}` の値が空です",
+                }
+            }
+        }
+    }
+
+    /// Message in `lang` with `args` put into its placeholders.
+    ///
+    /// Placeholders without argument are left as is, and arguments are never parsed again.
+    pub fn format(&self, lang: Lang, args: &[&str]) -> String {
+        let text = self.text(lang);
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find('{') {
+            out.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+            let index = after.find('}')
+                .and_then(|end| after[..end].parse::<usize>().ok().map(|i| (end, i)));
+            match index {
+                Some((end, i)) if i < args.len() => {
+                    out.push_str(args[i]);
+                    rest = &after[end + 1..];
+                }
+                _ => {
+                    out.push('{');
+                    rest = after;
+                }
+            }
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+/// Describe `warning` in `lang`.
+pub fn warning(lang: Lang, warning: &Warning) -> String {
+    match *warning {
+        Warning::DroppedParam(ref key) => Message::DroppedParam.format(lang, &[key.as_str()]),
+        Warning::SuspiciousPath { ref file, ref rendered } => {
+            let file = file.display().to_string();
+            Message::SuspiciousPath.format(lang, &[&file, rendered.as_str()])
+        }
+        Warning::EmptyValue(ref name) => Message::EmptyValue.format(lang, &[name.as_str()]),
+    }
+}
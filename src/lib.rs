@@ -1,28 +1,127 @@
 #![recursion_limit = "1024"]
 
+extern crate chrono;
 extern crate combine;
-extern crate env_logger;
 #[macro_use]
 extern crate error_chain;
+#[cfg(feature = "archive")]
+extern crate flate2;
+#[cfg(feature = "tokio")]
+extern crate futures;
+#[cfg(feature = "fs")]
 extern crate git2;
+extern crate glob;
+#[cfg(feature = "handlebars")]
+extern crate handlebars;
+#[cfg(feature = "fs")]
 extern crate java_properties;
+#[cfg(feature = "liquid")]
+extern crate liquid;
 #[macro_use]
 extern crate log;
+extern crate memchr;
+#[cfg(feature = "mmap")]
+extern crate memmap2;
+#[cfg(feature = "test-util")]
+#[macro_use]
+extern crate proptest;
 extern crate rand;
+extern crate regex;
+#[cfg(feature = "derive")]
+extern crate rig_derive;
 extern crate rustc_serialize;
+#[cfg(feature = "serde")]
 extern crate serde;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde_derive;
+#[cfg(any(feature = "tera", feature = "wasm", feature = "json"))]
 extern crate serde_json;
+#[cfg(feature = "yaml")]
+extern crate serde_yaml;
+#[cfg(feature = "archive")]
+extern crate tar;
+#[cfg(feature = "fs")]
 extern crate tempdir;
+#[cfg(feature = "tera")]
 #[macro_use]
 extern crate tera;
+#[cfg(feature = "tokio")]
+extern crate tokio;
 extern crate toml;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+extern crate unicode_normalization;
+#[cfg(feature = "fs")]
 extern crate url;
+#[cfg(feature = "fs")]
 extern crate walkdir;
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
+#[cfg(feature = "archive")]
+extern crate zip;
+
+#[cfg(feature = "derive")]
+pub use rig_derive::VtolParams;
+
+/// Enter a `tracing` span of given level, name and fields until the end of the enclosing block,
+/// e.g. `trace_span!(DEBUG, "render", file = %path.display())`.
+///
+/// Expands to nothing without `tracing` feature, so fields are not evaluated then.
+#[cfg(feature = "tracing")]
+macro_rules! trace_span {
+    ($level:ident, $($arg:tt)*) => {
+        let _span = ::tracing::span!(::tracing::Level::$level, $($arg)*).entered();
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace_span {
+    ($($arg:tt)*) => {};
+}
 
+#[cfg(feature = "archive")]
+pub mod archive;
+#[cfg(feature = "fs")]
+pub mod cache;
+pub mod diagnostics;
+#[cfg(feature = "fs")]
+pub mod diff;
 pub mod errors;
+pub mod expr;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "tera")]
 pub mod filters;
 pub mod format;
 pub mod fsutils;
+#[cfg(feature = "tokio")]
+pub mod generator;
+#[cfg(feature = "handlebars")]
+pub mod helpers;
+#[cfg(feature = "fs")]
+pub mod hooks;
+pub mod i18n;
+#[cfg(feature = "liquid")]
+pub mod liquid_filters;
+pub mod manifest;
+pub mod output;
+pub mod params;
 pub mod parser;
+#[cfg(feature = "pretty-diagnostics")]
+pub mod pretty;
+#[cfg(feature = "fs")]
 pub mod project;
+pub mod settings;
+pub mod skeleton;
+#[cfg(feature = "fs")]
+pub mod source;
+#[cfg(feature = "test-util")]
+pub mod strategies;
 pub mod template;
+#[cfg(feature = "fs")]
+pub mod testing;
+#[cfg(feature = "fs")]
+pub mod update;
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -0,0 +1,52 @@
+use std::sync::Arc;
+
+use liquid::{Context, FilterError, Value};
+
+use super::format::{format, validate, Formatter, FormatterRegistry};
+
+/// Apply `f` to the input of Liquid filter, e.g. `{{ name | snake }}`.
+fn apply(f: &Formatter, input: &Value) -> ::std::result::Result<Value, FilterError> {
+    let s = match *input {
+        Value::Str(ref s) => s,
+        _ => {
+            return Err(FilterError::InvalidType(format!("Filter `{}` expects a string",
+                                                        f.name())))
+        }
+    };
+    if let Some(reason) = validate(s, f.clone()) {
+        return Err(FilterError::InvalidType(format!("Filter `{}` cannot be applied to {:?}: {}",
+                                                    f.name(),
+                                                    s,
+                                                    reason)));
+    }
+    Ok(Value::Str(format(s, f.clone()).into_owned()))
+}
+
+/// Register every formatter as a filter of the same name.
+pub fn register(ctx: &mut Context) {
+    for f in Formatter::all() {
+        let f = f.clone();
+        ctx.add_filter(f.name(), Box::new(move |input: &Value, _: &[Value]| apply(&f, input)));
+    }
+}
+
+/// Apply the formatter registered under `name` to the input of Liquid filter.
+fn apply_registered(registry: &FormatterRegistry,
+                    name: &str,
+                    input: &Value)
+                    -> ::std::result::Result<Value, FilterError> {
+    match *input {
+        Value::Str(ref s) => Ok(Value::Str(registry.apply(name, s).unwrap_or_else(|| s.clone()))),
+        _ => Err(FilterError::InvalidType(format!("Filter `{}` expects a string", name))),
+    }
+}
+
+/// Register every formatter of `registry` as a filter of its name, replacing the built-in one of
+/// the same name.
+pub fn register_all(ctx: &mut Context, registry: &Arc<FormatterRegistry>) {
+    for name in registry.names() {
+        let (registry, filter) = (registry.clone(), name.to_owned());
+        let apply = move |input: &Value, _: &[Value]| apply_registered(&registry, &filter, input);
+        ctx.add_filter(name, Box::new(apply));
+    }
+}
@@ -1,4 +1,5 @@
 extern crate docopt;
+#[macro_use]
 extern crate error_chain;
 extern crate env_logger;
 extern crate git2;
@@ -7,26 +8,39 @@ extern crate java_properties;
 extern crate log;
 extern crate rustc_serialize;
 extern crate tempdir;
-extern crate url;
 
 extern crate rig;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashSet};
 use std::env;
-use std::io::{self, Write};
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
 use std::process::exit;
+use std::sync::Arc;
 
-use docopt::Docopt;
-use git2::{Config as Git2Config, FetchOptions};
-use git2::build::RepoBuilder;
+use docopt::{ArgvMap, Docopt};
+use git2::Config as Git2Config;
+use rustc_serialize::json::Json;
 use tempdir::TempDir;
-use url::Url;
 
+use rig::archive;
+use rig::cache;
+use rig::diagnostics::{Diagnostics, Warning};
+use rig::diff::{self, FileChange};
 use rig::errors::*;
 use rig::format::{format, Formatter};
-use rig::project::{Configuration, Project};
-use rig::template::Params;
+use rig::fsutils::{self, Outcome};
+use rig::i18n::{self, Lang, Message};
+#[cfg(feature = "pretty-diagnostics")]
+use rig::pretty::Diagnostic;
+use rig::params::{self, Origin, ParamsBuilder, NOW_PARAM};
+use rig::project::{read_params, write_answers, Configuration, Issue, OverwritePolicy, Project};
+use rig::settings::Settings;
+use rig::skeleton;
+use rig::source::{Checkout, Source};
+use rig::template::{MissingKeyBehavior, Params, Style, Template};
+use rig::testing::TemplateSuite;
+use rig::update::{self, State};
 
 const USAGE: &'static str = r#"
 Rig - Generate new project by cloning templates from git repository.
@@ -36,7 +50,16 @@ Rig - Generate new project by cloning templates from git repository.
   - giter8 compatibility features (e.g. maven directive) are not yet supported.
 
 Usage:
-    rig <repository> [options]
+    rig vars <repository> [options]
+    rig lint <repository> [options]
+    rig diff <repository> <directory> [options]
+    rig update <repository> [<directory>] [-d PARAM]... [options]
+    rig test <repository> [options]
+    rig list [--refresh]
+    rig init <directory> [options]
+    rig render [<file>] [-d PARAM]... [options]
+    rig new <repository> [-d PARAM]... [options]
+    rig <repository> [-d PARAM]... [options]
     rig (-h | --help)
     rig (-V | --version)
 
@@ -44,37 +67,217 @@ Options:
     -h, --help              Show help message
     -V, --version           Show version
     --name NAME             Specify project name (overrides default if any)
-    --output PATH           Specify output directory to generate project
+    --output PATH           Specify output directory to generate project, or archive file
+                            ending with `.zip`, `.tar.gz` or `.tgz` to pack it into
     --root PATH             Specify directory where template lives in repository
-    --verbatim EXTENSION    Comma separeted list of files exclude from template processing
+    --branch REF            Branch or tag of template repository to check out
+    --offline               Use templates in local cache, without connecting to remote
+    --refresh               Make `list` fetch cached templates whose remote branch has moved
+    --verbatim EXTENSION    Comma separated list of extensions of files copied as they are,
+                            in addition to images, fonts, archives and files containing NUL
     -p, --packaged          Force format `package` parameter value into directory tree
     -Y, --confirm           Use template default value to all parameters (Yes-To-All)
-    --dry-run               Show generation process to STDOUT, without producing any files
+    --no-input              Never prompt, and fail if any parameter has no value (for CI)
+    --yes                   Same as --no-input
+    --overwrite POLICY      What to do with files already in output directory: `overwrite`,
+                            `skip`, `backup` (to `<file>.bak`), `fail` or `prompt`
+                            [default: overwrite]
+    --no-hooks              Never run commands the template declares to run after generation
+    --dry-run               List files that would be created or overwritten with their sizes,
+                            without producing any files
+    --strict                Fail, listing all of them, if any placeholder has no value
+    --missing BEHAVIOR      How placeholders without value are rendered: `default` (their
+                            default value or name), `keep` (as written), `empty` or `error`
+                            (same as --strict) [default: default]
+    --deny-warnings         Treat warnings as errors, and stop before writing any file
+    --sanitize-paths        Replace `/`, `\`, `..` and NUL in rendered file names with `_`,
+                            instead of failing
+    -j, --jobs N            Number of files rendered in parallel, 0 for one per CPU [default: 0]
+    --answers FILE          Read answers to parameters from TOML file instead of asking
+    --record-answers FILE   Write resolved parameters to TOML file, to replay with --answers
     --giter8                Expects a giter8 template
+    --handlebars            Expects a template written in Handlebars syntax
+    --liquid                Expects a template written in Liquid syntax
+    --style STYLE           Syntax the template is written in: `tera`, `st` (or `giter8`),
+                            `handlebars` or `liquid`
+    --fixtures FILE         Parameters (in template config format) used by `test` command
+    --expected PATH         Expected output tree `test` command compares with. Without these,
+                            `test` runs cases in `.rig-tests` directory of the template
+    --apply                 Make `update` change files not modified since generated, instead of
+                            printing how they differ
+    -d, --define PARAM      Give parameter in `key=value` form, which is never asked.
+                            Parameters can also be given by `RIG_PARAM_<name>` environment
+                            variables, which --define overrides
+    --error-format FORMAT   Report errors and lint issues as `human` readable text or `json`,
+                            one object per line [default: human]
+
+Commands:
+    vars                    List placeholders the template uses, with formatters and files
+                            referencing them. <repository> can also be a local directory or file
+    lint                    Validate template files and config, reporting problems with positions.
+                            <repository> can also be a local directory
+    diff                    Render the template and show differences against existing <directory>
+    update                  Render the template with parameters recorded when <directory> (or
+                            current directory) was generated, and show differences against it.
+                            Files modified since generated are reported as conflicts
+    test                    Render the template with --fixtures into temporal directory, and
+                            compare it with --expected tree if given
+    list                    List cached templates and templates registered in user configuration
+    init                    Create skeleton of new template into <directory>. Template is named
+                            after the directory unless --name given
+    render                  Render single template <file> (or STDIN when omitted) to STDOUT
+    new                     Generate new project from <repository>, same as omitting command.
+                            <repository> can also be a local directory, or a `.zip`, `.tar.gz`
+                            or `.tgz` archive of template
 "#;
 
-#[derive(Debug, RustcDecodable)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum ErrorFormat {
+    Human,
+    Json,
+}
+
+#[derive(Debug)]
 struct Args {
+    cmd_vars: bool,
+    cmd_lint: bool,
+    cmd_diff: bool,
+    cmd_update: bool,
+    cmd_test: bool,
+    cmd_list: bool,
+    cmd_init: bool,
+    cmd_render: bool,
     arg_repository: String,
+    arg_directory: Option<String>,
+    arg_file: Option<String>,
     flag_name: Option<String>,
     flag_output: Option<String>,
     flag_root: Option<String>,
-    flag_verbatim: Option<String>, // unimplemented!
+    flag_branch: Option<String>,
+    flag_offline: bool,
+    flag_refresh: bool,
+    flag_verbatim: Option<String>,
     flag_packaged: bool,
     flag_confirm: bool,
+    flag_no_input: bool,
     flag_giter8: bool,
+    flag_handlebars: bool,
+    flag_liquid: bool,
+    flag_style: Option<Style>,
+    flag_overwrite: OverwritePolicy,
+    flag_no_hooks: bool,
     flag_dry_run: bool,
+    flag_strict: bool,
+    flag_missing: MissingKeyBehavior,
+    flag_deny_warnings: bool,
+    flag_sanitize_paths: bool,
+    flag_jobs: usize,
+    flag_answers: Option<String>,
+    flag_record_answers: Option<String>,
+    flag_fixtures: Option<String>,
+    flag_expected: Option<String>,
+    flag_apply: bool,
+    flag_define: Vec<String>,
+    flag_error_format: ErrorFormat,
     // flag_no_logo: bool, // I wish someday I could draw some logo
-    flag_help: bool,
     flag_version: bool,
 }
 
+impl Args {
+    fn from_argv(argv: &ArgvMap) -> ::std::result::Result<Args, docopt::Error> {
+        let error_format = match argv.get_str("--error-format") {
+            "human" => ErrorFormat::Human,
+            "json" => ErrorFormat::Json,
+            other => {
+                return Err(docopt::Error::Argv(format!("Unknown error format: {}", other)));
+            }
+        };
+        let style = match argv.get_str("--style") {
+            "" => None,
+            name => {
+                let style = Style::from_name(name);
+                if style.is_none() {
+                    return Err(docopt::Error::Argv(format!("Unknown template style: {}", name)));
+                }
+                style
+            }
+        };
+        let overwrite = match argv.get_str("--overwrite") {
+            "overwrite" => OverwritePolicy::Overwrite,
+            "skip" => OverwritePolicy::Skip,
+            "backup" => OverwritePolicy::Backup,
+            "fail" => OverwritePolicy::Fail,
+            "prompt" => OverwritePolicy::Prompt(Arc::new(ask_overwrite)),
+            other => {
+                return Err(docopt::Error::Argv(format!("Unknown overwrite policy: {}", other)));
+            }
+        };
+        let missing = match argv.get_str("--missing") {
+            "default" => MissingKeyBehavior::UseDefault,
+            "keep" => MissingKeyBehavior::KeepPlaceholder,
+            "empty" => MissingKeyBehavior::Empty,
+            "error" => MissingKeyBehavior::Error,
+            other => {
+                return Err(docopt::Error::Argv(format!("Unknown missing key behavior: {}", other)));
+            }
+        };
+        let jobs = argv.get_str("--jobs").parse().map_err(|_| {
+            docopt::Error::Argv(format!("Invalid number of jobs: {}", argv.get_str("--jobs")))
+        })?;
+        let opt = |key: &str| Some(argv.get_str(key)).filter(|s| !s.is_empty()).map(|s| s.to_owned());
+        Ok(Args {
+            cmd_vars: argv.get_bool("vars"),
+            cmd_lint: argv.get_bool("lint"),
+            cmd_diff: argv.get_bool("diff"),
+            cmd_update: argv.get_bool("update"),
+            cmd_test: argv.get_bool("test"),
+            cmd_list: argv.get_bool("list"),
+            cmd_init: argv.get_bool("init"),
+            cmd_render: argv.get_bool("render"),
+            arg_repository: argv.get_str("<repository>").to_owned(),
+            arg_directory: opt("<directory>"),
+            arg_file: opt("<file>"),
+            flag_name: opt("--name"),
+            flag_output: opt("--output"),
+            flag_root: opt("--root"),
+            flag_branch: opt("--branch"),
+            flag_offline: argv.get_bool("--offline"),
+            flag_refresh: argv.get_bool("--refresh"),
+            flag_verbatim: opt("--verbatim"),
+            flag_packaged: argv.get_bool("--packaged"),
+            flag_confirm: argv.get_bool("--confirm"),
+            flag_no_input: argv.get_bool("--no-input") || argv.get_bool("--yes"),
+            flag_giter8: argv.get_bool("--giter8"),
+            flag_handlebars: argv.get_bool("--handlebars"),
+            flag_liquid: argv.get_bool("--liquid"),
+            flag_style: style,
+            flag_overwrite: overwrite,
+            flag_no_hooks: argv.get_bool("--no-hooks"),
+            flag_dry_run: argv.get_bool("--dry-run"),
+            flag_strict: argv.get_bool("--strict"),
+            flag_missing: missing,
+            flag_deny_warnings: argv.get_bool("--deny-warnings"),
+            flag_sanitize_paths: argv.get_bool("--sanitize-paths"),
+            flag_jobs: jobs,
+            flag_answers: opt("--answers"),
+            flag_record_answers: opt("--record-answers"),
+            flag_fixtures: opt("--fixtures"),
+            flag_expected: opt("--expected"),
+            flag_apply: argv.get_bool("--apply"),
+            flag_define: argv.get_vec("--define").into_iter().map(|s| s.to_owned()).collect(),
+            flag_error_format: error_format,
+            flag_version: argv.get_bool("--version"),
+        })
+    }
+}
+
 fn main() {
 
     env_logger::init().unwrap();
 
-    let args: Args = Docopt::new(USAGE)
-        .and_then(|d| d.decode())
+    let args = Docopt::new(USAGE)
+        .and_then(|d| d.parse())
+        .and_then(|argv| Args::from_argv(&argv))
         .unwrap_or_else(|e| e.exit());
 
     debug!("{:?}", args);
@@ -84,129 +287,736 @@ fn main() {
         exit(0);
     }
 
-    // gather info of remote repository & networks
-    let url = normalize_url(&args.arg_repository).unwrap();
-    let mut repo = RepoBuilder::new();
-    if let Some(proxy_url) = find_proxy_url() {
+    let mut diag = Diagnostics::new();
+    diag.deny = args.flag_deny_warnings;
 
-        debug!("Proxy settings found, initializing fetch options.");
-
-        let mut proxy = git2::ProxyOptions::new();
-        proxy.url(proxy_url.as_ref());
-
-        let mut fetch = FetchOptions::new();
-        fetch.proxy_options(proxy);
+    let result = run(&args, &mut diag);
+    for warning in diag.warnings() {
+        report_warning(args.flag_error_format, warning);
+    }
+    match result {
+        Ok(code) => exit(code),
+        Err(e) => {
+            // only `render` reads the template from where the error can find it again
+            let source_dir = if args.cmd_render { Some(Path::new(".")) } else { None };
+            report_error(args.flag_error_format, &e, source_dir);
+            exit(1);
+        }
+    }
+}
 
-        repo.fetch_options(fetch);
-    } else {
-        debug!("No proxy settings found.")
+/// Run the command, returning exit status.
+fn run(args: &Args, diag: &mut Diagnostics) -> Result<i32> {
+    if args.cmd_list {
+        print_templates(args.flag_refresh)?;
+        return Ok(0);
     }
 
-    let clone_root = TempDir::new("rig__template").expect("Failed to create temporal directory");
-    info!("Cloning remote git repository: {:?} into {:?}",
-          url,
-          clone_root.path());
-    let _ = repo.clone(url.as_ref(), &clone_root.path()).unwrap();
+    if args.cmd_init {
+        init_template(args)?;
+        return Ok(0);
+    }
 
-    let project = if args.flag_giter8 {
+    let mut project = if args.flag_giter8 {
         Project::new_g8(Some("src/main/g8"))
     } else {
         Project::new(args.flag_root.as_ref(),
                      Configuration::Toml, // TODO: parameterize config format
                      args.flag_packaged)
     };
+    if args.flag_handlebars {
+        project.style = Style::Handlebars;
+    }
+    if args.flag_liquid {
+        project.style = Style::Liquid;
+    }
+    if let Some(ref style) = args.flag_style {
+        project.style = style.clone();
+    }
+    project.strict = args.flag_strict;
+    project.render_options.missing = args.flag_missing;
+    project.sanitize_paths = args.flag_sanitize_paths;
+    project.jobs = args.flag_jobs;
+    if let Some(ref verbatim) = args.flag_verbatim {
+        project.verbatim = verbatim.split(',').map(|ext| ext.trim().to_owned()).collect();
+    }
+    project.overwrite = args.flag_overwrite.clone();
 
-    let mut params = project
-        .default_params(&clone_root.path())
-        .unwrap_or(Params::minimal_req());
-    debug!("Read default context: {:?}", params);
+    if args.cmd_render {
+        render_file(&project, args)?;
+        return Ok(0);
+    }
 
-    if !args.flag_confirm {
-        collect_params(&args.flag_name, &mut params.param_map);
-        debug!("Context updated with user input: {:?}", params);
+    if args.cmd_vars {
+        print_vars(&project, args)?;
+        return Ok(0);
+    }
+
+    if args.cmd_lint {
+        let (clone_root, root) = template_root(args)?;
+        let issues = project.lint(&root)?;
+        let lint_root = project.resolve_root_dir(&root);
+        for issue in &issues {
+            match args.flag_error_format {
+                ErrorFormat::Human => print_issue(issue, &lint_root),
+                ErrorFormat::Json => println!("{}", issue_json(issue)),
+            }
+        }
+        drop(clone_root);
+        return Ok(if issues.is_empty() { 0 } else { 1 });
     }
 
+    if args.cmd_diff {
+        let (clone_root, root) = template_root(args)?;
+        let params = resolve_params(&project, &root, args, diag)?;
+        let existing = args.arg_directory.as_ref().map(|s| s.as_ref()).unwrap_or(".");
+        let changed = print_diff(&project, &params, &root, existing, diag)?;
+        drop(clone_root);
+        return Ok(if changed { 1 } else { 0 });
+    }
+
+    if args.cmd_update {
+        let (clone_root, root) = template_root(args)?;
+        let dir = Path::new(args.arg_directory.as_ref().map(|s| s.as_ref()).unwrap_or("."));
+        let ok = run_update(&project, &root, dir, args, diag)?;
+        drop(clone_root);
+        return Ok(if ok { 0 } else { 1 });
+    }
+
+    if args.cmd_test {
+        let (clone_root, root) = template_root(args)?;
+        let passed = run_test(&project, &root, args, diag)?;
+        drop(clone_root);
+        return Ok(if passed { 0 } else { 1 });
+    }
+
+    let (clone_root, root) = template_root(args)?;
+    let params = resolve_params(&project, &root, args, diag)?;
+
     // ensure we have real path to output directory
-    let output_dir = get_output_dir(&args.flag_output, params.get("name").unwrap());
+    let name = params.get("name").cloned().unwrap_or_default();
+    let output_dir = get_output_dir(&args.flag_output, &name)?;
     debug!("Set output directory: {:?}", output_dir);
 
-    project.generate(&params, &clone_root.path(), &output_dir, args.flag_dry_run).unwrap();
+    if args.flag_dry_run {
+        for file in project.preview_with(&params, &root, &output_dir, diag)? {
+            let action = match file.outcome {
+                Outcome::Created => "create",
+                Outcome::Existed => "overwrite",
+                Outcome::Skipped => "skip",
+                Outcome::BackedUp => "back up and overwrite",
+            };
+            println!("Would {} {} ({} bytes)", action, file.path.display(), file.bytes);
+        }
+        drop(clone_root);
+        return Ok(0);
+    }
+    // projects named as archives are generated into temporal directory, and packed from there
+    let packed = match archive::Format::of(&output_dir) {
+        Some(format) => {
+            let dir = TempDir::new("rig__packed")
+                .chain_err(|| "Failed to create temporal directory")?;
+            Some((dir, format))
+        }
+        None => None,
+    };
+    let project_dir = match packed {
+        Some((ref dir, _)) => dir.path().to_path_buf(),
+        None => output_dir.clone(),
+    };
+    let written = project.generate_with(&params, &root, &project_dir, false, diag)?;
+    let mut state = State::new(Some(&args.arg_repository),
+                               args.flag_branch.as_ref().map(|s| s.as_ref()),
+                               &params);
+    state.record(&project_dir, &written)?;
+    state.write(&project_dir)?;
+    if !args.flag_no_hooks {
+        project.hooks(&root)?.run_post(&project_dir, &params)?;
+    }
+    if let Some((dir, format)) = packed {
+        archive::pack(dir.path(), &output_dir, format)?;
+    }
 
-    println!("Project successfully generated: {:?}", &output_dir);
+    let output = format!("{:?}", &output_dir);
+    println!("{}", Message::Generated.format(Lang::from_env(), &[&output]));
     drop(clone_root);
+    Ok(0)
 }
 
-fn find_proxy_url() -> Option<Url> {
+/// Print error and its causes to STDERR in requested format.
+fn report_error(format: ErrorFormat, e: &Error, source_dir: Option<&Path>) {
+    let stderr = &mut io::stderr();
+    match format {
+        ErrorFormat::Human => print_error(stderr, e, source_dir),
+        ErrorFormat::Json => {
+            let mut obj = BTreeMap::new();
+            obj.insert("type".to_owned(), Json::String("error".to_owned()));
+            obj.insert("kind".to_owned(), Json::String(error_kind(e.kind()).to_owned()));
+            obj.insert("message".to_owned(), Json::String(e.to_string()));
+            let causes = e.iter().skip(1).map(|c| Json::String(c.to_string())).collect();
+            obj.insert("causes".to_owned(), Json::Array(causes));
+            match *e.kind() {
+                ErrorKind::MissingParams(ref names) => {
+                    let names = names.iter().map(|n| Json::String(n.clone())).collect();
+                    obj.insert("params".to_owned(), Json::Array(names));
+                }
+                ErrorKind::RenderFailure(ref file, position) => {
+                    obj.insert("file".to_owned(),
+                               Json::String(file.to_string_lossy().into_owned()));
+                    if let Some(pos) = position {
+                        obj.insert("line".to_owned(), Json::U64(pos.line as u64));
+                        obj.insert("column".to_owned(), Json::U64(pos.column as u64));
+                    }
+                }
+                _ => {}
+            }
+            let _ = writeln!(stderr, "{}", Json::Object(obj));
+        }
+    }
+}
 
-    // we take env vars first
-    if let Some(env_val) = env::var_os("http_proxy") {
-        debug!("Setting proxy configuration from environment key: `http_proxy`.");
-        Url::parse(&env_val.to_string_lossy()).ok()
-    } else {
-        // if no env vars set, look for git global config
-        if let Ok(global_conf) = Git2Config::find_global() {
+/// Print error and its causes, one per line.
+#[cfg(not(feature = "pretty-diagnostics"))]
+fn print_error(stderr: &mut Write, e: &Error, _source_dir: Option<&Path>) {
+    let lang = Lang::from_env();
+    let mut causes = e.iter();
+    if let Some(e) = causes.next() {
+        let _ = writeln!(stderr, "{}", Message::ErrorLine.format(lang, &[&e.to_string()]));
+    }
+    for cause in causes {
+        let _ = writeln!(stderr, "{}", Message::CausedBy.format(lang, &[&cause.to_string()]));
+    }
+}
+
+/// Print error quoting the template it failed at, looking up the file under `source_dir`.
+#[cfg(feature = "pretty-diagnostics")]
+fn print_error(stderr: &mut Write, e: &Error, source_dir: Option<&Path>) {
+    let diag = Diagnostic::from_error(e);
+    let source = match (source_dir, diag.file.as_ref()) {
+        (Some(dir), Some(file)) => fsutils::read_file(&dir.join(file)).ok(),
+        _ => None,
+    };
+    let _ = write!(stderr, "{}", diag.render(source.as_ref().map(|s| s.as_ref())));
+}
+
+/// Print lint issue in a single line.
+#[cfg(not(feature = "pretty-diagnostics"))]
+fn print_issue(issue: &Issue, _root: &Path) {
+    println!("{}", issue);
+}
+
+/// Print lint issue quoting the line of template file under `root`.
+#[cfg(feature = "pretty-diagnostics")]
+fn print_issue(issue: &Issue, root: &Path) {
+    let source = fsutils::read_file(&root.join(&issue.file)).ok();
+    println!("{}", Diagnostic::from_issue(issue).render(source.as_ref().map(|s| s.as_ref())));
+}
+
+/// Print warning to STDERR in requested format.
+fn report_warning(format: ErrorFormat, warning: &Warning) {
+    let stderr = &mut io::stderr();
+    match format {
+        ErrorFormat::Human => {
+            let lang = Lang::from_env();
+            let msg = i18n::warning(lang, warning);
+            let _ = writeln!(stderr, "{}", Message::WarningLine.format(lang, &[&msg]));
+        }
+        ErrorFormat::Json => {
+            let mut obj = BTreeMap::new();
+            obj.insert("type".to_owned(), Json::String("warning".to_owned()));
+            obj.insert("code".to_owned(), Json::String(warning.code().to_owned()));
+            obj.insert("message".to_owned(), Json::String(warning.to_string()));
+            let _ = writeln!(stderr, "{}", Json::Object(obj));
+        }
+    }
+}
+
+/// Stable identifier of error kind, for machine readable output.
+fn error_kind(kind: &ErrorKind) -> &'static str {
+    match *kind {
+        ErrorKind::Git(_) => "git",
+        ErrorKind::Glob(_) |
+        ErrorKind::Regex(_) => "config",
+        ErrorKind::Io(_) => "io",
+        ErrorKind::ParseUrl(_) |
+        ErrorKind::InvalidUrlFormat(_) => "url",
+        #[cfg(feature = "tera")]
+        ErrorKind::Tera(_) => "render",
+        #[cfg(feature = "handlebars")]
+        ErrorKind::HandlebarsRender(_) => "render",
+        #[cfg(feature = "handlebars")]
+        ErrorKind::HandlebarsTemplate(_) => "parse",
+        #[cfg(feature = "liquid")]
+        ErrorKind::Liquid(_) => "render",
+        #[cfg(feature = "json")]
+        ErrorKind::Json(_) => "config",
+        #[cfg(feature = "yaml")]
+        ErrorKind::Yaml(_) => "config",
+        ErrorKind::Toml(_) |
+        ErrorKind::TomlSer(_) |
+        ErrorKind::TomlDecodeFailure |
+        ErrorKind::PropertiesDecodeFailure |
+        ErrorKind::UnsupportedValue(_) |
+        ErrorKind::CyclicParams(_) => "config",
+        ErrorKind::Parse(..) |
+        ErrorKind::IncludeCycle(_) => "parse",
+        ErrorKind::Formatter(..) => "formatter",
+        ErrorKind::RenderFailure(..) => "render",
+        ErrorKind::IllegalPath(..) => "illegal-path",
+        ErrorKind::TemplateNotFound(_) |
+        ErrorKind::NotCached(_) => "not-found",
+        ErrorKind::InvalidUtf8(..) => "encoding",
+        ErrorKind::MissingParams(_) => "missing-params",
+        ErrorKind::HookFailed(..) => "hook",
+        ErrorKind::InvalidValue(..) => "invalid-value",
+        ErrorKind::DeniedWarnings(_) => "denied-warnings",
+        ErrorKind::FileExists(_) |
+        ErrorKind::DirectoryNotEmpty(_) => "conflict",
+        _ => "error",
+    }
+}
+
+fn issue_json(issue: &Issue) -> Json {
+    let mut obj = BTreeMap::new();
+    obj.insert("type".to_owned(), Json::String("issue".to_owned()));
+    obj.insert("code".to_owned(), Json::String(issue.kind.code().to_owned()));
+    obj.insert("message".to_owned(), Json::String(issue.kind.to_string()));
+    obj.insert("file".to_owned(),
+               Json::String(issue.file.to_string_lossy().into_owned()));
+    if let Some(pos) = issue.position {
+        obj.insert("line".to_owned(), Json::U64(pos.line as u64));
+        obj.insert("column".to_owned(), Json::U64(pos.column as u64));
+    }
+    Json::Object(obj)
+}
 
-            if let Ok(config) = Git2Config::open(global_conf.as_path()) {
-                config.get_string("http.proxy").map(|v| Url::parse(&v).unwrap()).ok()
+/// Read template defaults, overridden by `--answers`, `RIG_PARAM_*` environment variables and
+/// `--define` in this order, and ask user to fill the rest unless `--confirm` given. Derived
+/// parameters are evaluated last.
+fn resolve_params(project: &Project,
+                  root: &Path,
+                  args: &Args,
+                  diag: &mut Diagnostics)
+                  -> Result<Params> {
+    let defaults = project
+        .default_params_with(root, diag)
+        .unwrap_or(Params::minimal_req());
+    debug!("Read default context: {:?}", defaults);
+
+    let mut builder = ParamsBuilder::new();
+    builder.defaults(&defaults);
+    if let Some(ref file) = args.flag_answers {
+        builder.file(Path::new(file), Configuration::Toml)?;
+    }
+    builder.env();
+    for def in &args.flag_define {
+        builder.define(def);
+    }
+    let mut answered = builder.origins()
+        .into_iter()
+        .filter(|&(_, origin)| *origin != Origin::Default)
+        .map(|(name, origin)| {
+            debug!("{} given by {:?}", name, origin);
+            name.to_owned()
+        })
+        .collect::<HashSet<_>>();
+    // built-in, never asked
+    answered.insert(NOW_PARAM.to_owned());
+    let mut params = builder.build();
+    params::stamp_now(&mut params);
+
+    if args.flag_no_input {
+        if let Some(ref name) = args.flag_name {
+            params.param_map.insert("name".to_owned(), name.clone());
+        }
+        let missing = project.missing_params(root, &params)?;
+        if !missing.is_empty() {
+            bail!(ErrorKind::MissingParams(missing));
+        }
+    } else if !args.flag_confirm {
+        collect_params(&args.flag_name, &mut params.param_map, &answered)?;
+        debug!("Context updated with user input: {:?}", params);
+    }
+
+    if let Some(ref file) = args.flag_record_answers {
+        write_answers(Path::new(file), &params)?;
+        info!("Answers recorded to {:?}", file);
+    }
+    params.resolve_refs()?;
+    project.manifest(root)?.derive(&mut params)?;
+    Ok(params)
+}
+
+/// Render the template into temporal directory and print how it differs from `existing`.
+///
+/// Returns whether any difference found.
+fn print_diff(project: &Project,
+              params: &Params,
+              root: &Path,
+              existing: &str,
+              diag: &mut Diagnostics)
+              -> Result<bool> {
+    let rendered = TempDir::new("rig__rendered").chain_err(|| "Failed to create temporal directory")?;
+    project.generate_with(params, root, rendered.path(), false, diag)?;
+
+    let changes = diff::diff_trees(rendered.path(), Path::new(existing))?;
+    for change in &changes {
+        match *change {
+            FileChange::Added(ref path) => println!("Only in template: {}", path.display()),
+            FileChange::Modified(_, ref diff) => print!("{}", diff),
+            FileChange::BinaryModified(ref path) => {
+                println!("Binary files differ: {}", path.display())
+            }
+        }
+    }
+    Ok(!changes.is_empty())
+}
+
+/// Render the template with parameters recorded in project `dir`, overridden by
+/// `RIG_PARAM_*` environment variables and `--define`, and print how files differ, or apply
+/// changes without conflict with `--apply`.
+///
+/// Returns whether the project is up to date, or every change is applied with `--apply`.
+fn run_update(project: &Project,
+              root: &Path,
+              dir: &Path,
+              args: &Args,
+              diag: &mut Diagnostics)
+              -> Result<bool> {
+    let mut state = State::read(dir)?;
+    let defaults = project
+        .default_params_with(root, diag)
+        .unwrap_or(Params::minimal_req());
+    let mut builder = ParamsBuilder::new();
+    builder.defaults(&defaults)
+        .layer(&state.params, Origin::File(dir.join(update::STATE_FILE)))
+        .env();
+    for def in &args.flag_define {
+        builder.define(def);
+    }
+    let mut params = builder.build();
+    params::stamp_now(&mut params);
+    params.resolve_refs()?;
+    project.manifest(root)?.derive(&mut params)?;
+
+    let updates = update::changes(project, &params, root, dir, &state, diag)?;
+    for file in &updates {
+        let path = file.path.display();
+        if file.conflict {
+            println!("Conflict: {} (modified since generated)", path);
+        } else if args.flag_apply {
+            println!("{} {}", if file.existing.is_some() { "Updated" } else { "Created" }, path);
+        }
+        if !args.flag_apply {
+            match file.diff() {
+                Some(diff) => print!("{}", diff),
+                None => println!("Binary files differ: {}", path),
+            }
+        }
+    }
+    if !args.flag_apply {
+        return Ok(updates.is_empty());
+    }
+    update::apply(dir, &updates, &mut state)?;
+    state.params = params;
+    if let Some(ref branch) = args.flag_branch {
+        state.version = Some(branch.clone());
+    }
+    state.write(dir)?;
+    Ok(updates.iter().all(|file| !file.conflict))
+}
+
+/// Render the template with fixture params, and check the result against expected tree. Without
+/// `--fixtures` nor `--expected`, run test cases the template carries instead, if any.
+///
+/// Returns whether the test passed.
+fn run_test(project: &Project, root: &Path, args: &Args, diag: &mut Diagnostics) -> Result<bool> {
+    if args.flag_fixtures.is_none() && args.flag_expected.is_none() {
+        let suite = TemplateSuite::discover(project, root)?;
+        if !suite.cases().is_empty() {
+            let results = suite.run();
+            for result in &results {
+                let status = if result.passed() { "ok" } else { "FAILED" };
+                println!("test {} ... {}", result.name, status);
+            }
+            for result in &results {
+                if let Some(ref report) = result.report {
+                    print!("\n---- {} ----\n{}", result.name, report);
+                }
+            }
+            let failed = results.iter().filter(|r| !r.passed()).count();
+            println!("\n{} passed, {} failed", results.len() - failed, failed);
+            return Ok(failed == 0);
+        }
+    }
+    let mut params = project
+        .default_params_with(root, diag)
+        .unwrap_or(Params::minimal_req());
+    if let Some(ref fixtures) = args.flag_fixtures {
+        let fixtures = read_params(Path::new(fixtures), project.config)?;
+        params.param_map.extend(fixtures.param_map);
+    }
+    debug!("Test context: {:?}", params);
+
+    let rendered = TempDir::new("rig__test").chain_err(|| "Failed to create temporal directory")?;
+    if let Err(e) = project.generate_with(&params, root, rendered.path(), false, diag) {
+        println!("FAILED: template cannot be rendered");
+        for e in e.iter() {
+            println!("    {}", e);
+        }
+        return Ok(false);
+    }
+
+    let mut passed = true;
+    if let Some(ref expected) = args.flag_expected {
+        let expected = Path::new(expected);
+        for path in diff::missing_files(rendered.path(), expected)? {
+            println!("Not rendered: {}", path.display());
+            passed = false;
+        }
+        for change in diff::diff_trees(rendered.path(), expected)? {
+            match change {
+                FileChange::Added(ref path) => println!("Not expected: {}", path.display()),
+                FileChange::Modified(_, ref diff) => print!("{}", diff),
+                FileChange::BinaryModified(ref path) => {
+                    println!("Binary files differ: {}", path.display())
+                }
+            }
+            passed = false;
+        }
+    }
+
+    println!("{}", if passed { "ok" } else { "FAILED" });
+    Ok(passed)
+}
+
+/// Render single template file, or STDIN, to STDOUT.
+fn render_file(project: &Project, args: &Args) -> Result<()> {
+    let (name, body) = match args.arg_file {
+        Some(ref file) if file != "-" => (file.clone(), fsutils::read_file(Path::new(file))?),
+        _ => {
+            let mut body = String::new();
+            io::stdin().read_to_string(&mut body)?;
+            ("<stdin>".to_owned(), body)
+        }
+    };
+
+    let mut builder = ParamsBuilder::new();
+    if let Some(ref file) = args.flag_answers {
+        builder.file(Path::new(file), Configuration::Toml)?;
+    }
+    builder.env();
+    for def in &args.flag_define {
+        builder.define(def);
+    }
+    let mut params = builder.build();
+    params::stamp_now(&mut params);
+    params.resolve_refs()?;
+
+    let rendered = project.render(&name, &body, &params)?;
+    io::stdout().write_all(rendered.as_bytes())?;
+    Ok(())
+}
+
+/// Create template skeleton into `<directory>`.
+fn init_template(args: &Args) -> Result<()> {
+    let dest = PathBuf::from(args.arg_directory.as_ref().map(|s| s.as_ref()).unwrap_or("."));
+    let name = args.flag_name
+        .clone()
+        .or(dest.file_name().map(|s| s.to_string_lossy().into_owned()))
+        .unwrap_or("my-template".to_owned());
+    let author = Git2Config::open_default()
+        .and_then(|config| config.get_string("user.name"))
+        .unwrap_or("Anonymous".to_owned());
+
+    for file in skeleton::init(&dest, &name, &author, args.flag_giter8)? {
+        let file = file.display().to_string();
+        println!("{}", Message::Created.format(Lang::from_env(), &[&file]));
+    }
+    Ok(())
+}
+
+/// Print cached templates and templates registered in user configuration, fetching stale cache
+/// entries again if `refresh`.
+fn print_templates(refresh: bool) -> Result<()> {
+    if let Some(cache_dir) = cache::cache_dir() {
+        println!("Cached templates ({}):", cache_dir.display());
+        for mut entry in cache::list(&cache_dir)? {
+            if refresh {
+                match entry.is_stale() {
+                    Ok(true) => entry = entry.refresh()?,
+                    Ok(false) => {}
+                    Err(e) => warn!("Cannot check whether {} is up to date: {}", entry.name, e),
+                }
+            }
+            let revision = match (entry.branch.as_ref(), entry.short_revision()) {
+                (Some(branch), Some(rev)) => format!("{}@{}", branch, rev),
+                (None, Some(rev)) => rev.to_owned(),
+                _ => "-".to_owned(),
+            };
+            println!("    {}  {}  {}  updated {}",
+                     entry.name,
+                     revision,
+                     entry.url.as_ref().map(|s| s.as_ref()).unwrap_or("-"),
+                     entry.updated.map(cache::format_time).unwrap_or("-".to_owned()));
+        }
+    }
+
+    let settings = Settings::load()?;
+    if let Some(path) = Settings::path() {
+        println!("Registered templates ({}):", path.display());
+    }
+    for (name, registered) in &settings.templates {
+        match registered.version {
+            Some(ref version) => println!("    {}  {} ({})", name, registered.source, version),
+            None => println!("    {}  {}", name, registered.source),
+        }
+    }
+    Ok(())
+}
+
+/// Print placeholders of a local template file / directory, or a remote repository.
+fn print_vars(project: &Project, args: &Args) -> Result<()> {
+    let local = Path::new(&args.arg_repository);
+
+    if local.is_file() {
+        let tpl = Template::read_file(project.style.clone(), local)?;
+        for ph in tpl.placeholders() {
+            if ph.formatters().is_empty() {
+                println!("{}", ph.name());
             } else {
-                warn!("Cannot locate or open git global configuration");
-                None
+                println!("{} ({})", ph.name(), format_chain(ph.formatters()));
             }
+        }
+        return Ok(());
+    }
+
+    let (clone_root, root) = template_root(args)?;
+
+    let defaults = project.default_params(&root).unwrap_or(Params::from_map(BTreeMap::new()));
+    let vars = project.collect_vars(&root)?;
 
-        } else {
-            None
+    for (name, usage) in &vars {
+        match defaults.get(name) {
+            Some(v) => println!("{} [{}]", name, v),
+            None => println!("{}", name),
+        }
+        let chains = usage.formatters
+            .iter()
+            .filter(|chain| !chain.is_empty())
+            .map(|chain| format_chain(chain))
+            .collect::<Vec<_>>();
+        if !chains.is_empty() {
+            println!("    formatters: {}", chains.join(" "));
+        }
+        for file in &usage.files {
+            println!("    {}", file.display());
         }
     }
+    drop(clone_root);
+    Ok(())
 }
 
-fn normalize_url(raw: &str) -> Result<Url> {
-    if let Some(_) = raw.find('/') {
-        Url::parse(raw)
-            .or(Url::parse(format!("https://github.com/{}", raw).as_ref()))
-            .map_err(|e| ErrorKind::ParseUrl(e).into())
-    } else {
-        Err(ErrorKind::InvalidUrlFormat(raw.to_string()).into())
+/// Use local directory as template if exists, otherwise check out `--branch` of the repository,
+/// from local cache only if `--offline`.
+///
+/// Names registered in user configuration are resolved to their repositories, checking out the
+/// version registered unless `--branch` is given.
+fn template_root(args: &Args) -> Result<(Option<Checkout>, PathBuf)> {
+    let (template, branch) = (&args.arg_repository, &args.flag_branch);
+    let local = Path::new(template);
+    if local.is_dir() {
+        return Ok((None, local.to_path_buf()));
+    }
+    if local.is_file() && archive::Format::of(local).is_some() {
+        let checkout = archive::open(local)?;
+        let path = checkout.path().to_path_buf();
+        return Ok((Some(checkout), path));
     }
+
+    let settings = Settings::load().unwrap_or_else(|e| {
+        warn!("Cannot read user configuration: {}", e);
+        Settings::default()
+    });
+    let source = match settings.template(template) {
+        Some(registered) => {
+            debug!("Template {:?} registered as {:?}", template, registered);
+            let version = branch.as_ref().or(registered.version.as_ref());
+            Source::parse(&registered.source, version.map(|s| s.as_ref()))?
+        }
+        None if !template.contains('/') => bail!(ErrorKind::TemplateNotFound(template.to_owned())),
+        None => Source::parse(template, branch.as_ref().map(|s| s.as_ref()))?,
+    };
+    let checkout = if args.flag_offline { source.cached()? } else { source.fetch()? };
+    let path = checkout.path().to_path_buf();
+    Ok((Some(checkout), path))
 }
 
-fn collect_params<'a>(name: &'a Option<String>,
-                      params: &'a mut HashMap<String, String>)
-                      -> &'a mut HashMap<String, String> {
+/// Ask user what to do with existing `file`, skipping it unless answered otherwise.
+fn ask_overwrite(file: &Path) -> OverwritePolicy {
+    let file = file.display().to_string();
+    print!("{}", Message::OverwritePrompt.format(Lang::from_env(), &[&file]));
+    let _ = io::stdout().flush();
+    let mut answer = String::new();
+    let _ = io::stdin().read_line(&mut answer);
+    match &*answer.trim().to_lowercase() {
+        "o" | "overwrite" => OverwritePolicy::Overwrite,
+        "b" | "backup" => OverwritePolicy::Backup,
+        _ => OverwritePolicy::Skip,
+    }
+}
+
+fn format_chain(chain: &[Formatter]) -> String {
+    chain.iter().map(|f| f.name()).collect::<Vec<_>>().join(",")
+}
+
+/// Ask user to fill parameters not `answered` yet, `name` first and the rest in sorted order.
+fn collect_params(name: &Option<String>,
+                  params: &mut BTreeMap<String, String>,
+                  answered: &HashSet<String>)
+                  -> Result<()> {
+    let mut keys = params.keys().cloned().collect::<Vec<_>>();
+    keys.sort_by_key(|k| k != "name");
+
+    let lang = Lang::from_env();
     let mut s = String::new();
-    for (k, v) in params.iter_mut() {
+    for k in keys {
+
+        if answered.contains(&k) {
+            continue;
+        }
 
         // we treat `name` parameter specially
         if k == "name" {
             if let Some(ref arg_name) = *name {
-                *v = arg_name.clone();
+                params.insert(k, arg_name.clone());
                 continue;
             }
         }
 
-        print!("{} [{}]:", k, v);
-        io::stdout().flush().unwrap();
-        io::stdin().read_line(&mut s).unwrap();
+        print!("{}", Message::Prompt.format(lang, &[&k, &params[&k]]));
+        io::stdout().flush()?;
+        s.clear();
+        io::stdin().read_line(&mut s)?;
         if !s.trim().is_empty() {
-            *v = s.trim().to_string();
-            s.clear();
+            params.insert(k, s.trim().to_string());
         }
     }
-    params
+    Ok(())
 }
 
-fn get_output_dir(arg_name: &Option<String>, default_name: &str) -> PathBuf {
-    let mut output_dir = env::current_dir().unwrap();
-    if let Some(ref name) = *arg_name {
-        let path = Path::new(name);
-        if path.is_relative() {
-            let normalized = format(name, Formatter::Normalize);
-            output_dir.push(&normalized);
-        } else if path.is_absolute() {
-            output_dir = path.to_path_buf();
-        }
-    } else {
-        output_dir.push(&format(default_name, Formatter::Normalize));
+fn get_output_dir(arg_name: &Option<String>, default_name: &str) -> Result<PathBuf> {
+    let mut output_dir = env::current_dir()?;
+    let relative = match *arg_name {
+        Some(ref name) if Path::new(name).is_absolute() => return Ok(PathBuf::from(name)),
+        Some(ref name) => format(name, Formatter::Normalize),
+        None => format(default_name, Formatter::Normalize),
+    };
+    // never generate into current directory itself
+    if relative.is_empty() {
+        bail!(ErrorKind::MissingParams(vec!["name".to_owned()]));
     }
+    output_dir.push(&*relative);
 
-    output_dir
+    Ok(output_dir)
 }
@@ -0,0 +1,184 @@
+//! Declarations of template parameters in `[params]` table of TOML config, with their defaults,
+//! descriptions and the values they may take.
+//!
+//! ```toml
+//! name = "app"
+//!
+//! [params.license]
+//! description = "License of the project"
+//! default = "MIT"
+//! choices = ["MIT", "Apache-2.0"]
+//!
+//! [params.crate_name]
+//! pattern = "[a-z][a-z0-9_]*"
+//!
+//! [derived]
+//! module_name = "lower(replace(name, '-', '_'))"
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use regex::Regex;
+use toml;
+use toml::value::{Table, Value};
+
+use super::errors::*;
+use super::expr::{self, Expr};
+use super::fsutils;
+use super::template::{self, ParamMap, ParamSpec, Params, DERIVED_TABLE, MANIFEST_TABLE};
+
+/// Declaration of a parameter in template manifest.
+#[derive(Clone, Debug)]
+pub struct ParamDecl {
+    pub spec: ParamSpec,
+    /// Values the parameter may take, or empty if any value is allowed.
+    pub choices: Vec<String>,
+    /// Pattern every value must match as a whole, if any.
+    pub pattern: Option<Regex>,
+}
+
+impl ParamDecl {
+    /// Tell why `value` is not allowed for the parameter, if it is not.
+    pub fn check(&self, value: &str) -> Option<String> {
+        if !self.choices.is_empty() && !self.choices.iter().any(|c| c == value) {
+            return Some(format!("must be one of {}", self.choices.join(", ")));
+        }
+        match self.pattern {
+            Some(ref pattern) if !pattern.is_match(value) => {
+                // shown without the anchors it is compiled with
+                let written = &pattern.as_str()["^(?:".len()..pattern.as_str().len() - ")$".len()];
+                Some(format!("must match `{}`", written))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Parameters declared by a template, in order of their names.
+#[derive(Clone, Debug, Default)]
+pub struct Manifest {
+    pub params: Vec<ParamDecl>,
+    /// Parameters derived from others by expressions in `[derived]` table.
+    pub derived: BTreeMap<String, Expr>,
+}
+
+impl Manifest {
+    /// Read `[params]` table of TOML config `file`, if it has one.
+    pub fn read(file: &Path) -> Result<Manifest> {
+        let s = fsutils::read_file(file)?;
+        let toml = toml::from_str::<Table>(&s).chain_err(|| ErrorKind::TomlDecodeFailure)?;
+        Manifest::from_toml(&toml)
+    }
+
+    /// Fails on unknown keys in declarations, malformed patterns and expressions, and defaults
+    /// not allowed by their own declarations.
+    pub fn from_toml(toml: &Table) -> Result<Manifest> {
+        let mut params = Vec::new();
+        if let Some(value) = toml.get(MANIFEST_TABLE) {
+            let table = match value.as_table() {
+                Some(table) => table,
+                None => bail!("`{}` must be a table of parameter declarations", MANIFEST_TABLE),
+            };
+            for (name, value) in table {
+                match value.as_table() {
+                    Some(decl) => params.push(read_decl(name, decl)?),
+                    None => bail!("declaration of `{}` must be a table", name),
+                }
+            }
+        }
+        let mut derived = BTreeMap::new();
+        if let Some(value) = toml.get(DERIVED_TABLE) {
+            let table = match value.as_table() {
+                Some(table) => table,
+                None => bail!("`{}` must be a table of expressions", DERIVED_TABLE),
+            };
+            for (name, value) in table {
+                match value.as_str() {
+                    Some(s) => derived.insert(name.clone(), Expr::parse(s)?),
+                    None => bail!("expression deriving `{}` must be a string", name),
+                };
+            }
+        }
+        Ok(Manifest {
+            params: params,
+            derived: derived,
+        })
+    }
+
+    /// Declaration of parameter `name`, if any.
+    pub fn get(&self, name: &str) -> Option<&ParamDecl> {
+        self.params.iter().find(|decl| decl.spec.name == name)
+    }
+
+    /// Parameters given default values by the declarations.
+    pub fn defaults(&self) -> Params {
+        Params::from_map(self.params
+            .iter()
+            .filter_map(|d| d.spec.default.clone().map(|default| (d.spec.name.clone(), default))))
+    }
+
+    /// Give derived parameters not given yet their values in `params`, see `expr::derive`.
+    pub fn derive(&self, params: &mut Params) -> Result<()> {
+        expr::derive(&self.derived, params)
+    }
+
+    /// Check every value in `params` a declaration restricts, failing with
+    /// `ErrorKind::InvalidValue` on the first one not allowed.
+    pub fn validate<M: ParamMap>(&self, params: &M) -> Result<()> {
+        for decl in &self.params {
+            if let Some(value) = params.lookup(&decl.spec.name) {
+                if let Some(reason) = decl.check(value) {
+                    bail!(ErrorKind::InvalidValue(decl.spec.name.clone(), value.clone(), reason));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_decl(name: &str, decl: &Table) -> Result<ParamDecl> {
+    let string = |key: &str| -> Result<Option<String>> {
+        match decl.get(key) {
+            Some(value) => {
+                template::convert(value)
+                    .map(Some)
+                    .ok_or_else(|| format!("`{}` of `{}` must be a scalar", key, name).into())
+            }
+            None => Ok(None),
+        }
+    };
+    for key in decl.keys() {
+        if !["description", "default", "choices", "pattern"].contains(&key.as_str()) {
+            bail!("Unknown key `{}` in declaration of `{}`", key, name);
+        }
+    }
+
+    let choices = match decl.get("choices") {
+        Some(&Value::Array(ref items)) => {
+            match items.iter().map(template::convert).collect::<Option<Vec<_>>>() {
+                Some(choices) => choices,
+                None => bail!("`choices` of `{}` must be an array of scalars", name),
+            }
+        }
+        Some(_) => bail!("`choices` of `{}` must be an array of scalars", name),
+        None => Vec::new(),
+    };
+    let pattern = match string("pattern")? {
+        Some(pattern) => Some(Regex::new(&format!("^(?:{})$", pattern))?),
+        None => None,
+    };
+    let decl = ParamDecl {
+        spec: ParamSpec {
+            name: name.to_owned(),
+            description: string("description")?,
+            default: string("default")?,
+        },
+        choices: choices,
+        pattern: pattern,
+    };
+    if let Some(reason) = decl.spec.default.as_ref().and_then(|d| decl.check(d)) {
+        bail!("default of `{}` {}", name, reason);
+    }
+    Ok(decl)
+}
@@ -0,0 +1,224 @@
+//! Where rendered templates go, behind `OutputSink`, and how their paths are rendered.
+//!
+//! Nothing here touches filesystem but `DirSink`, so templates can be rendered from any `Loader`
+//! into `MemorySink` where filesystem is not available, e.g. in browser with `wasm` feature.
+
+use std::collections::{BTreeMap, BTreeSet};
+#[cfg(feature = "fs")]
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::errors::*;
+use super::format::Formatter;
+#[cfg(feature = "fs")]
+use super::fsutils;
+use super::template::{FormatCache, Loader, Params, RenderOptions, Style, Template};
+
+/// Destination of rendered files, by paths relative to the output root.
+pub trait OutputSink {
+    /// Create directory at `path` with its parents, which may be left empty.
+    fn create_dir(&mut self, path: &Path) -> Result<()>;
+
+    /// Write `contents` to file at `path`, creating its parents.
+    fn write_file(&mut self, path: &Path, contents: &[u8]) -> Result<()>;
+}
+
+/// Files and directories kept in memory.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MemorySink {
+    pub files: BTreeMap<PathBuf, Vec<u8>>,
+    pub dirs: BTreeSet<PathBuf>,
+}
+
+impl MemorySink {
+    pub fn new() -> MemorySink {
+        MemorySink::default()
+    }
+}
+
+impl OutputSink for MemorySink {
+    fn create_dir(&mut self, path: &Path) -> Result<()> {
+        self.dirs.insert(path.to_path_buf());
+        Ok(())
+    }
+
+    fn write_file(&mut self, path: &Path, contents: &[u8]) -> Result<()> {
+        self.files.insert(path.to_path_buf(), contents.to_vec());
+        Ok(())
+    }
+}
+
+/// Files and directories written under `root` on filesystem.
+#[cfg(feature = "fs")]
+#[derive(Clone, Debug)]
+pub struct DirSink {
+    pub root: PathBuf,
+}
+
+#[cfg(feature = "fs")]
+impl DirSink {
+    pub fn new<P: AsRef<Path>>(root: P) -> DirSink {
+        DirSink { root: root.as_ref().to_path_buf() }
+    }
+}
+
+#[cfg(feature = "fs")]
+impl OutputSink for DirSink {
+    fn create_dir(&mut self, path: &Path) -> Result<()> {
+        fsutils::create_dir_all(&self.root.join(path)).map(|_| ())
+    }
+
+    fn write_file(&mut self, path: &Path, contents: &[u8]) -> Result<()> {
+        let dest = self.root.join(path);
+        fsutils::create_dir_all(dest.parent().unwrap_or(&self.root))?;
+        fsutils::write_with(&dest, |f| {
+                f.write_all(contents)
+                    .chain_err(|| ErrorKind::FsFailure("writing file", dest.clone()))
+            })
+            .map(|_| ())
+    }
+}
+
+/// Render every template of `names` read from `loader`, written in `style`, into `sink` at its
+/// path rendered by `render_path`. Templates can include others from `loader`, by names relative
+/// to the directory of the including one, as `Template::write_including` resolves them; e.g.
+/// `{% include "header" %}` in `src/$name$.rs` loads `src/header`.
+pub fn render_tree<L, S>(loader: &L,
+                         names: &[String],
+                         style: Style,
+                         params: &Params,
+                         options: &RenderOptions,
+                         sink: &mut S)
+                         -> Result<()>
+    where L: Loader,
+          S: OutputSink
+{
+    let cache = FormatCache::new();
+    for name in names {
+        let path = render_path(name, params)?;
+        let tpl = Template::read_str(style.clone(), loader.load(name)?);
+        let mut buf = Vec::with_capacity(tpl.size_hint());
+        tpl.write_including(name, &mut buf, params, &cache, options, loader)?;
+        sink.write_file(&path, &buf)?;
+    }
+    Ok(())
+}
+
+/// Longest file name in bytes most file systems allow.
+pub const MAX_NAME_LEN: usize = 255;
+
+/// Names reserved for devices on Windows, with or without extension.
+const WINDOWS_RESERVED: &'static [&'static str] = &["CON", "PRN", "AUX", "NUL", "COM1", "COM2",
+                                                    "COM3", "COM4", "COM5", "COM6", "COM7",
+                                                    "COM8", "COM9", "LPT1", "LPT2", "LPT3",
+                                                    "LPT4", "LPT5", "LPT6", "LPT7", "LPT8",
+                                                    "LPT9"];
+
+/// Render `path` of template entry in `Style::Path`, e.g. `src/$package__packaged$/$name$.rs`,
+/// into where it goes relative to the output root.
+///
+/// Names are made legal on this OS as the directory generator makes them, and fail with
+/// `ErrorKind::IllegalPath` if they would escape the root, e.g. with `..`.
+pub fn render_path(path: &str, params: &Params) -> Result<PathBuf> {
+    let cache = FormatCache::new();
+    let mut rendered = PathBuf::new();
+    for part in path.split('/') {
+        if let Some(reason) = illegal_name(part, false) {
+            bail!(ErrorKind::IllegalPath(PathBuf::from(path), part.to_owned(), reason));
+        }
+        rendered.push(render_name(part, params, &cache, &RenderOptions::default(), false)?);
+    }
+    Ok(rendered)
+}
+
+/// Render file `name` of template entry in `Style::Path`, failing with `ErrorKind::IllegalPath`
+/// if the result would escape its directory, or replacing what would with `_` if `sanitize`.
+///
+/// Names changed by rendering are made legal on this OS by `legalize_name`.
+pub(crate) fn render_name(name: &str,
+                          params: &Params,
+                          cache: &FormatCache,
+                          options: &RenderOptions,
+                          sanitize: bool)
+                          -> Result<String> {
+    let tpl = Template::read_str(Style::Path, name);
+    // only `packaged` formatter may nest directories
+    let nested = tpl.placeholders()
+        .iter()
+        .any(|ph| ph.formatters().contains(&Formatter::DirectoryPath));
+    let mut buf = Vec::new();
+    tpl.write_with(&mut buf, params, cache, options)?;
+
+    let mut rendered = String::from_utf8(buf).map_err(|e| Error::from(e.to_string()))?;
+    if rendered == name {
+        return Ok(rendered);
+    }
+    if let Some(reason) = illegal_name(&rendered, nested) {
+        if sanitize {
+            let sanitized = sanitize_name(&rendered, nested);
+            warn!("Rendered name {:?} {}, sanitized into {:?}", rendered, reason, sanitized);
+            rendered = sanitized;
+        } else {
+            bail!(ErrorKind::IllegalPath(PathBuf::from(name), rendered, reason));
+        }
+    }
+    Ok(legalize_name(&rendered))
+}
+
+/// Make every component of rendered file `name` legal on this OS: on Windows, characters not
+/// allowed in file names and trailing dot or space are replaced with `_`, and `_` is appended
+/// to names reserved for devices. Components longer than `MAX_NAME_LEN` bytes are truncated on
+/// every OS.
+fn legalize_name(name: &str) -> String {
+    name.split('/').map(legalize_component).collect::<Vec<_>>().join("/")
+}
+
+fn legalize_component(name: &str) -> String {
+    let mut name = name.to_owned();
+    if cfg!(windows) {
+        name = name.chars()
+            .map(|c| if c < ' ' || "<>:\"|?*".contains(c) { '_' } else { c })
+            .collect();
+        if name.ends_with(|c| c == '.' || c == ' ') {
+            name.pop();
+            name.push('_');
+        }
+        let stem_len = name.find('.').unwrap_or(name.len());
+        if WINDOWS_RESERVED.iter().any(|r| r.eq_ignore_ascii_case(&name[..stem_len])) {
+            name.insert(stem_len, '_');
+        }
+    }
+    if name.len() > MAX_NAME_LEN {
+        let end = (0..MAX_NAME_LEN + 1).rev().find(|&i| name.is_char_boundary(i)).unwrap_or(0);
+        name.truncate(end);
+    }
+    name
+}
+
+/// Tell why rendered file `name` is not allowed, if it would escape its directory.
+///
+/// `/` is allowed only if `nested`, as long as every component is legal.
+fn illegal_name(name: &str, nested: bool) -> Option<&'static str> {
+    if name.contains('\0') {
+        return Some("contains NUL");
+    }
+    if name.contains('\\') {
+        return Some("contains `\\`");
+    }
+    if !nested && name.contains('/') {
+        return Some("contains `/`");
+    }
+    if name.split('/').any(|c| c.is_empty() || c == "." || c == "..") {
+        return Some("has empty, `.` or `..` component");
+    }
+    None
+}
+
+/// Replace illegal characters and components of rendered file `name` with `_`.
+fn sanitize_name(name: &str, nested: bool) -> String {
+    let name = name.replace(|c| c == '\0' || c == '\\' || (!nested && c == '/'), "_");
+    name.split('/')
+        .map(|c| if c.is_empty() || c == "." || c == ".." { "_" } else { c })
+        .collect::<Vec<_>>()
+        .join("/")
+}
@@ -0,0 +1,193 @@
+//! Parameters merged from layered sources: template defaults, params files, environment
+//! variables, and values given explicitly.
+//!
+//! Whatever order layers are added in, a value from a source of higher `Origin::precedence` wins
+//! over one of lower, and the value added last wins between sources of the same precedence.
+
+use std::collections::BTreeMap;
+use std::env;
+#[cfg(feature = "fs")]
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "fs")]
+use chrono::Local;
+
+#[cfg(feature = "fs")]
+use super::errors::*;
+#[cfg(feature = "fs")]
+use super::project::{self, Configuration};
+use super::template::{ParamType, Params};
+
+/// Prefix of environment variables giving parameters, e.g. `RIG_PARAM_name`.
+pub const PARAM_ENV_PREFIX: &'static str = "RIG_PARAM_";
+
+/// Built-in parameter of when the project is generated, as RFC 3339 datetime in local time,
+/// e.g. `2017-04-01T09:30:00+09:00`. Format it with `date` formatter, e.g. `date("%Y")`.
+pub const NOW_PARAM: &'static str = "now";
+
+/// Give `NOW_PARAM` the current time in `params`, unless it is given already.
+#[cfg(feature = "fs")]
+pub fn stamp_now(params: &mut Params) {
+    params.param_map.entry(NOW_PARAM.to_owned()).or_insert_with(|| Local::now().to_rfc3339());
+}
+
+/// Environment variable of parameter `name`, e.g. `RIG_PARAM_crate_name` of `crate-name`.
+///
+/// Characters other than ASCII alphanumerics and `_` are replaced with `_`.
+pub fn param_env_name(name: &str) -> String {
+    let name = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .collect::<String>();
+    format!("{}{}", PARAM_ENV_PREFIX, name)
+}
+
+/// Source a parameter value came from, in order of precedence from the lowest.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Origin {
+    /// Default value in template config.
+    Default,
+    /// Params file, e.g. answers recorded from an earlier run.
+    #[cfg(feature = "fs")]
+    File(PathBuf),
+    /// Environment variable of the name.
+    Env(String),
+    /// Value given explicitly, e.g. by `--define` of CLI.
+    Override,
+}
+
+impl Origin {
+    /// Values from higher are taken over those from lower.
+    pub fn precedence(&self) -> u8 {
+        match *self {
+            Origin::Default => 0,
+            #[cfg(feature = "fs")]
+            Origin::File(_) => 1,
+            Origin::Env(_) => 2,
+            Origin::Override => 3,
+        }
+    }
+}
+
+/// Builder of `Params` from layered sources, remembering where each value came from.
+#[derive(Clone, Debug, Default)]
+pub struct ParamsBuilder {
+    values: BTreeMap<String, (String, Origin)>,
+    lists: BTreeMap<String, (Vec<String>, Origin)>,
+    types: BTreeMap<String, ParamType>,
+    defaults: Option<Params>,
+}
+
+impl ParamsBuilder {
+    pub fn new() -> ParamsBuilder {
+        ParamsBuilder::default()
+    }
+
+    /// Add parameters of `params` as values from `origin`.
+    pub fn layer(&mut self, params: &Params, origin: Origin) -> &mut ParamsBuilder {
+        for (name, value) in &params.param_map {
+            self.set_from(name, value, origin.clone());
+        }
+        for (name, items) in &params.lists {
+            let replace = self.lists
+                .get(name)
+                .map(|&(_, ref o)| o.precedence() <= origin.precedence())
+                .unwrap_or(true);
+            if replace {
+                self.lists.insert(name.clone(), (items.clone(), origin.clone()));
+            }
+        }
+        for (name, ty) in &params.types {
+            self.types.entry(name.clone()).or_insert(*ty);
+        }
+        self
+    }
+
+    /// Add default values of the template. Nested tables of `defaults` read from TOML config are
+    /// kept for engines resolving dotted names through them, and so are their types over those
+    /// of other layers.
+    pub fn defaults(&mut self, defaults: &Params) -> &mut ParamsBuilder {
+        self.defaults = Some(defaults.clone());
+        self.types.extend(defaults.types.iter().map(|(k, v)| (k.clone(), *v)));
+        self.layer(defaults, Origin::Default)
+    }
+
+    /// Add parameters read from params `file` in `config` format.
+    #[cfg(feature = "fs")]
+    pub fn file(&mut self, file: &Path, config: Configuration) -> Result<&mut ParamsBuilder> {
+        let params = project::read_params(file, config)?;
+        Ok(self.layer(&params, Origin::File(file.to_path_buf())))
+    }
+
+    /// Add parameters given by environment variables named by `param_env_name`.
+    pub fn env(&mut self) -> &mut ParamsBuilder {
+        self.env_vars(env::vars())
+    }
+
+    /// Same as `env`, reading `vars` instead of environment of the process.
+    ///
+    /// Variables are taken as parameters known from earlier layers if their names match, and as
+    /// parameters named after `PARAM_ENV_PREFIX` otherwise.
+    pub fn env_vars<I>(&mut self, vars: I) -> &mut ParamsBuilder
+        where I: IntoIterator<Item = (String, String)>
+    {
+        for (var, value) in vars {
+            if !var.starts_with(PARAM_ENV_PREFIX) || var.len() == PARAM_ENV_PREFIX.len() {
+                continue;
+            }
+            let name = self.values
+                .keys()
+                .find(|name| param_env_name(name) == var)
+                .cloned()
+                .unwrap_or_else(|| var[PARAM_ENV_PREFIX.len()..].to_owned());
+            self.set_from(&name, &value, Origin::Env(var.clone()));
+        }
+        self
+    }
+
+    /// Give `value` to parameter `name` explicitly.
+    pub fn set(&mut self, name: &str, value: &str) -> &mut ParamsBuilder {
+        self.set_from(name, value, Origin::Override)
+    }
+
+    /// Give parameter explicitly in `key=value` form, taking `key` alone as empty value.
+    pub fn define(&mut self, definition: &str) -> &mut ParamsBuilder {
+        let mut kv = definition.splitn(2, '=');
+        let key = kv.next().unwrap_or("");
+        let value = kv.next().unwrap_or("");
+        self.set(key, value)
+    }
+
+    fn set_from(&mut self, name: &str, value: &str, origin: Origin) -> &mut ParamsBuilder {
+        let replace = self.values
+            .get(name)
+            .map(|&(_, ref o)| o.precedence() <= origin.precedence())
+            .unwrap_or(true);
+        if replace {
+            self.values.insert(name.to_owned(), (value.to_owned(), origin));
+        }
+        self
+    }
+
+    /// Where the value of parameter `name` came from, if it has any.
+    pub fn origin(&self, name: &str) -> Option<&Origin> {
+        match self.values.get(name) {
+            Some(&(_, ref origin)) => Some(origin),
+            None => self.lists.get(name).map(|&(_, ref origin)| origin),
+        }
+    }
+
+    /// Every parameter with where its value came from, in order of names.
+    pub fn origins(&self) -> Vec<(&str, &Origin)> {
+        self.values.iter().map(|(name, &(_, ref origin))| (name.as_str(), origin)).collect()
+    }
+
+    pub fn build(&self) -> Params {
+        let mut params = Params::from_map(self.values
+            .iter()
+            .map(|(name, &(ref value, _))| (name.clone(), value.clone())));
+        params.lists = self.lists.iter().map(|(k, &(ref v, _))| (k.clone(), v.clone())).collect();
+        params.types = self.types.clone();
+        params.toml = self.defaults.as_ref().and_then(|d| d.toml.clone());
+        params
+    }
+}
@@ -1,73 +1,573 @@
 use combine::*;
 use combine::char::{alpha_num, char, string, spaces};
-use combine::primitives::Consumed;
+use combine::primitives::{Error, Info};
+use memchr::{memchr, memchr2};
 
 use super::template::*;
 
-/// Intermediate state of parsing template
-pub type Progress<'a> = (String, Option<Placeholder>, &'a str);
+/// Intermediate state of parsing template: raw text, placeholder or delimiter of block with its
+/// byte offset in input, and rest of the input.
+///
+/// Raw text is always a slice of the input. An escaped character is parsed alone as raw text of
+/// itself, with the escaping backslash skipped.
+pub type Progress<'a> = (&'a str, Option<(usize, Tag)>, &'a str);
 
+/// Parse raw text and a placeholder or delimiter of `if` block or `for` loop following it at the
+/// head of `tpl`.
+///
+/// Delimiters are returned as they appear, whether blocks are balanced is not checked here.
+///
+/// Escaped delimiters are returned as raw text: `\$` and `$$` in `$` delimited styles, string
+/// literals like `{{ "{{" }}` and `{% raw %}` blocks in Tera and Liquid, and `\{{` in Handlebars.
+/// Text between a pair of `$--` fences in `StringTemplate` style is returned as is, too.
+///
+/// This function is total over any input: it never panics, and for non-empty `tpl` it returns
+/// either `Err`, or `Ok` with the rest strictly shorter than `tpl`. Parsing the rest repeatedly
+/// until it gets empty therefore always terminates, in at most `tpl.len()` steps.
 pub fn parse_template<'a>(tpl: &'a str, style: &'a Style)
     -> Result<Progress<'a>, ParseError<&'a str>>
 {
-    match *style {
+    let parsed = match *style {
         Style::ST => parse_st(tpl),
         Style::Path => parse_pathname(tpl),
-        _ => unreachable!(),
+        Style::Tera | Style::Liquid => parse_tera(tpl, style),
+        Style::Handlebars => parse_handlebars(tpl),
+        Style::Custom(ref delims) => parse_custom(tpl, delims, style),
+    };
+    match parsed {
+        Ok((_, _, rest)) if !tpl.is_empty() && rest.len() >= tpl.len() => {
+            Err(failure(tpl, "parser made no progress"))
+        }
+        parsed => parsed,
     }
 }
 
+/// Piece of template yielded by `TemplateIter`, with its byte offset in the template.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Chunk<'a> {
+    /// Raw text written as is. Escaping backslashes are left out of them, so an escaped
+    /// character comes alone.
+    Raw(usize, &'a str),
+    /// Placeholder, or delimiter of `if` block or `for` loop.
+    Tag(usize, Tag),
+}
+
+/// Iterator over raw text and tags of a template, parsing it lazily as `parse_template` does.
+///
+/// Iteration ends after the first error, which is yielded as is.
+#[derive(Clone, Debug)]
+pub struct TemplateIter<'a> {
+    tpl: &'a str,
+    rest: &'a str,
+    style: &'a Style,
+    /// Tag parsed along with raw text before it, yielded next.
+    pending: Option<Chunk<'a>>,
+    failed: bool,
+}
+
+impl<'a> TemplateIter<'a> {
+    pub fn new(tpl: &'a str, style: &'a Style) -> TemplateIter<'a> {
+        TemplateIter {
+            tpl: tpl,
+            rest: tpl,
+            style: style,
+            pending: None,
+            failed: false,
+        }
+    }
+
+    /// Rest of the template not parsed yet.
+    pub fn rest(&self) -> &'a str {
+        self.rest
+    }
+}
+
+impl<'a> Iterator for TemplateIter<'a> {
+    type Item = Result<Chunk<'a>, ParseError<&'a str>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(chunk) = self.pending.take() {
+            return Some(Ok(chunk));
+        }
+        while !self.failed && !self.rest.is_empty() {
+            let offset = self.tpl.len() - self.rest.len();
+            match parse_template(self.rest, self.style) {
+                Ok((raw, tag, rest)) => {
+                    self.rest = rest;
+                    let tag = tag.map(|(at, tag)| Chunk::Tag(offset + at, tag));
+                    if raw.is_empty() {
+                        match tag {
+                            Some(tag) => return Some(Ok(tag)),
+                            None => continue,
+                        }
+                    }
+                    self.pending = tag;
+                    let start = raw.as_ptr() as usize - self.tpl.as_ptr() as usize;
+                    return Some(Ok(Chunk::Raw(start, raw)));
+                }
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Byte offset in `input` where given parse error occurred.
+pub fn error_offset(input: &str, err: &ParseError<&str>) -> usize {
+    err.position.saturating_sub(input.as_ptr() as usize)
+}
+
+fn failure<'a>(at: &'a str, message: &'static str) -> ParseError<&'a str> {
+    ParseError::new(at.as_ptr() as usize, Error::Message(Info::Borrowed(message)))
+}
+
+/// Split raw text of `$` delimited styles off `input`, stopping before a placeholder or an escape.
+///
+/// A character escaped with backslash, and `$` escaped as `$$`, are split alone. Returns `None`
+/// where input starts with a placeholder.
+fn split_raw<'a>(input: &'a str) -> Result<Option<(&'a str, &'a str)>, ParseError<&'a str>> {
+    if input.starts_with("$$") {
+        return Ok(Some((&input[..1], &input[2..])));
+    }
+    if input.starts_with('\\') {
+        let escaped = &input[1..];
+        return match escaped.chars().next() {
+            Some(c) => Ok(Some(escaped.split_at(c.len_utf8()))),
+            None => Err(failure(escaped, "end of input after escape")),
+        };
+    }
+    let end = memchr2(b'$', b'\\', input.as_bytes()).unwrap_or(input.len());
+    if end == 0 && !input.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(input.split_at(end)))
+    }
+}
+
+/// Fence opening and closing text `StringTemplate` style copies as is, e.g. `$--$x$$--`.
+const VERBATIM_FENCE: &'static str = "$--";
+
 /// Parse template written in `StringTemplate` like format
 fn parse_st(input: &str) -> Result<Progress, ParseError<&str>> {
+    if input.starts_with(VERBATIM_FENCE) {
+        let body = &input[VERBATIM_FENCE.len()..];
+        return match body.find(VERBATIM_FENCE) {
+            Some(end) => Ok((&body[..end], None, &body[end + VERBATIM_FENCE.len()..])),
+            None => Err(failure(body, "unclosed verbatim fence")),
+        };
+    }
+    if let Some((raw, rest)) = split_raw(input)? {
+        return Ok((raw, None, rest));
+    }
+    if let Some(progress) = parse_st_block(input)? {
+        return Ok(progress);
+    }
 
-    let ident = || many1::<String, _>(alpha_num().or(one_of("_-".chars())).skip(spaces()));
+    let ident = || many1::<String, _>(alpha_num().or(one_of("_-.".chars())).skip(spaces()));
     let lex_char = |c| char(c).skip(spaces());
 
-    let escape_ph = many::<String, _>(satisfy(|c| c != '$').then(|c| {
-        parser(move |input| if c == '\\' {
-            any()
-                .map(|d| match d {
-                    '\\' => '\\',
-                    other => other
-                })
-                .parse_stream(input)
-        } else {
-            Ok((c, Consumed::Empty(input)))
-        })
-    }));
-
     let string_literal = between(char('"'), char('"'), many::<String, _>(satisfy(|c| c != '"')));
-    let fmt_args = string("format").skip(spaces()).with(lex_char('=').with(string_literal));
-    let placeholder = between(
+    let option = string("format")
+        .or(string("default"))
+        .skip(spaces())
+        .and(lex_char('=').with(string_literal));
+    let mut placeholder = between(
         lex_char('$'),
         char('$'),
-        ident().and(optional(char(';').with(fmt_args))))
-        .map(|parsed| Placeholder::new(&parsed.0, parsed.1, Style::ST));
+        ident().and(many::<Vec<_>, _>(char(';').skip(spaces()).with(option))))
+        .map(|(name, options)| {
+            let (mut format, mut default) = (None, None);
+            for (key, value) in options {
+                if key == "format" {
+                    format = Some(value);
+                } else {
+                    default = Some(value);
+                }
+            }
+            Placeholder::new(&name, format, Style::ST).with_default(default)
+        });
+
+    placeholder.parse(input).map(|(ph, rest)| ("", Some((0, Tag::Placeholder(ph))), rest))
+}
+
+/// Parse `$if(name)$`, `$if(name.truthy)$`, `$else$`, `$endif$` or `$include("path")$` at the
+/// head of `input`, if it starts with one of them.
+fn parse_st_block(input: &str) -> Result<Option<Progress>, ParseError<&str>> {
+    let (tag, len) = if input.starts_with("$else$") {
+        (Tag::Else, "$else$".len())
+    } else if input.starts_with("$endif$") {
+        (Tag::EndIf, "$endif$".len())
+    } else if input.starts_with("$if(") {
+        let expr = &input["$if(".len()..];
+        let end = expr.find(|c: char| !is_name_char(c)).unwrap_or(expr.len());
+        if !expr[end..].starts_with(")$") {
+            return Err(failure(&input[1..], "malformed condition"));
+        }
+        let name = &expr[..end];
+        let cond = if name.ends_with(".truthy") {
+            Condition::Truthy(name[..name.len() - ".truthy".len()].to_owned())
+        } else {
+            Condition::NonEmpty(name.to_owned())
+        };
+        if cond.name().is_empty() || !cond.name().chars().all(is_name_char) {
+            return Err(failure(&input[1..], "malformed condition"));
+        }
+        (Tag::If(cond), "$if(".len() + end + ")$".len())
+    } else if input.starts_with("$include(") {
+        let expr = &input["$include(".len()..];
+        let end = match expr.find(")$") {
+            Some(end) => end,
+            None => return Err(failure(&input[1..], "malformed include")),
+        };
+        match parse_include(&expr[..end]) {
+            Some(path) => (Tag::Include(path.to_owned()), "$include(".len() + end + ")$".len()),
+            None => return Err(failure(&input[1..], "malformed include")),
+        }
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(("", Some((0, tag)), &input[len..])))
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+/// Whether `c` may appear in parameter names, which are dotted where they come from nested
+/// tables, e.g. `author.name`.
+fn is_name_char(c: char) -> bool {
+    is_ident_char(c) || c == '.'
+}
+
+/// Parse simple `{{ name | filter }}` expressions, `{% if %}` blocks and `{% for %}` loops of
+/// `Tera` and `Liquid` templates.
+///
+/// Only variable substitution and `if`, `else`, `endif`, `for` and `endfor` tags are recognized,
+/// other tags are passed through as raw text. Filter arguments, either written as
+/// `filter(args)` or `filter: args`, are passed to the formatter as `filter(args)`, except the
+/// quoted value of `default` filter which becomes the default of the placeholder.
+fn parse_tera<'a>(input: &'a str, style: &Style) -> Result<Progress<'a>, ParseError<&'a str>> {
+    let end = find_open_tag(input.as_bytes(), true).unwrap_or(input.len());
+    let (raw, input) = input.split_at(end);
+    if input.is_empty() {
+        return Ok((raw, None, input));
+    }
+    if let Some((literal, rest)) = parse_tera_literal(input)? {
+        // raw text before the literal is returned alone, as progress holds one raw text
+        return Ok(if raw.is_empty() { (literal, None, rest) } else { (raw, None, input) });
+    }
+    if input.starts_with("{%") {
+        return parse_tera_block(input).map(|(tag, rest)| (raw, Some((end, tag)), rest));
+    }
+
+    let ident = || many1::<String, _>(alpha_num().or(one_of("_-.".chars())).skip(spaces()));
+    let lex_char = |c| char(c).skip(spaces());
+
+    let filter_args = between(lex_char('('), lex_char(')'), many::<String, _>(satisfy(|c| c != ')')))
+        .or(lex_char(':').with(many::<String, _>(satisfy(|c| c != '|' && c != '}'))));
+    let filters = many::<Vec<_>, _>(lex_char('|').with(ident()).and(optional(filter_args)));
+    let mut placeholder = between(
+        string("{{").skip(spaces()),
+        string("}}"),
+        ident().and(filters))
+        .map(|(name, filters)| {
+            let mut names = Vec::new();
+            let mut default = None;
+            for (filter, args) in filters {
+                match args {
+                    Some(ref args) if filter == "default" => default = quoted_arg(args),
+                    Some(ref args) => names.push(format!("{}({})", filter, args.trim())),
+                    None if filter == "default" => {}
+                    None => names.push(filter),
+                }
+            }
+            let args = if names.is_empty() { None } else { Some(names.join("|")) };
+            Placeholder::new(&name, args, style.clone()).with_default(default)
+        });
+
+    placeholder.parse(input).map(|(ph, rest)| (raw, Some((end, Tag::Placeholder(ph))), rest))
+}
+
+/// Parse `{% raw %}...{% endraw %}` block or `{{ "..." }}` string literal at the head of `input`
+/// into the text written as is and the rest, if it starts with one of them.
+fn parse_tera_literal(input: &str) -> Result<Option<(&str, &str)>, ParseError<&str>> {
+    if input.starts_with("{%") {
+        let close = match input.find("%}") {
+            Some(close) => close,
+            None => return Err(failure(&input[2..], "unclosed tag")),
+        };
+        if input[2..close].trim() != "raw" {
+            return Ok(None);
+        }
+        let body = &input[close + 2..];
+        let mut from = 0;
+        while let Some(i) = body[from..].find("{%") {
+            let at = from + i;
+            if let Some(end) = body[at..].find("%}") {
+                if body[at + 2..at + end].trim() == "endraw" {
+                    return Ok(Some((&body[..at], &body[at + end + 2..])));
+                }
+            }
+            from = at + 2;
+        }
+        return Err(failure(&input[2..], "unclosed raw block"));
+    }
+
+    let expr = input[2..].trim_start();
+    let quote = match expr.chars().next() {
+        Some(c) if c == '"' || c == '\'' => c,
+        _ => return Ok(None),
+    };
+    let literal = &expr[1..];
+    let end = match literal.find(quote) {
+        Some(end) => end,
+        None => return Err(failure(&input[2..], "unclosed string")),
+    };
+    let rest = literal[end + 1..].trim_start();
+    if !rest.starts_with("}}") {
+        return Err(failure(&input[2..], "filters cannot be applied to string"));
+    }
+    Ok(Some((&literal[..end], &rest[2..])))
+}
+
+/// Parse `{% if cond %}`, `{% else %}`, `{% endif %}`, `{% for var in list %}` or
+/// `{% endfor %}` at the head of `input`.
+///
+/// Conditions are a parameter name optionally compared with a quoted string by `==` or `!=`,
+/// and may be negated with `not`.
+fn parse_tera_block(input: &str) -> Result<(Tag, &str), ParseError<&str>> {
+    let close = match input.find("%}") {
+        Some(close) => close,
+        None => return Err(failure(&input[2..], "unclosed tag")),
+    };
+    let tag = input[2..close].trim();
+    let tag = if tag == "else" {
+        Tag::Else
+    } else if tag == "endif" {
+        Tag::EndIf
+    } else if tag == "endfor" {
+        Tag::EndFor
+    } else if tag.starts_with("for") {
+        match parse_loop(&tag["for".len()..]) {
+            Some((var, list)) => Tag::For(var.to_owned(), list.to_owned()),
+            None => return Err(failure(&input[2..], "malformed loop")),
+        }
+    } else if tag.starts_with("include") {
+        match parse_include(&tag["include".len()..]) {
+            Some(path) => Tag::Include(path.to_owned()),
+            None => return Err(failure(&input[2..], "malformed include")),
+        }
+    } else {
+        match parse_condition(&tag["if".len()..]) {
+            Some(cond) => Tag::If(cond),
+            None => return Err(failure(&input[2..], "malformed condition")),
+        }
+    };
+    Ok((tag, &input[close + 2..]))
+}
+
+/// Path of the template to include, quoted in `expr` of `include` tag.
+fn parse_include(expr: &str) -> Option<&str> {
+    let expr = expr.trim();
+    let quote = expr.chars().next().filter(|&c| c == '"' || c == '\'')?;
+    if expr.len() < 2 || !expr.ends_with(quote) {
+        return None;
+    }
+    let path = &expr[1..expr.len() - 1];
+    if path.is_empty() || path.contains(quote) { None } else { Some(path) }
+}
+
+/// Split `var in list` of `for` tag into names of the variable and the list.
+fn parse_loop(expr: &str) -> Option<(&str, &str)> {
+    let mut words = expr.split_whitespace();
+    match (words.next(), words.next(), words.next(), words.next()) {
+        (Some(var), Some("in"), Some(list), None) if var.chars().all(is_ident_char) &&
+                                                     list.chars().all(is_name_char) => {
+            Some((var, list))
+        }
+        _ => None,
+    }
+}
+
+fn parse_condition(expr: &str) -> Option<Condition> {
+    let expr = expr.trim();
+    if expr.starts_with("not ") {
+        return parse_condition(&expr["not".len()..]).map(|cond| Condition::Not(Box::new(cond)));
+    }
+    let end = expr.find(|c: char| !is_name_char(c)).unwrap_or(expr.len());
+    let (name, rest) = expr.split_at(end);
+    let rest = rest.trim_start();
+    if name.is_empty() {
+        return None;
+    } else if rest.is_empty() {
+        return Some(Condition::NonEmpty(name.to_owned()));
+    }
+
+    let op = ["==", "!=", "<=", ">=", "<", ">"].iter().find(|op| rest.starts_with(**op))?;
+    let value = parse_literal(rest[op.len()..].trim())?.to_owned();
+    let name = name.to_owned();
+    Some(match *op {
+        "==" => Condition::Equals(name, value),
+        "!=" => Condition::Not(Box::new(Condition::Equals(name, value))),
+        "<=" => Condition::Compare(name, Comparison::LessOrEqual, value),
+        ">=" => Condition::Compare(name, Comparison::GreaterOrEqual, value),
+        "<" => Condition::Compare(name, Comparison::Less, value),
+        _ => Condition::Compare(name, Comparison::Greater, value),
+    })
+}
+
+/// Value of quoted string `literal`, or bare number or boolean `literal` as it is, e.g. `4`,
+/// `-0.5` or `true`.
+fn parse_literal(literal: &str) -> Option<&str> {
+    match literal.chars().next()? {
+        quote @ '"' | quote @ '\'' => {
+            if literal.len() < 2 || !literal.ends_with(quote) {
+                return None;
+            }
+            let value = &literal[1..literal.len() - 1];
+            if value.contains(quote) { None } else { Some(value) }
+        }
+        _ if literal == "true" || literal == "false" => Some(literal),
+        _ if literal.chars().all(|c| c.is_ascii_digit() || "+-.".contains(c)) &&
+             literal.parse::<f64>().is_ok() => Some(literal),
+        _ => None,
+    }
+}
+
+/// First quoted string in filter arguments, e.g. `x` of `default(value="x")` or `default: 'x'`.
+fn quoted_arg(args: &str) -> Option<String> {
+    let start = args.find(|c: char| c == '"' || c == '\'')?;
+    let quote = &args[start..start + 1];
+    let value = &args[start + 1..];
+    value.find(quote).map(|end| value[..end].to_owned())
+}
+
+/// Parse placeholders between custom delimiters, with formatters separated by `arg_sep` of
+/// `delims`, e.g. `<<name|upper>>`.
+///
+/// The opening delimiter escaped by a backslash is returned as raw text of itself, and so is
+/// any other text, backslashes included.
+fn parse_custom<'a>(input: &'a str,
+                    delims: &Delimiters,
+                    style: &Style)
+                    -> Result<Progress<'a>, ParseError<&'a str>> {
+    let (open, close) = (delims.open.as_str(), delims.close.as_str());
+    if input.starts_with('\\') && input[1..].starts_with(open) {
+        return Ok((&input[1..1 + open.len()], None, &input[1 + open.len()..]));
+    }
+    let end = match input.find(open) {
+        Some(end) if end > 0 && input[..end].ends_with('\\') => end - 1,
+        Some(end) => end,
+        None => input.len(),
+    };
+    if end > 0 || open.is_empty() {
+        return Ok((&input[..end], None, &input[end..]));
+    }
 
-    let mut parser = escape_ph.and(optional(placeholder));
-    parser.parse(input).map(|(result, rest)| (result.0, result.1, rest))
+    let expr = &input[open.len()..];
+    let close_at = match expr.find(close) {
+        Some(close_at) if !close.is_empty() => close_at,
+        _ => return Err(failure(expr, "unclosed placeholder")),
+    };
+    let (name, args) = match expr[..close_at].find(delims.arg_sep) {
+        Some(sep) => {
+            let args = expr[sep + delims.arg_sep.len_utf8()..close_at].to_owned();
+            (expr[..sep].trim(), Some(args))
+        }
+        None => (expr[..close_at].trim(), None),
+    };
+    if name.is_empty() || !name.chars().all(is_name_char) {
+        return Err(failure(expr, "malformed placeholder"));
+    }
+    let ph = Placeholder::new(name, args, style.clone());
+    Ok(("", Some((0, Tag::Placeholder(ph))), &expr[close_at + close.len()..]))
+}
+
+/// Parse simple `{{ name }}` and `{{ helper name }}` expressions of `Handlebars` templates.
+///
+/// Block helpers, `else`, comments and partials are passed through as raw text. `\{{` is
+/// written as `{{`, leaving the rest of the expression as raw text.
+fn parse_handlebars(tpl: &str) -> Result<Progress, ParseError<&str>> {
+    if tpl.starts_with("\\{{") {
+        return Ok((&tpl[1..3], None, &tpl[3..]));
+    }
+    let end = find_open_tag(tpl.as_bytes(), false).unwrap_or(tpl.len());
+    if end > 0 && tpl[..end].ends_with('\\') {
+        return Ok((&tpl[..end - 1], None, &tpl[end - 1..]));
+    }
+    let (raw, input) = tpl.split_at(end);
+    if input.is_empty() {
+        return Ok((raw, None, input));
+    }
+
+    let tag = input[2..].trim_start();
+    let word = tag.split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+        .next()
+        .unwrap_or("");
+    if tag.starts_with(|c: char| "#/!>^".contains(c)) || word == "else" {
+        return match input.find("}}") {
+            Some(close) => {
+                let (raw, rest) = tpl.split_at(end + close + 2);
+                Ok((raw, None, rest))
+            }
+            None => Err(failure(input, "unclosed tag")),
+        };
+    }
+
+    let ident = || many1::<String, _>(alpha_num().or(one_of("_-.".chars()))).skip(spaces());
+
+    let mut placeholder = between(
+        string("{{").skip(spaces()),
+        string("}}"),
+        ident().and(optional(ident())))
+        .map(|(first, second)| match second {
+            Some(name) => Placeholder::new(&name, Some(first), Style::Handlebars),
+            None => Placeholder::new(&first, None, Style::Handlebars),
+        });
+
+    placeholder.parse(input).map(|(ph, rest)| (raw, Some((end, Tag::Placeholder(ph))), rest))
+}
+
+/// Offset of the first `{{` in `input`, or of `{%` opening `if`, `else`, `endif`, `for`,
+/// `endfor`, `include` or `raw` tag if `blocks` is set.
+fn find_open_tag(input: &[u8], blocks: bool) -> Option<usize> {
+    let mut from = 0;
+    while let Some(i) = memchr(b'{', &input[from..]) {
+        let at = from + i;
+        match input.get(at + 1) {
+            Some(&b'{') => return Some(at),
+            Some(&b'%') if blocks && is_block_tag(&input[at + 2..]) => return Some(at),
+            _ => {}
+        }
+        from = at + 1;
+    }
+    None
+}
+
+/// Whether the inside of `{%` tag starts with keyword of `if` block, `for` loop, `include` or
+/// `raw` block.
+fn is_block_tag(tag: &[u8]) -> bool {
+    let start = tag.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(tag.len());
+    let tag = &tag[start..];
+    ["if", "else", "endif", "for", "endfor", "include", "raw"].iter().any(|keyword| {
+        tag.starts_with(keyword.as_bytes()) &&
+        tag.get(keyword.len()).map_or(true, |&b| !(b.is_ascii_alphanumeric() || b == b'_'))
+    })
 }
 
 /// Parse template appears in path names.
 fn parse_pathname(input: &str) -> Result<Progress, ParseError<&str>> {
+    if let Some((raw, rest)) = split_raw(input)? {
+        return Ok((raw, None, rest));
+    }
 
-    let ident = || many1::<String, _>(alpha_num().or(one_of("_-".chars())).skip(spaces()));
+    let ident = || many1::<String, _>(alpha_num().or(one_of("_-.".chars())).skip(spaces()));
     let lex_char = |c| char(c).skip(spaces());
 
-    let escape_ph = many::<String, _>(satisfy(|c| c != '$').then(|c| {
-        parser(move |input| if c == '\\' {
-            any()
-                .map(|d| match d {
-                    '\\' => '\\',
-                    other => other
-                })
-                .parse_stream(input)
-        } else {
-            Ok((c, Consumed::Empty(input)))
-        })
-    }));
-
-    let placeholder = between(
+    let mut placeholder = between(
         lex_char('$'),
         char('$'),
         ident())
@@ -80,6 +580,5 @@ fn parse_pathname(input: &str) -> Result<Progress, ParseError<&str>> {
             }
         });
 
-    let mut parser = escape_ph.and(optional(placeholder));
-    parser.parse(input).map(|(result, rest)| (result.0, result.1, rest))
+    placeholder.parse(input).map(|(ph, rest)| ("", Some((0, Tag::Placeholder(ph))), rest))
 }
@@ -0,0 +1,151 @@
+//! Rendering of errors and lint issues in the manner of `rustc`, with location, excerpt of the
+//! source line, caret underline and help text.
+//!
+//! Only built with `pretty-diagnostics` feature.
+
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use super::errors::*;
+use super::format::Formatter;
+use super::project::{Issue, IssueKind};
+use super::template::Position;
+
+/// A message located in a template file, ready to render.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    /// `error` or `warning`.
+    pub severity: &'static str,
+    pub message: String,
+    pub file: Option<PathBuf>,
+    pub position: Option<Position>,
+    /// Shown next to the underline.
+    pub label: Option<String>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    /// Describe lint `issue`, with hint on how to fix it.
+    pub fn from_issue(issue: &Issue) -> Diagnostic {
+        let (label, help) = match issue.kind {
+            IssueKind::InvalidConfig(_) => (None, None),
+            IssueKind::UnsupportedValue(_) => {
+                (None, Some("only strings, numbers, booleans and dates can be parameters".to_owned()))
+            }
+            IssueKind::Malformed(_) => {
+                (Some("placeholder is not closed properly".to_owned()),
+                 Some("close the placeholder, or escape its delimiter to write it literally"
+                     .to_owned()))
+            }
+            IssueKind::UnknownFormatter { .. } => {
+                let known = Formatter::all().iter().map(|f| f.name()).collect::<Vec<_>>();
+                (Some("unknown formatter".to_owned()),
+                 Some(format!("known formatters are: {}", known.join(", "))))
+            }
+            IssueKind::UndeclaredParam(ref name) => {
+                (Some("not declared".to_owned()),
+                 Some(format!("declare `{}` with its default value in template config", name)))
+            }
+            IssueKind::UnusedParam(ref name) => {
+                (None, Some(format!("remove `{}` from template config, or refer to it", name)))
+            }
+        };
+        Diagnostic {
+            severity: "warning",
+            message: issue.kind.to_string(),
+            file: Some(issue.file.clone()),
+            position: issue.position,
+            label: label,
+            notes: Vec::new(),
+            help: help,
+        }
+    }
+
+    /// Describe `e`, pointing at the template it failed to render if any.
+    pub fn from_error(e: &Error) -> Diagnostic {
+        let mut causes = e.iter().skip(1).map(|c| c.to_string()).collect::<Vec<_>>();
+        let (file, position, label) = match *e.kind() {
+            ErrorKind::RenderFailure(ref file, position) => {
+                let label = if causes.is_empty() { None } else { Some(causes.remove(0)) };
+                (Some(file.clone()), position, label)
+            }
+            _ => (None, None, None),
+        };
+        Diagnostic {
+            severity: "error",
+            message: e.to_string(),
+            file: file,
+            position: position,
+            label: label,
+            notes: causes,
+            help: None,
+        }
+    }
+
+    /// Render into multiple lines, quoting the line at `position` from `source` if given.
+    pub fn render(&self, source: Option<&str>) -> String {
+        let mut out = String::new();
+        let line = match (source, self.position) {
+            (Some(src), Some(pos)) if pos.line > 0 => src.lines().nth(pos.line - 1),
+            _ => None,
+        };
+        let gutter = match (line, self.position) {
+            (Some(_), Some(pos)) => " ".repeat(pos.line.to_string().len()),
+            _ => String::new(),
+        };
+
+        let _ = writeln!(out, "{}: {}", self.severity, self.message);
+        if let Some(ref file) = self.file {
+            let _ = write!(out, "{}--> {}", gutter, file.display());
+            if let Some(pos) = self.position {
+                let _ = write!(out, ":{}:{}", pos.line, pos.column);
+            }
+            out.push('\n');
+        }
+
+        if let (Some(text), Some(pos)) = (line, self.position) {
+            // keep tabs, so that carets line up with the quoted text
+            let indent = text.chars()
+                .take(pos.column.saturating_sub(1))
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect::<String>();
+            let label = self.label.as_ref().map(|l| format!(" {}", l)).unwrap_or_default();
+            let _ = writeln!(out, "{} |", gutter);
+            let _ = writeln!(out, "{} | {}", pos.line, text);
+            let _ = writeln!(out,
+                             "{} | {}{}{}",
+                             gutter,
+                             indent,
+                             "^".repeat(underline_width(text, pos.column)),
+                             label);
+            if !self.notes.is_empty() || self.help.is_some() {
+                let _ = writeln!(out, "{} |", gutter);
+            }
+        } else if let Some(ref label) = self.label {
+            let _ = writeln!(out, "{} = note: {}", gutter, label);
+        }
+
+        for note in &self.notes {
+            let _ = writeln!(out, "{} = note: {}", gutter, note);
+        }
+        if let Some(ref help) = self.help {
+            let _ = writeln!(out, "{} = help: {}", gutter, help);
+        }
+        out
+    }
+}
+
+/// Number of characters to underline from `column` of `text`: the whole placeholder beginning
+/// there up to its closing delimiter, or rest of the line if it is not closed.
+fn underline_width(text: &str, column: usize) -> usize {
+    let rest = text.chars().skip(column.saturating_sub(1)).collect::<String>();
+    let width = if rest.starts_with("{{") {
+        rest[2..].find("}}").map(|i| rest[..i + 4].chars().count())
+    } else if rest.starts_with('$') {
+        rest[1..].find('$').map(|i| rest[..i + 2].chars().count())
+    } else {
+        Some(1)
+    };
+    width.unwrap_or_else(|| rest.chars().count()).max(1)
+}
@@ -1,26 +1,419 @@
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::ffi::{OsStr, OsString};
+use std::fmt;
 use std::fs;
+use std::io::BufReader;
+use std::mem;
+use std::panic;
 use std::path::{Path, PathBuf};
 use std::str;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
 
+use glob::Pattern;
+#[cfg(feature = "handlebars")]
+use handlebars::{self, Handlebars};
 use java_properties;
+#[cfg(feature = "handlebars")]
+use rustc_serialize::json::Json;
+#[cfg(feature = "liquid")]
+use liquid::{self, LiquidOptions, LocalTemplateRepository, Renderable};
+#[cfg(feature = "tera")]
 use tera::{Context, Tera};
 use toml;
 use walkdir::{DirEntry, WalkDir, WalkDirIterator};
 
+use super::diagnostics::{self, Diagnostics, Warning};
 use super::errors::*;
+#[cfg(feature = "tera")]
 use super::filters;
-use super::fsutils;
-use super::template::{Style, Params, Template};
+use super::format::{Formatter, FormatterRegistry};
+use super::fsutils::{self, Outcome, Written};
+use super::hooks::Hooks;
+use super::manifest::Manifest;
+use super::output::render_name;
+use super::params::{param_env_name, NOW_PARAM};
+#[cfg(feature = "handlebars")]
+use super::helpers;
+#[cfg(feature = "liquid")]
+use super::liquid_filters;
+use super::template::{Condition, FileLoader, FormatCache, MissingKeyBehavior, Position, Style,
+                      Params, RenderOptions, Template, CONDITIONS_TABLE, ENGINES_TABLE,
+                      IGNORE_TABLE};
+#[cfg(feature = "tera")]
+use super::template::RESERVED_TABLES;
+use super::testing::SUITE_DIR;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Project {
     pub root_path: Option<String>,
     pub config: Configuration,
     pub style: Style,
     pub force_packaged: bool,
+    /// Fail rendering if any placeholder has no value, instead of leaving it as is.
+    pub strict: bool,
+    /// Replace characters in rendered file names which would escape their directory, instead of
+    /// failing.
+    pub sanitize_paths: bool,
+    /// Number of files rendered in parallel, or `0` for one per CPU.
+    pub jobs: usize,
+    /// Extensions of files copied as they are, in addition to `fsutils::BINARY_EXTENSIONS`.
+    pub verbatim: Vec<String>,
+    /// What to do with files already in the destination.
+    pub overwrite: OverwritePolicy,
+    /// How the built-in engine renders placeholders without value. `MissingKeyBehavior::Error`
+    /// works as `strict` does.
+    pub render_options: RenderOptions,
+    /// User-defined formatters placeholders of the built-in engine can apply, in preference to
+    /// built-in ones of the same names.
+    pub formatters: Arc<FormatterRegistry>,
+}
+
+/// What to do when a template file would be written over a file already in the destination.
+#[derive(Clone)]
+pub enum OverwritePolicy {
+    /// Write over the file, reported as `Outcome::Existed`.
+    Overwrite,
+    /// Leave the file as it is, reported as `Outcome::Skipped`.
+    Skip,
+    /// Move the file aside to `fsutils::backup_path` first, reported as `Outcome::BackedUp`.
+    Backup,
+    /// Fail with `ErrorKind::FileExists` before writing any file.
+    Fail,
+    /// Ask the function which of the other policies applies to each file.
+    Prompt(Arc<Fn(&Path) -> OverwritePolicy + Send + Sync>),
+}
+
+impl Default for OverwritePolicy {
+    fn default() -> OverwritePolicy {
+        OverwritePolicy::Overwrite
+    }
+}
+
+impl fmt::Debug for OverwritePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OverwritePolicy::Overwrite => f.write_str("Overwrite"),
+            OverwritePolicy::Skip => f.write_str("Skip"),
+            OverwritePolicy::Backup => f.write_str("Backup"),
+            OverwritePolicy::Fail => f.write_str("Fail"),
+            OverwritePolicy::Prompt(_) => f.write_str("Prompt(..)"),
+        }
+    }
+}
+
+/// Entries of the template resolved by `Project::plan`, to be generated in order.
+pub(crate) struct Plan {
+    /// Template root.
+    pub root: PathBuf,
+    /// Every entry to generate, with where it goes.
+    pub tree: Vec<(DirEntry, PathBuf)>,
+    /// Existing files left as they are by `OverwritePolicy::Skip`, not in `tree`.
+    pub skipped: Vec<PathBuf>,
+    /// Existing files to move aside before generating, by `OverwritePolicy::Backup`.
+    pub backups: Vec<PathBuf>,
+}
+
+impl Plan {
+    /// Report skipped files as `Outcome::Skipped`, and those written over backups as
+    /// `Outcome::BackedUp`, along with `written`.
+    pub fn report(&self, mut written: Vec<Written>) -> Vec<Written> {
+        for file in &mut written {
+            if self.backups.contains(&file.path) {
+                file.outcome = Outcome::BackedUp;
+            }
+        }
+        written.extend(self.skipped.iter().map(|path| {
+            Written {
+                path: path.clone(),
+                bytes: 0,
+                outcome: Outcome::Skipped,
+            }
+        }));
+        written
+    }
+}
+
+/// Files generated only where a parameter is true, selected by glob patterns in template config.
+#[derive(Clone, Debug, Default)]
+pub struct Conditions {
+    patterns: Vec<(Pattern, String)>,
+}
+
+impl Conditions {
+    /// Read `[conditions]` table of TOML config `file`, if it has one.
+    ///
+    /// Keys of the table are glob patterns matched against paths from template root as those of
+    /// `[engines]`, and values are names of parameters, e.g. `"ci/**" = "use_ci"` to generate
+    /// `ci` directory only where `use_ci` is `true`, `yes` or `y`.
+    pub fn read(file: &Path) -> Result<Conditions> {
+        let s = fsutils::read_file(file)?;
+        let toml = toml::from_str::<toml::value::Table>(&s)
+            .chain_err(|| ErrorKind::TomlDecodeFailure)?;
+        Conditions::from_toml(&toml)
+    }
+
+    pub fn from_toml(toml: &toml::value::Table) -> Result<Conditions> {
+        let mut patterns = Vec::new();
+        if let Some(value) = toml.get(CONDITIONS_TABLE) {
+            let table = match value.as_table() {
+                Some(table) => table,
+                None => {
+                    bail!("`{}` must be a table of glob patterns to parameter names",
+                          CONDITIONS_TABLE)
+                }
+            };
+            for (glob, name) in table {
+                match name.as_str() {
+                    Some(name) => patterns.push((Pattern::new(glob)?, name.to_owned())),
+                    None => bail!("Condition of `{}` must be a parameter name", glob),
+                }
+            }
+        }
+        Ok(Conditions { patterns: patterns })
+    }
+
+    /// Whether the entry at `path` relative to template root is generated with `params`, that is
+    /// every parameter of patterns matching it is true.
+    ///
+    /// A directory is left out as a whole where a pattern matches every path in it, e.g. `ci`
+    /// with `ci/**`.
+    pub fn includes(&self, path: &Path, is_dir: bool, params: &Params) -> bool {
+        // a file named `*` stands for any path in the directory
+        let any_child = path.join("*");
+        self.patterns
+            .iter()
+            .filter(|&&(ref pattern, _)| {
+                pattern.matches_path(path) || (is_dir && pattern.matches_path(&any_child))
+            })
+            .all(|&(_, ref name)| Condition::Truthy(name.clone()).holds(params))
+    }
+
+    /// Names of parameters the patterns depend on.
+    pub fn params(&self) -> Vec<&str> {
+        self.patterns.iter().map(|&(_, ref name)| name.as_str()).collect()
+    }
+}
+
+/// File in template root listing files not to be copied into generated project.
+pub const IGNORE_FILE: &'static str = ".rigignore";
+
+/// Files left out of generation by glob patterns, listed in `IGNORE_FILE` or in `[ignore]` table
+/// of TOML config.
+#[derive(Clone, Debug, Default)]
+pub struct Ignores {
+    /// Patterns, with whether they match only directories.
+    patterns: Vec<(Pattern, bool)>,
+}
+
+impl Ignores {
+    /// Read ignore file `file`, which has a glob pattern per line.
+    ///
+    /// Blank lines and lines starting with `#` are skipped. Patterns ending with `/` match only
+    /// directories.
+    pub fn read(file: &Path) -> Result<Ignores> {
+        let s = fsutils::read_file(file)?;
+        Ignores::parse(s.lines().map(|line| line.trim()).filter(|line| !line.starts_with('#')))
+    }
+
+    /// Read `patterns` array in `[ignore]` table of TOML config `file`, if it has one.
+    pub fn read_toml(file: &Path) -> Result<Ignores> {
+        let s = fsutils::read_file(file)?;
+        let toml = toml::from_str::<toml::value::Table>(&s)
+            .chain_err(|| ErrorKind::TomlDecodeFailure)?;
+        Ignores::from_toml(&toml)
+    }
+
+    pub fn from_toml(toml: &toml::value::Table) -> Result<Ignores> {
+        let table = match toml.get(IGNORE_TABLE) {
+            Some(value) => {
+                match value.as_table() {
+                    Some(table) => table,
+                    None => bail!("`{}` must be a table", IGNORE_TABLE),
+                }
+            }
+            None => return Ok(Ignores::default()),
+        };
+        for key in table.keys() {
+            if key != "patterns" {
+                bail!("Unknown key `{}` in `{}`", key, IGNORE_TABLE);
+            }
+        }
+        let patterns = match table.get("patterns") {
+            Some(&toml::Value::Array(ref items)) => {
+                match items.iter().map(|item| item.as_str()).collect::<Option<Vec<_>>>() {
+                    Some(patterns) => patterns,
+                    None => bail!("`patterns` of `{}` must be an array of strings", IGNORE_TABLE),
+                }
+            }
+            Some(_) => bail!("`patterns` of `{}` must be an array of strings", IGNORE_TABLE),
+            None => Vec::new(),
+        };
+        Ignores::parse(patterns)
+    }
+
+    fn parse<'a, I>(patterns: I) -> Result<Ignores>
+        where I: IntoIterator<Item = &'a str>
+    {
+        let mut ignores = Ignores::default();
+        for pattern in patterns.into_iter().filter(|p| !p.is_empty()) {
+            let dir_only = pattern.ends_with('/');
+            let pattern = Pattern::new(pattern.trim_end_matches('/'))?;
+            ignores.patterns.push((pattern, dir_only));
+        }
+        Ok(ignores)
+    }
+
+    /// Add patterns of `other`.
+    pub fn extend(&mut self, other: Ignores) {
+        self.patterns.extend(other.patterns);
+    }
+
+    /// Whether the entry at `path` relative to template root is left out.
+    ///
+    /// Patterns without `/` match file names at any depth, like `*.lock`. A directory matching
+    /// any pattern, or matched as a whole like `target` with `target/**`, is left out along with
+    /// everything in it.
+    pub fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        // a file named `*` stands for any path in the directory
+        let any_child = path.join("*");
+        let name = path.file_name().map(Path::new);
+        self.patterns
+            .iter()
+            .filter(|&&(_, dir_only)| is_dir || !dir_only)
+            .any(|&(ref pattern, _)| {
+                pattern.matches_path(path) || (is_dir && pattern.matches_path(&any_child)) ||
+                (!pattern.as_str().contains('/') &&
+                 name.map(|name| pattern.matches_path(name)).unwrap_or(false))
+            })
+    }
+}
+
+/// Engines selected for files by glob patterns in template config.
+#[derive(Clone, Debug, Default)]
+pub struct Engines {
+    patterns: Vec<(Pattern, Style)>,
+}
+
+impl Engines {
+    /// Read `[engines]` table of TOML config `file`, if it has one.
+    ///
+    /// Keys of the table are glob patterns matched against paths from template root, where `*`
+    /// also matches `/`, and values are names of engines, e.g. `"**" = "liquid"` for every file.
+    pub fn read(file: &Path) -> Result<Engines> {
+        let s = fsutils::read_file(file)?;
+        let toml = toml::from_str::<toml::value::Table>(&s)
+            .chain_err(|| ErrorKind::TomlDecodeFailure)?;
+        Engines::from_toml(&toml)
+    }
+
+    pub fn from_toml(toml: &toml::value::Table) -> Result<Engines> {
+        let mut patterns = Vec::new();
+        if let Some(value) = toml.get(ENGINES_TABLE) {
+            let table = match value.as_table() {
+                Some(table) => table,
+                None => bail!("`{}` must be a table of glob patterns to engine names", ENGINES_TABLE),
+            };
+            for (glob, name) in table {
+                match name.as_str().and_then(Style::from_name) {
+                    Some(style) => patterns.push((Pattern::new(glob)?, style)),
+                    None => bail!("Unknown engine {:?} for `{}`", name, glob),
+                }
+            }
+        }
+        Ok(Engines { patterns: patterns })
+    }
+
+    /// Style of the file at `path` relative to template root, or `default` if nothing selects it.
+    ///
+    /// Where several patterns match the path, the longest one wins.
+    pub fn style_of(&self, path: &Path, default: &Style) -> Style {
+        self.patterns
+            .iter()
+            .filter(|&&(ref pattern, _)| pattern.matches_path(path))
+            .max_by_key(|&&(ref pattern, _)| pattern.as_str().len())
+            .map(|&(_, ref style)| style.clone())
+            .unwrap_or_else(|| default.clone())
+    }
+}
+
+/// Where and how a parameter is referenced across a template tree.
+#[derive(Clone, Debug, Default)]
+pub struct VarUsage {
+    /// Distinct formatter chains applied to the parameter.
+    pub formatters: BTreeSet<Vec<Formatter>>,
+    /// Files referencing the parameter, relative to template root.
+    pub files: BTreeSet<PathBuf>,
+    /// Whether any reference has no default value written in it, so that the parameter must be
+    /// given.
+    pub required: bool,
+}
+
+/// Problem found in a template tree by `Project::lint`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Issue {
+    /// File the issue was found in, relative to template root.
+    pub file: PathBuf,
+    pub position: Option<Position>,
+    pub kind: IssueKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum IssueKind {
+    /// Config file is missing or cannot be decoded.
+    InvalidConfig(String),
+    /// Config value which cannot be used as a parameter, e.g. TOML array.
+    UnsupportedValue(String),
+    /// Placeholder syntax cannot be parsed.
+    Malformed(String),
+    UnknownFormatter { param: String, formatter: String },
+    /// Placeholder refers to a parameter not declared in config file.
+    UndeclaredParam(String),
+    /// Parameter declared in config file is never referred to.
+    UnusedParam(String),
+}
+
+impl IssueKind {
+    /// Stable identifier of the kind of issue, for machine readable output.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            IssueKind::InvalidConfig(_) => "invalid-config",
+            IssueKind::UnsupportedValue(_) => "unsupported-value",
+            IssueKind::Malformed(_) => "malformed",
+            IssueKind::UnknownFormatter { .. } => "unknown-formatter",
+            IssueKind::UndeclaredParam(_) => "undeclared-param",
+            IssueKind::UnusedParam(_) => "unused-param",
+        }
+    }
+}
+
+impl fmt::Display for IssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IssueKind::InvalidConfig(ref msg) => write!(f, "invalid config: {}", msg),
+            IssueKind::UnsupportedValue(ref key) => {
+                write!(f, "value of `{}` cannot be used as a parameter", key)
+            }
+            IssueKind::Malformed(ref msg) => write!(f, "malformed placeholder: {}", msg),
+            IssueKind::UnknownFormatter { ref param, ref formatter } => {
+                write!(f, "unknown formatter `{}` on `{}`", formatter, param)
+            }
+            IssueKind::UndeclaredParam(ref name) => write!(f, "parameter `{}` is not declared", name),
+            IssueKind::UnusedParam(ref name) => write!(f, "parameter `{}` is never used", name),
+        }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if let Some(pos) = self.position {
+            write!(f, ":{}:{}", pos.line, pos.column)?;
+        }
+        write!(f, ": {}", self.kind)
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -36,6 +429,13 @@ impl Default for Project {
             config: Configuration::Toml,
             style: Style::Tera,
             force_packaged: false,
+            strict: false,
+            sanitize_paths: false,
+            jobs: 0,
+            verbatim: Vec::new(),
+            overwrite: OverwritePolicy::Overwrite,
+            render_options: RenderOptions::default(),
+            formatters: Arc::new(FormatterRegistry::new()),
         }
     }
 }
@@ -49,6 +449,13 @@ impl Project {
             config: config,
             style: Style::Tera,
             force_packaged: packaged,
+            strict: false,
+            sanitize_paths: false,
+            jobs: 0,
+            verbatim: Vec::new(),
+            overwrite: OverwritePolicy::Overwrite,
+            render_options: RenderOptions::default(),
+            formatters: Arc::new(FormatterRegistry::new()),
         }
     }
 
@@ -58,6 +465,13 @@ impl Project {
             config: Configuration::JavaProps,
             style: Style::ST,
             force_packaged: true,
+            strict: false,
+            sanitize_paths: false,
+            jobs: 0,
+            verbatim: Vec::new(),
+            overwrite: OverwritePolicy::Overwrite,
+            render_options: RenderOptions::default(),
+            formatters: Arc::new(FormatterRegistry::new()),
         }
     }
 
@@ -85,115 +499,1096 @@ impl Project {
     }
 
     pub fn default_params(&self, clone_root: &Path) -> Result<Params> {
+        self.default_params_with(clone_root, &mut Diagnostics::new())
+    }
+
+    /// Same as `default_params`, collecting warnings into `diag`.
+    pub fn default_params_with(&self, clone_root: &Path, diag: &mut Diagnostics) -> Result<Params> {
         let root = self.resolve_root_dir(clone_root);
-        get_defaults(self, &root)
+        let defaults_file = root.join(self.config_name());
+        read_params_with(&defaults_file, self.config, diag)
+    }
+
+    /// Engines selected for files in template config. Only TOML config can select them.
+    pub fn engines(&self, clone_root: &Path) -> Result<Engines> {
+        let root = self.resolve_root_dir(clone_root);
+        let file = root.join(self.config_name());
+        match self.config {
+            Configuration::Toml if fsutils::exists(&file) => Engines::read(&file),
+            _ => Ok(Engines::default()),
+        }
+    }
+
+    /// Files generated only where parameters are true in template config. Only TOML config can
+    /// select them.
+    pub fn conditions(&self, clone_root: &Path) -> Result<Conditions> {
+        let root = self.resolve_root_dir(clone_root);
+        let file = root.join(self.config_name());
+        match self.config {
+            Configuration::Toml if fsutils::exists(&file) => Conditions::read(&file),
+            _ => Ok(Conditions::default()),
+        }
+    }
+
+    /// Files left out of generation by `IGNORE_FILE` and TOML config of the template at
+    /// `clone_root`.
+    pub fn ignores(&self, clone_root: &Path) -> Result<Ignores> {
+        self.ignores_in(&self.resolve_root_dir(clone_root))
+    }
+
+    /// Same as `ignores`, with the template `root` resolved already.
+    fn ignores_in(&self, root: &Path) -> Result<Ignores> {
+        let mut ignores = Ignores::default();
+        let ignore_file = root.join(IGNORE_FILE);
+        if fsutils::exists(&ignore_file) {
+            ignores = Ignores::read(&ignore_file)?;
+        }
+        let file = root.join(self.config_name());
+        if let Configuration::Toml = self.config {
+            if fsutils::exists(&file) {
+                ignores.extend(Ignores::read_toml(&file)?);
+            }
+        }
+        Ok(ignores)
+    }
+
+    /// Commands the template declares to run after generation. Only TOML config can declare them.
+    pub fn hooks(&self, clone_root: &Path) -> Result<Hooks> {
+        let root = self.resolve_root_dir(clone_root);
+        let file = root.join(self.config_name());
+        match self.config {
+            Configuration::Toml if fsutils::exists(&file) => Hooks::read(&file),
+            _ => Ok(Hooks::default()),
+        }
+    }
+
+    /// Parameters declared in template config. Only TOML config can declare them.
+    pub fn manifest(&self, clone_root: &Path) -> Result<Manifest> {
+        let root = self.resolve_root_dir(clone_root);
+        let file = root.join(self.config_name());
+        match self.config {
+            Configuration::Toml if fsutils::exists(&file) => Manifest::read(&file),
+            _ => Ok(Manifest::default()),
+        }
     }
 
-    // TODO: give clear `Err` type
     // TODO: make it run async
+    /// Generate files from the template at `clone_root` into `dest`, returning every file written,
+    /// and those skipped by `overwrite` policy.
+    ///
+    /// Nothing is written with `dry_run`, and files are reported as `preview` does instead.
     pub fn generate(&self,
                     params: &Params,
                     clone_root: &Path,
                     dest: &Path,
                     dry_run: bool)
-                    -> Result<()> {
+                    -> Result<Vec<Written>> {
+        self.generate_with(params, clone_root, dest, dry_run, &mut Diagnostics::new())
+    }
 
-        let root = self.resolve_root_dir(clone_root);
-        let walker = WalkDir::new(&root).into_iter();
+    /// Same as `generate`, collecting warnings into `diag`.
+    ///
+    /// Fails before writing any file if `diag` denies warnings and there are any.
+    pub fn generate_with(&self,
+                         params: &Params,
+                         clone_root: &Path,
+                         dest: &Path,
+                         dry_run: bool,
+                         diag: &mut Diagnostics)
+                         -> Result<Vec<Written>> {
+
+        if dry_run {
+            return self.preview_with(params, clone_root, dest, diag);
+        }
+        trace_span!(INFO, "generate", template = %clone_root.display(), dest = %dest.display());
+        let engines = self.engines(clone_root)?;
+        // formatted values are shared by every file and path name of this run
+        let cache = self.format_cache();
+        let mut plan = self.plan(params, clone_root, dest, diag, &cache)?;
+        let root = plan.root.clone();
+
+        let mut written = Vec::new();
+        fsutils::create_dir_all(dest)?;
+        let files = create_dirs(mem::replace(&mut plan.tree, Vec::new()))?;
+        for file in &plan.backups {
+            fsutils::backup(file)?;
+        }
+        let mut rendered = Vec::with_capacity(files.len());
+        let mut copied = Vec::new();
+        for (src, dest) in files {
+            if self.is_verbatim(src.path())? {
+                copied.push((src, dest));
+            } else {
+                rendered.push((src, dest));
+            }
+        }
+        let copied = parallel_map(&copied, self.threads(), |&(ref src, ref dest)| {
+            fsutils::copy_file(src.path(), dest)
+        });
+        written.extend(copied.into_iter().collect::<Result<Vec<_>>>()?);
+        for (style, files) in group_by_style(rendered, &engines, &root, &self.style) {
+            written.extend(match style {
+                #[cfg(feature = "tera")]
+                Style::Tera => self.generate_with_tera(params, &root, files, &cache)?,
+                #[cfg(feature = "handlebars")]
+                Style::Handlebars => self.generate_with_handlebars(params, &root, files)?,
+                #[cfg(feature = "liquid")]
+                Style::Liquid => self.generate_with_liquid(params, &root, files)?,
+                style => self.generate_tree(style, params, &root, files, &cache)?,
+            });
+        }
+
+        Ok(plan.report(written))
+    }
+
+    /// Render every file of the template at `clone_root` in memory, and report those `generate`
+    /// would write into `dest`, without touching the file system.
+    ///
+    /// Files already in `dest` are reported as `generate` would decide on them by `overwrite`
+    /// policy. Each file is rendered on its own, so that Handlebars partials and Tera
+    /// includes are not resolved.
+    pub fn preview(&self, params: &Params, clone_root: &Path, dest: &Path) -> Result<Vec<Written>> {
+        self.preview_with(params, clone_root, dest, &mut Diagnostics::new())
+    }
+
+    /// Same as `preview`, collecting warnings into `diag`.
+    pub fn preview_with(&self,
+                        params: &Params,
+                        clone_root: &Path,
+                        dest: &Path,
+                        diag: &mut Diagnostics)
+                        -> Result<Vec<Written>> {
+
+        trace_span!(INFO, "preview", template = %clone_root.display(), dest = %dest.display());
+        let engines = self.engines(clone_root)?;
+        let plan = self.plan(params, clone_root, dest, diag, &self.format_cache())?;
+        let root = &plan.root;
+        let files = plan.tree
+            .iter()
+            .filter(|&&(ref src, _)| src.file_type().is_file())
+            .collect::<Vec<_>>();
+        let previewed = parallel_map(&files, self.threads(), |&&(ref src, ref dest)| {
+            let file = src.path().strip_prefix(root).unwrap_or(src.path());
+            let bytes = if self.is_verbatim(src.path())? {
+                let failed = ErrorKind::FsFailure("reading metadata of", src.path().to_path_buf());
+                fs::metadata(src.path()).chain_err(|| failed)?.len()
+            } else {
+                let style = engines.style_of(file, &self.style);
+                let body = fsutils::read_text(src.path())?;
+                self.render_file(&plan.root, style, file, &body, params)?.len() as u64
+            };
+            Ok(Written {
+                path: dest.clone(),
+                bytes: bytes,
+                outcome: if fsutils::exists(dest) { Outcome::Existed } else { Outcome::Created },
+            })
+        });
+        Ok(plan.report(previewed.into_iter().collect::<Result<_>>()?))
+    }
+
+    /// Render every file of the template at `clone_root` in memory, with where it goes relative
+    /// to `dest`, in the order `generate` writes them.
+    ///
+    /// Files already in `dest` are never decided on by `overwrite` policy. Each file is rendered
+    /// on its own, as `preview` does.
+    pub fn render_tree(&self,
+                       params: &Params,
+                       clone_root: &Path,
+                       dest: &Path,
+                       diag: &mut Diagnostics)
+                       -> Result<Vec<(PathBuf, Vec<u8>)>> {
 
+        let engines = self.engines(clone_root)?;
+        let project = Project { overwrite: OverwritePolicy::Overwrite, ..self.clone() };
+        let plan = project.plan(params, clone_root, dest, diag, &self.format_cache())?;
+        let root = &plan.root;
+        let files = plan.tree
+            .iter()
+            .filter(|&&(ref src, _)| src.file_type().is_file())
+            .collect::<Vec<_>>();
+        let rendered = parallel_map(&files, self.threads(), |&&(ref src, ref to)| {
+            let file = src.path().strip_prefix(root).unwrap_or(src.path());
+            let contents = if self.is_verbatim(src.path())? {
+                fs::read(src.path())
+                    .chain_err(|| ErrorKind::FsFailure("reading file", src.path().to_path_buf()))?
+            } else {
+                let style = engines.style_of(file, &self.style);
+                let body = fsutils::read_text(src.path())?;
+                self.render_file(root, style, file, &body, params)?.into_bytes()
+            };
+            Ok((to.strip_prefix(dest).unwrap_or(to).to_path_buf(), contents))
+        });
+        rendered.into_iter().collect()
+    }
+
+    /// Check `params`, and resolve where each entry of the template at `clone_root` goes in
+    /// `dest`, without writing anything.
+    ///
+    /// Entries are in the order they are generated, and existing files are decided on by
+    /// `overwrite` policy. Fails with `ErrorKind::InvalidValue` if the template manifest does not
+    /// allow any value, with `ErrorKind::FileExists` if the policy does not allow overwriting,
+    /// and if `diag` denies warnings and there are any.
+    pub(crate) fn plan(&self,
+                       params: &Params,
+                       clone_root: &Path,
+                       dest: &Path,
+                       diag: &mut Diagnostics,
+                       cache: &FormatCache)
+                       -> Result<Plan> {
+
+        trace_span!(DEBUG, "plan");
+        if !fsutils::is_directory(clone_root) {
+            bail!(ErrorKind::TemplateNotFound(clone_root.display().to_string()));
+        }
+        if self.is_strict() {
+            let unresolved = self.unresolved_params(clone_root, params)?;
+            if !unresolved.is_empty() {
+                bail!(ErrorKind::MissingParams(unresolved));
+            }
+        }
+        self.manifest(clone_root)?.validate(params)?;
+        let root = self.resolve_root_dir(clone_root);
+        let conditions = self.conditions(clone_root)?;
         let mut name_map: HashMap<OsString, String> = HashMap::new();
         let mut tree: Vec<(DirEntry, PathBuf)> = Vec::new();
-        let default_file = root.join(self.config_name());
+        let mut skipped: Vec<PathBuf> = Vec::new();
 
-        for entry in walker.filter_entry(|e| !is_git_metadata(e)) {
-            let entry = entry.unwrap();
-
-            if entry.path() == &root || entry.path() == &default_file {
-                debug!("skipping {:?}", entry.file_name());
+        for entry in self.walk_template(&root)? {
+            let rel_path = entry.path().strip_prefix(&root).unwrap_or(entry.path()).to_path_buf();
+            if skipped.iter().any(|dir| rel_path.starts_with(dir)) {
                 continue;
             }
+            if !conditions.includes(&rel_path, entry.file_type().is_dir(), params) {
+                debug!("skipping {:?} by condition", rel_path);
+                if entry.file_type().is_dir() {
+                    skipped.push(rel_path);
+                }
+                continue;
+            }
+            let dest = resolve_dirname(self, &entry, dest, &mut name_map, params, cache)?;
+            if let Some(rendered) = dest.file_name().map(|s| s.to_string_lossy().into_owned()) {
+                if rendered != entry.file_name().to_string_lossy() &&
+                   diagnostics::is_suspicious_name(&rendered) {
+                    let file = entry.path().strip_prefix(&root).unwrap_or(entry.path());
+                    diag.warn(Warning::SuspiciousPath {
+                        file: file.to_path_buf(),
+                        rendered: rendered,
+                    });
+                }
+            }
+            tree.push((entry, dest));
+        }
+        // spare parsing every file where no value can be warned about, as in most dry runs
+        if params.param_map.values().any(|v| v.is_empty()) {
+            for name in self.collect_vars_with(clone_root, params)?.keys() {
+                if params.get(name).map(|v| v.is_empty()).unwrap_or(false) {
+                    diag.warn(Warning::EmptyValue(name.clone()));
+                }
+            }
+        }
+        diag.check()?;
+        debug!("{:?}", &name_map);
 
-            &tree.push((entry.clone(), resolve_dirname(self, &entry, dest, &mut name_map, params)));
+        let mut plan = Plan {
+            root: root,
+            tree: Vec::with_capacity(tree.len()),
+            skipped: Vec::new(),
+            backups: Vec::new(),
+        };
+        for (entry, dest) in tree {
+            if entry.file_type().is_file() && fsutils::exists(&dest) {
+                let mut policy = self.overwrite.clone();
+                if let OverwritePolicy::Prompt(ask) = policy {
+                    policy = ask(&dest);
+                }
+                match policy {
+                    OverwritePolicy::Skip => {
+                        plan.skipped.push(dest);
+                        continue;
+                    }
+                    OverwritePolicy::Backup => plan.backups.push(dest.clone()),
+                    OverwritePolicy::Fail => bail!(ErrorKind::FileExists(dest)),
+                    OverwritePolicy::Overwrite |
+                    OverwritePolicy::Prompt(_) => {}
+                }
+            }
+            plan.tree.push((entry, dest));
+        }
+        Ok(plan)
+    }
+
+    /// Render a single template `body` with the engine this project generates files with.
+    ///
+    /// `name` identifies the template in error messages.
+    pub fn render(&self, name: &str, body: &str, params: &Params) -> Result<String> {
+        self.render_as(self.style.clone(), name, body, params)
+    }
 
+    /// Same as `render`, with the template written in `style` rather than that of the project.
+    pub fn render_as(&self,
+                     style: Style,
+                     name: &str,
+                     body: &str,
+                     params: &Params)
+                     -> Result<String> {
+        self.render_in(None, style, name, body, params)
+    }
+
+    /// Same as `render_as`, with template `file` under template `root`, so that templates it
+    /// includes are loaded from files there.
+    pub fn render_file(&self,
+                       root: &Path,
+                       style: Style,
+                       file: &Path,
+                       body: &str,
+                       params: &Params)
+                       -> Result<String> {
+        self.render_in(Some(root), style, &file.to_string_lossy(), body, params)
+    }
+
+    fn render_in(&self,
+                 root: Option<&Path>,
+                 style: Style,
+                 name: &str,
+                 body: &str,
+                 params: &Params)
+                 -> Result<String> {
+        let tpl = Template::read_str(style.clone(), body);
+        if self.is_strict() {
+            let unresolved: BTreeSet<String> = tpl.placeholders()
+                .into_iter()
+                .filter(|ph| ph.default_value().is_none())
+                .map(|ph| ph.name().to_owned())
+                .filter(|name| params.get(name).is_none())
+                .collect();
+            if !unresolved.is_empty() {
+                bail!(ErrorKind::MissingParams(unresolved.into_iter().collect()));
+            }
         }
-        // TODO:
-        if !dry_run {
-            fs::create_dir_all(dest).unwrap();
-            match self.style {
-                Style::Tera => self.generate_with_tera(params, tree),
-                _ => self.generate_tree(params, tree)
+
+        let rendered = match style {
+            #[cfg(feature = "tera")]
+            Style::Tera if !self.applies_formatters(&tpl) => {
+                let mut tera = Tera::default();
+                init_tera_filters(&mut tera);
+                tera.add_raw_template(name, body)
+                    .map_err(Error::from)
+                    .and_then(|_| Ok(tera.render(name, tera_context(params))?))
+            }
+            #[cfg(feature = "handlebars")]
+            Style::Handlebars => {
+                let mut hbs = new_handlebars(&self.formatters);
+                hbs.register_template_string(name, body)
+                    .map_err(Error::from)
+                    .and_then(|_| Ok(hbs.render(name, &handlebars_context(params))?))
+            }
+            #[cfg(feature = "liquid")]
+            Style::Liquid => {
+                render_liquid(root.unwrap_or(Path::new(".")), body, params, &self.formatters)
+            }
+            _ => {
+                let mut buf = Vec::with_capacity(tpl.size_hint());
+                let cache = self.format_cache();
+                let written = match root {
+                    Some(root) => {
+                        tpl.write_including(&template_name(Path::new(name)),
+                                             &mut buf,
+                                             params,
+                                             &cache,
+                                             &self.render_options,
+                                             &FileLoader::new(root))
+                            .map(|_| ())
+                    }
+                    None => {
+                        tpl.write_with(&mut buf, params, &cache, &self.render_options).map(|_| ())
+                    }
+                };
+                written.and_then(|_| {
+                    String::from_utf8(buf).map_err(|e| Error::from(e.to_string()))
+                })
+            }
+        };
+        in_template(rendered, Path::new(name), style, body, params)
+    }
+
+    /// Collect every placeholder used in file contents and path names of the template tree.
+    pub fn collect_vars(&self, clone_root: &Path) -> Result<BTreeMap<String, VarUsage>> {
+        self.collect_vars_in(clone_root, None)
+    }
+
+    /// Same as `collect_vars`, leaving out files and directories which `[conditions]` exclude
+    /// from generation with `params`.
+    pub fn collect_vars_with(&self,
+                             clone_root: &Path,
+                             params: &Params)
+                             -> Result<BTreeMap<String, VarUsage>> {
+        self.collect_vars_in(clone_root, Some(params))
+    }
+
+    fn collect_vars_in(&self,
+                       clone_root: &Path,
+                       params: Option<&Params>)
+                       -> Result<BTreeMap<String, VarUsage>> {
+        let root = self.resolve_root_dir(clone_root);
+        let engines = self.engines(clone_root)?;
+        let conditions = match params {
+            Some(_) => self.conditions(clone_root)?,
+            None => Conditions::default(),
+        };
+        let mut vars: BTreeMap<String, VarUsage> = BTreeMap::new();
+        let mut skipped: Vec<PathBuf> = Vec::new();
+
+        for entry in self.walk_template(&root)? {
+            let rel_path = entry.path().strip_prefix(&root).unwrap_or(entry.path()).to_path_buf();
+            if let Some(params) = params {
+                if skipped.iter().any(|dir| rel_path.starts_with(dir)) {
+                    continue;
+                }
+                if !conditions.includes(&rel_path, entry.file_type().is_dir(), params) {
+                    if entry.file_type().is_dir() {
+                        skipped.push(rel_path);
+                    }
+                    continue;
+                }
+            }
+
+            let mut found = Template::read_str(Style::Path, entry.file_name().to_string_lossy())
+                .placeholders();
+            if entry.file_type().is_file() && !self.is_verbatim(entry.path())? {
+                let style = engines.style_of(&rel_path, &self.style);
+                if is_large(entry.path()) {
+                    let analysis = Template::analyze_stream(style, open_stream(entry.path())?)?;
+                    found.extend(analysis.placeholders.into_iter().map(|(_, ph)| ph));
+                } else {
+                    found.extend(Template::read_file(style, entry.path())?.placeholders());
+                }
+            }
+
+            for ph in found {
+                let usage = vars.entry(ph.name().to_owned()).or_default();
+                usage.formatters.insert(ph.formatters().to_vec());
+                usage.files.insert(rel_path.clone());
+                usage.required |= ph.default_value().is_none();
             }
         }
-        debug!("{:?}", &name_map);
 
-        Ok(())
+        Ok(vars)
+    }
+
+    /// Names of parameters referenced in the template tree but have no value in `params`, leaving
+    /// out files which `[conditions]` exclude with them.
+    ///
+    /// Empty value is regarded as missing, so as config entries which are declared without default.
+    /// Parameters with default values written in every reference, and derived ones, are never
+    /// missing.
+    pub fn missing_params(&self, clone_root: &Path, params: &Params) -> Result<Vec<String>> {
+        let vars = self.collect_vars_with(clone_root, params)?;
+        let derived = self.manifest(clone_root)?.derived;
+        let mut missing: BTreeSet<String> = vars.into_iter()
+            .filter(|&(_, ref usage)| usage.required)
+            .map(|(name, _)| name)
+            .filter(|name| !derived.contains_key(name))
+            .filter(|name| params.get(name).map(|v| v.is_empty()).unwrap_or(true))
+            .collect();
+        missing.extend(params.param_map
+            .iter()
+            .filter(|&(_, v)| v.is_empty())
+            .map(|(k, _)| k.clone()));
+        Ok(missing.into_iter().collect())
+    }
+
+    /// Names of parameters referenced in the template tree but not given in `params` at all,
+    /// leaving out those with default values written in every reference, and files which
+    /// `[conditions]` exclude.
+    pub fn unresolved_params(&self, clone_root: &Path, params: &Params) -> Result<Vec<String>> {
+        let vars = self.collect_vars_with(clone_root, params)?;
+        Ok(vars.into_iter()
+            .filter(|&(_, ref usage)| usage.required)
+            .map(|(name, _)| name)
+            .filter(|name| params.get(name).is_none())
+            .collect())
     }
 
-    fn generate_tree(&self, params: &Params, tree: Vec<(DirEntry, PathBuf)>) {
+    /// Parse every file of the template tree strictly, and validate the config file.
+    pub fn lint(&self, clone_root: &Path) -> Result<Vec<Issue>> {
+        let root = self.resolve_root_dir(clone_root);
+        let config = PathBuf::from(self.config_name());
+        let mut issues = Vec::new();
+
+        let defaults = match get_defaults(self, &root) {
+            Ok(params) => Some(params),
+            Err(e) => {
+                let msg = e.iter().map(|e| e.to_string()).collect::<Vec<_>>().join(": ");
+                issues.push(Issue {
+                    file: config.clone(),
+                    position: None,
+                    kind: IssueKind::InvalidConfig(msg),
+                });
+                None
+            }
+        };
+        let engines = match self.engines(clone_root) {
+            Ok(engines) => engines,
+            Err(e) => {
+                let msg = e.iter().map(|e| e.to_string()).collect::<Vec<_>>().join(": ");
+                issues.push(Issue {
+                    file: config.clone(),
+                    position: None,
+                    kind: IssueKind::InvalidConfig(msg),
+                });
+                Engines::default()
+            }
+        };
+        if let Some(tbl) = defaults.as_ref().and_then(|p| p.toml.clone()) {
+            let mut dropped = Diagnostics::new();
+            Params::convert_toml_with(tbl, &mut dropped);
+            for warning in dropped.warnings() {
+                if let Warning::DroppedParam(ref key) = *warning {
+                    issues.push(Issue {
+                        file: config.clone(),
+                        position: None,
+                        kind: IssueKind::UnsupportedValue(key.clone()),
+                    });
+                }
+            }
+        }
+
+        #[cfg(feature = "tera")]
+        let mut tera = {
+            let mut tera = Tera::default();
+            init_tera_filters(&mut tera);
+            tera
+        };
+
+        // the output directory is named after `name`, whether templates refer to it or not
+        let mut used: HashSet<String> = Some("name".to_owned()).into_iter().collect();
+        if let Ok(conditions) = self.conditions(clone_root) {
+            used.extend(conditions.params().into_iter().map(|name| name.to_owned()));
+        }
+        if let Ok(hooks) = self.hooks(clone_root) {
+            // hooks can refer to parameters only through environment variables
+            used.extend(defaults.iter()
+                .flat_map(|params| params.param_map.keys())
+                .filter(|name| {
+                    let var = param_env_name(name);
+                    hooks.post.iter().any(|command| command.contains(&var))
+                })
+                .cloned());
+        }
 
-        for loc in tree {
-            let (src, dest) = loc;
+        for entry in self.walk_template(&root)? {
+            let rel_path = entry.path().strip_prefix(&root).unwrap_or(entry.path()).to_path_buf();
 
-            if src.file_type().is_file() {
+            let mut found: Vec<(Option<Position>, Style, _)> =
+                Template::read_str(Style::Path, entry.file_name().to_string_lossy())
+                    .placeholders()
+                    .into_iter()
+                    .map(|ph| (None, Style::Path, ph))
+                    .collect();
 
-                let mut f = fs::OpenOptions::new()
-                    .write(true)
-                    .truncate(true)
-                    .create(true)
-                    .open(dest.as_path())
-                    .unwrap();
+            if entry.file_type().is_file() && !self.is_verbatim(entry.path())? {
+                let style = engines.style_of(&rel_path, &self.style);
+                // large files are streamed only where the native engine renders them
+                let tpl = if is_native(&style) && is_large(entry.path()) {
+                    None
+                } else {
+                    Some(Template::read_file(style.clone(), entry.path())?)
+                };
+                let analysis = match tpl {
+                    Some(ref tpl) => tpl.analyze(),
+                    None => Template::analyze_stream(style.clone(), open_stream(entry.path())?)?,
+                };
+                used.extend(analysis.params.iter().cloned());
 
-                let mut tpl = Template::read_file(self.style.clone(),
-                                                  &src.path())
-                    .unwrap();
-                tpl.write_to(&mut f, &params.param_map).unwrap();
-                f.sync_data().unwrap();
+                let malformed = match (style.clone(), tpl) {
+                    // native parser only knows substitutions, so let Tera judge the syntax
+                    #[cfg(feature = "tera")]
+                    (Style::Tera, Some(tpl)) => {
+                        tera.add_raw_template(&rel_path.to_string_lossy(), tpl.body())
+                            .err()
+                            .map(|e| {
+                                (None,
+                                 e.iter().map(|e| e.to_string()).collect::<Vec<_>>().join(": "))
+                            })
+                    }
+                    #[cfg(feature = "handlebars")]
+                    (Style::Handlebars, Some(tpl)) => {
+                        handlebars::Template::compile(tpl.body())
+                            .err()
+                            .map(|e| (None, e.to_string()))
+                    }
+                    #[cfg(feature = "liquid")]
+                    (Style::Liquid, Some(tpl)) => {
+                        liquid::parse(tpl.body(), LiquidOptions::default())
+                            .err()
+                            .map(|e| (None, e.to_string()))
+                    }
+                    _ => {
+                        analysis.error
+                            .map(|pos| (Some(pos), "cannot parse placeholder".to_owned()))
+                    }
+                };
+                if let Some((position, msg)) = malformed {
+                    issues.push(Issue {
+                        file: rel_path.clone(),
+                        position: position,
+                        kind: IssueKind::Malformed(msg),
+                    });
+                }
+                found.extend(analysis.placeholders
+                    .into_iter()
+                    .map(|(pos, ph)| (Some(pos), style.clone(), ph)));
+            }
 
-            } else if src.file_type().is_dir() {
-                fs::create_dir_all(dest.as_path()).expect("Creating directory");
+            for (pos, style, ph) in found {
+                used.insert(ph.name().to_owned());
+                let unknown: Vec<&str> = match style {
+                    #[cfg(feature = "tera")]
+                    Style::Tera => {
+                        ph.filter_names()
+                            .iter()
+                            .filter(|name| tera.get_filter(name).is_err())
+                            .map(|name| name.as_ref())
+                            .collect()
+                    }
+                    // filters of Liquid standard library cannot be told from unknown ones
+                    #[cfg(feature = "liquid")]
+                    Style::Liquid => Vec::new(),
+                    _ => ph.unknown_formatters(),
+                };
+                let unknown = unknown.into_iter().filter(|name| !self.formatters.contains(name));
+                for formatter in unknown {
+                    issues.push(Issue {
+                        file: rel_path.clone(),
+                        position: pos,
+                        kind: IssueKind::UnknownFormatter {
+                            param: ph.name().to_owned(),
+                            formatter: formatter.to_owned(),
+                        },
+                    });
+                }
+                if let Some(ref params) = defaults {
+                    if params.get(ph.name()).is_none() {
+                        issues.push(Issue {
+                            file: rel_path.clone(),
+                            position: pos,
+                            kind: IssueKind::UndeclaredParam(ph.name().to_owned()),
+                        });
+                    }
+                }
+            }
+        }
+        if let Some(ref params) = defaults {
+            for name in params.param_map.keys().chain(params.lists.keys()) {
+                if !used.contains(name) {
+                    issues.push(Issue {
+                        file: config.clone(),
+                        position: None,
+                        kind: IssueKind::UnusedParam(name.clone()),
+                    });
+                }
             }
         }
+
+        Ok(issues)
+    }
+
+    /// Walk the template tree, skipping git metadata, template root, template metadata files and
+    /// files ignored.
+    fn walk_template(&self, root: &Path) -> Result<Vec<DirEntry>> {
+        let default_file = root.join(self.config_name());
+        let ignore_file = root.join(IGNORE_FILE);
+        let suite_dir = root.join(SUITE_DIR);
+        let ignores = self.ignores_in(root)?;
+        // sorted, so that generation reports files and errors in the same order every time
+        let walker = WalkDir::new(root).sort_by(|a, b| a.cmp(b)).into_iter();
+        let is_ignored = |e: &DirEntry| {
+            let rel_path = e.path().strip_prefix(root).unwrap_or(e.path());
+            e.path() != root && ignores.matches(rel_path, e.file_type().is_dir())
+        };
+        // test cases of the template are not a part of it
+        let is_suite = |e: &DirEntry| e.path() == suite_dir.as_path();
+
+        let mut entries = Vec::new();
+        let walker = walker.filter_entry(|e| !is_git_metadata(e) && !is_suite(e) && !is_ignored(e));
+        for entry in walker {
+            let entry = entry.map_err(|e| Error::from(e.to_string()))?;
+
+            if entry.path() == root || entry.path() == &default_file ||
+               entry.path() == &ignore_file {
+                debug!("skipping {:?}", entry.file_name());
+                continue;
+            }
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+
+    /// Whether the template file at `path` is copied byte for byte, rather than rendered.
+    ///
+    /// Files with extensions in `fsutils::BINARY_EXTENSIONS` or `verbatim` are, and so are those
+    /// `fsutils::looks_binary` judges not a text.
+    pub fn is_verbatim(&self, path: &Path) -> Result<bool> {
+        if self.has_verbatim_extension(path) {
+            return Ok(true);
+        }
+        fsutils::looks_binary(path)
+    }
+
+    /// Whether `path` has an extension of files copied as they are, without looking into it.
+    pub(crate) fn has_verbatim_extension(&self, path: &Path) -> bool {
+        let ext = match path.extension() {
+            Some(ext) => ext.to_string_lossy().to_lowercase(),
+            None => return false,
+        };
+        fsutils::BINARY_EXTENSIONS.contains(&&*ext) ||
+        self.verbatim.iter().any(|v| v.trim_start_matches('.').eq_ignore_ascii_case(&ext))
+    }
+
+    /// Whether placeholders without value fail generation, by `strict` or `render_options`.
+    fn is_strict(&self) -> bool {
+        self.strict || self.render_options.missing == MissingKeyBehavior::Error
+    }
+
+    /// Whether any placeholder of `tpl` applies a formatter of `formatters`.
+    ///
+    /// Tera takes filters only as functions, not closures, so templates which do are rendered by
+    /// the built-in engine instead.
+    #[cfg(feature = "tera")]
+    fn applies_formatters(&self, tpl: &Template) -> bool {
+        !self.formatters.is_empty() &&
+        tpl.placeholders().iter().any(|ph| ph.uses_any(&self.formatters))
+    }
+
+    /// Cache of formatted values for a generation run, applying `formatters`.
+    pub(crate) fn format_cache(&self) -> FormatCache {
+        FormatCache::with_registry(self.formatters.clone())
+    }
+
+    /// Number of threads to render files with.
+    fn threads(&self) -> usize {
+        if self.jobs > 0 {
+            self.jobs
+        } else {
+            thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+        }
+    }
+
+    fn generate_tree(&self,
+                     style: Style,
+                     params: &Params,
+                     root: &Path,
+                     files: Vec<(DirEntry, PathBuf)>,
+                     cache: &FormatCache)
+                     -> Result<Vec<Written>> {
+
+        parallel_map(&files, self.threads(), |&(ref src, ref dest)| {
+            // parse just before rendering, so that only one file at a time is held per thread
+            let file = src.path().strip_prefix(root).unwrap_or(src.path());
+            trace_span!(DEBUG, "generate_file", file = %file.display(), style = ?style);
+            let loader = FileLoader::new(root);
+            let written = if is_large(src.path()) {
+                let input = open_stream(src.path())?;
+                fsutils::write_with(dest, |f| {
+                    let rendered = Template::write_stream_including(style.clone(),
+                                                                    &template_name(file),
+                                                                    input,
+                                                                    f,
+                                                                    params,
+                                                                    cache,
+                                                                    &self.render_options,
+                                                                    &loader);
+                    // the body is not held, so placeholders failed cannot be located
+                    in_template(rendered, file, style.clone(), "", params)
+                })
+            } else {
+                let tpl = Template::read_file(style.clone(), src.path())?;
+                fsutils::write_with(dest, |f| {
+                    let rendered = tpl.write_including(&template_name(file),
+                                                 f,
+                                                 params,
+                                                 cache,
+                                                 &self.render_options,
+                                                 &loader)
+                        .map(|_| ());
+                    in_template(rendered, file, style.clone(), tpl.body(), params)
+                })
+            };
+            written.and_then(|written| keep_permissions(src, written))
+        })
+            .into_iter()
+            .collect()
     }
 
+    #[cfg(feature = "tera")]
     fn generate_with_tera(&self,
                           params: &Params,
-                          tree: Vec<(DirEntry, PathBuf)>) {
+                          root: &Path,
+                          files: Vec<(DirEntry, PathBuf)>,
+                          cache: &FormatCache)
+                          -> Result<Vec<Written>> {
+
+        let mut files = files;
+        let mut custom = Vec::new();
+        if !self.formatters.is_empty() {
+            for (src, dest) in mem::replace(&mut files, Vec::new()) {
+                if self.applies_formatters(&Template::read_file(Style::Tera, src.path())?) {
+                    custom.push((src, dest));
+                } else {
+                    files.push((src, dest));
+                }
+            }
+        }
+        let mut written = self.generate_tree(Style::Tera, params, root, custom, cache)?;
 
         let mut tera = Tera::default();
-        let mut ctx = Context::new();
+        let ctx = tera_context(params);
         init_tera_filters(&mut tera);
 
-        // TODO: which toml table will be used in context?
-        for (k, v) in &params.param_map {
-            &ctx.add(&k, &v);
+        let bodies = parallel_map(&files, self.threads(), |&(ref src, _)| {
+            fsutils::read_text(src.path())
+        });
+        let bodies = bodies.into_iter().collect::<Result<Vec<_>>>()?;
+        for (&(ref src, ref dest), body) in files.iter().zip(bodies.iter()) {
+            let file = src.path().strip_prefix(root).unwrap_or(src.path());
+            tera.add_raw_template(dest.to_string_lossy().as_ref(), body)
+                .chain_err(|| ErrorKind::RenderFailure(file.to_path_buf(), None))?;
         }
+        debug!("{:?}", &tera.templates);
 
-        for ref loc in &tree {
-            let (ref src, ref dest) = **loc;
-            if src.file_type().is_file() {
-                tera.add_template_file(&src.path(),
-                                       Some(dest.to_string_lossy().as_ref()))
-                    .unwrap();
-            }
+        let jobs = files.iter().zip(bodies.iter()).collect::<Vec<_>>();
+        let rendered = parallel_map(&jobs, self.threads(), |&(&(ref src, ref dest), body)| {
+            debug!("{:?} => {:?}", &src, &dest);
+            let file = src.path().strip_prefix(root).unwrap_or(src.path());
+            trace_span!(DEBUG, "generate_file", file = %file.display(), style = "tera");
+            let content = tera.render(dest.to_string_lossy().as_ref(), ctx.clone())
+                .map_err(Error::from);
+            let content = in_template(content, file, Style::Tera, body, params)?;
+            fsutils::write_file(dest, &content).and_then(|written| keep_permissions(src, written))
+        });
+        written.extend(rendered.into_iter().collect::<Result<Vec<_>>>()?);
+        Ok(written)
+    }
+
+    #[cfg(feature = "handlebars")]
+    fn generate_with_handlebars(&self,
+                                params: &Params,
+                                root: &Path,
+                                files: Vec<(DirEntry, PathBuf)>)
+                                -> Result<Vec<Written>> {
+
+        let mut hbs = new_handlebars(&self.formatters);
+        let bodies = parallel_map(&files, self.threads(), |&(ref src, _)| {
+            fsutils::read_text(src.path())
+        });
+        let bodies = bodies.into_iter().collect::<Result<Vec<_>>>()?;
+        // named after the path from template root, so that partials are included by it
+        for (&(ref src, _), body) in files.iter().zip(bodies.iter()) {
+            let file = src.path().strip_prefix(root).unwrap_or(src.path());
+            hbs.register_template_string(&file.to_string_lossy(), &**body)
+                .chain_err(|| ErrorKind::RenderFailure(file.to_path_buf(), None))?;
         }
-        debug!("{:?}", &tera.templates);
 
-        for loc in tree {
-            let (src, dest) = loc;
+        let ctx = handlebars_context(params);
+        let jobs = files.iter().zip(bodies.iter()).collect::<Vec<_>>();
+        parallel_map(&jobs, self.threads(), |&(&(ref src, ref dest), body)| {
             debug!("{:?} => {:?}", &src, &dest);
+            let file = src.path().strip_prefix(root).unwrap_or(src.path());
+            trace_span!(DEBUG, "generate_file", file = %file.display(), style = "handlebars");
+            let content = hbs.render(&file.to_string_lossy(), &ctx)
+                .map_err(Error::from);
+            let content = in_template(content, file, Style::Handlebars, body, params)?;
+            fsutils::write_file(dest, &content).and_then(|written| keep_permissions(src, written))
+        })
+            .into_iter()
+            .collect()
+    }
 
-            if src.file_type().is_file() {
+    #[cfg(feature = "liquid")]
+    fn generate_with_liquid(&self,
+                            params: &Params,
+                            root: &Path,
+                            files: Vec<(DirEntry, PathBuf)>)
+                            -> Result<Vec<Written>> {
 
-                let content = tera
-                    .render(dest.to_string_lossy().as_ref(), ctx.clone())
-                    .unwrap();
+        // Liquid templates are neither `Send` nor `Sync`, so each file is parsed by its thread
+        parallel_map(&files, self.threads(), |&(ref src, ref dest)| {
+            debug!("{:?} => {:?}", &src, &dest);
+            let file = src.path().strip_prefix(root).unwrap_or(src.path());
+            trace_span!(DEBUG, "generate_file", file = %file.display(), style = "liquid");
+            let body = fsutils::read_text(src.path())?;
+            let content = render_liquid(root, &body, params, &self.formatters);
+            let content = in_template(content, file, Style::Liquid, &body, params)?;
+            fsutils::write_file(dest, &content).and_then(|written| keep_permissions(src, written))
+        })
+            .into_iter()
+            .collect()
+    }
+}
 
-                fsutils::write_file(&dest, &content).unwrap();
-            } else {
-                fs::create_dir_all(dest.as_path()).expect("Creating directory");
-            }
+/// Name of template `file` relative to template root, as `Loader` resolves includes from.
+fn template_name(file: &Path) -> String {
+    file.components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Bytes of template files rendered by `Template::write_stream_including` rather than read whole.
+const STREAM_THRESHOLD: u64 = 16 * 1024 * 1024;
+
+/// Whether template `file` is larger than `STREAM_THRESHOLD`.
+fn is_large(file: &Path) -> bool {
+    fs::metadata(file).map(|m| m.len() > STREAM_THRESHOLD).unwrap_or(false)
+}
+
+fn open_stream(file: &Path) -> Result<BufReader<fs::File>> {
+    fs::File::open(file)
+        .map(BufReader::new)
+        .chain_err(|| ErrorKind::FsFailure("opening file", file.to_path_buf()))
+}
+
+/// Whether templates in `style` are rendered by the native engine, which streams large files.
+fn is_native(style: &Style) -> bool {
+    match *style {
+        #[cfg(feature = "tera")]
+        Style::Tera => false,
+        #[cfg(feature = "handlebars")]
+        Style::Handlebars => false,
+        #[cfg(feature = "liquid")]
+        Style::Liquid => false,
+        _ => true,
+    }
+}
+
+/// Give the file `written` the permissions of template file `src` it is rendered from, so that
+/// e.g. scripts stay executable.
+fn keep_permissions(src: &DirEntry, written: Written) -> Result<Written> {
+    fsutils::copy_permissions(src.path(), &written.path)?;
+    Ok(written)
+}
+
+/// Group `files` by the style selected for each of them, keeping their order within each group.
+fn group_by_style(files: Vec<(DirEntry, PathBuf)>,
+                  engines: &Engines,
+                  root: &Path,
+                  default: &Style)
+                  -> Vec<(Style, Vec<(DirEntry, PathBuf)>)> {
+    let mut groups: Vec<(Style, Vec<(DirEntry, PathBuf)>)> = Vec::new();
+    for (src, dest) in files {
+        let style = {
+            let file = src.path().strip_prefix(root).unwrap_or(src.path());
+            engines.style_of(file, default)
+        };
+        match groups.iter().position(|&(ref s, _)| *s == style) {
+            Some(i) => groups[i].1.push((src, dest)),
+            None => groups.push((style, vec![(src, dest)])),
         }
     }
+    groups
+}
+
+/// Create directories in `tree` in order, leaving files to render.
+fn create_dirs(tree: Vec<(DirEntry, PathBuf)>) -> Result<Vec<(DirEntry, PathBuf)>> {
+    let mut files = Vec::new();
+    for (src, dest) in tree {
+        if src.file_type().is_file() {
+            files.push((src, dest));
+        } else if src.file_type().is_dir() {
+            fsutils::create_dir_all(&dest)?;
+        }
+    }
+    Ok(files)
+}
+
+/// Apply `f` to every item on up to `threads` threads, keeping results in order of `items`.
+///
+/// Make sure to collect results in that order, so that the first error reported is the same
+/// regardless of scheduling.
+fn parallel_map<T, R, F>(items: &[T], threads: usize, f: F) -> Vec<R>
+    where T: Sync,
+          R: Send,
+          F: Fn(&T) -> R + Sync
+{
+    let threads = threads.min(items.len());
+    if threads <= 1 {
+        return items.iter().map(f).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    // so that spans entered by `f` on workers are children of the caller's
+    #[cfg(feature = "tracing")]
+    let parent = ::tracing::Span::current();
+    let mut done = thread::scope(|scope| {
+        let workers = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    #[cfg(feature = "tracing")]
+                    let _parent = parent.enter();
+                    let mut done = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::SeqCst);
+                        if i >= items.len() {
+                            return done;
+                        }
+                        done.push((i, f(&items[i])));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        workers.into_iter()
+            .flat_map(|w| w.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+            .collect::<Vec<_>>()
+    });
+    done.sort_by_key(|&(i, _)| i);
+    done.into_iter().map(|(_, r)| r).collect()
+}
+
+/// Attach template `file` to render error, with position of the offending placeholder in `body`
+/// if it can be told.
+///
+/// Tera does not report positions, so the first placeholder without value is blamed for its
+/// failures.
+#[cfg_attr(not(feature = "tera"), allow(unused_variables))]
+fn in_template<T>(result: Result<T>,
+                  file: &Path,
+                  style: Style,
+                  body: &str,
+                  params: &Params)
+                  -> Result<T> {
+    let e = match result {
+        Ok(v) => return Ok(v),
+        Err(e) => e,
+    };
+    let position = match *e.kind() {
+        ErrorKind::Parse(line, column) => {
+            Some(Position {
+                line: line,
+                column: column,
+            })
+        }
+        ErrorKind::Formatter(ref param, _, ref formatter, _) => {
+            Template::read_str(style, body)
+                .scan()
+                .placeholders
+                .into_iter()
+                .find(|&(_, ref ph)| {
+                    ph.name() == param && ph.formatters().iter().any(|f| f.name() == formatter)
+                })
+                .map(|(pos, _)| pos)
+        }
+        #[cfg(feature = "tera")]
+        ErrorKind::Tera(_) => {
+            Template::read_str(style, body)
+                .scan()
+                .placeholders
+                .into_iter()
+                .find(|&(_, ref ph)| !params.param_map.contains_key(ph.name()))
+                .map(|(pos, _)| pos)
+        }
+        _ => None,
+    };
+    Err(e).chain_err(|| ErrorKind::RenderFailure(file.to_path_buf(), position))
 }
 
 fn is_git_metadata(entry: &DirEntry) -> bool {
@@ -205,8 +1600,9 @@ fn resolve_dirname(project: &Project,
                    entry: &DirEntry,
                    dest_root: &Path,
                    alt_paths: &mut HashMap<OsString, String>,
-                   params: &Params)
-                   -> PathBuf
+                   params: &Params,
+                   cache: &FormatCache)
+                   -> Result<PathBuf>
 {
 
     let mut segment: Vec<&OsStr> = Vec::new();
@@ -236,55 +1632,159 @@ fn resolve_dirname(project: &Project,
         }
     }
 
-    let mut buf = Vec::new();
     // FIXME: we need to re-design `Template` so we can manipulate its elements
     let mut pkg = base.to_string_lossy();
     if pkg.as_ref() == "$package$" && project.force_packaged {
         pkg = Cow::from("$package__packaged$");
     }
-    Template::write_once(&mut buf,
-                         Style::Path,
-                         pkg,
-                         &params.param_map)
-        .unwrap();
-
-    let name = String::from_utf8(buf).unwrap();
+    let name = render_name(&pkg,
+                           params,
+                           cache,
+                           &project.render_options,
+                           project.sanitize_paths)?;
     if &name != base.to_string_lossy().as_ref() {
         alt_paths.insert(base.to_os_string(), name.clone());
     }
     dest.push(&name);
     debug!("Destination entry: {:?}", dest);
 
-    dest
+    Ok(dest)
 }
 
 fn get_defaults(project: &Project, root_dir: &Path) -> Result<Params> {
     let defaults_file = root_dir.join(project.config_name());
+    read_params(&defaults_file, project.config)
+}
+
+/// Read parameters from a file written in given configuration format.
+pub fn read_params(defaults_file: &Path, config: Configuration) -> Result<Params> {
+    read_params_with(defaults_file, config, &mut Diagnostics::new())
+}
 
+/// Same as `read_params`, collecting warnings into `diag`.
+pub fn read_params_with(defaults_file: &Path,
+                        config: Configuration,
+                        diag: &mut Diagnostics)
+                        -> Result<Params> {
     // TODO: get default value from specific toml table if there is any
-    match project.config {
+    match config {
         Configuration::JavaProps => {
-            fs::File::open(&defaults_file)
-                .map(|f| {
-                    let props = java_properties::read(f).unwrap();
-                    Params::from_map(props)
-                })
-                .map_err(|e| ErrorKind::Io(e).into()) // Should convert ParseError
+            let f = fs::File::open(defaults_file)
+                .chain_err(|| ErrorKind::FsFailure("opening file", defaults_file.to_path_buf()))?;
+            java_properties::read(f)
+                .map(Params::from_map)
+                .chain_err(|| ErrorKind::PropertiesDecodeFailure)
         }
         Configuration::Toml => {
-            fsutils::read_file(&defaults_file)
-                .map(|s| {
-                    let tbl: toml::value::Table = toml::from_str(&s).unwrap();
-                    Params::convert_toml(tbl)
-                })
-                .chain_err(|| ErrorKind::TomlDecodeFailure)
+            let s = fsutils::read_file(defaults_file)?;
+            let toml = toml::from_str::<toml::value::Table>(&s)
+                .chain_err(|| ErrorKind::TomlDecodeFailure)?;
+            // defaults declared in `[params]` fill parameters not given at top level
+            let declared = Manifest::from_toml(&toml)?.defaults();
+            let mut params = Params::convert_toml_with(toml, diag);
+            for (k, v) in declared.param_map {
+                params.param_map.entry(k).or_insert(v);
+            }
+            Ok(params)
         }
     }
 }
 
+/// Write resolved parameters into TOML file, which can be read back with `read_params`. List
+/// parameters are written as arrays of strings.
+pub fn write_answers(file: &Path, params: &Params) -> Result<()> {
+    let mut answers = params.param_map
+        .iter()
+        .filter(|&(k, _)| k != NOW_PARAM)
+        .map(|(k, v)| (k.clone(), toml::Value::String(v.clone())))
+        .collect::<toml::value::Table>();
+    for (k, items) in &params.lists {
+        let items = items.iter().map(|item| toml::Value::String(item.clone())).collect();
+        answers.insert(k.clone(), toml::Value::Array(items));
+    }
+    let s = toml::to_string(&answers)?;
+    fsutils::write_file(file, &s)?;
+    Ok(())
+}
+
+/// Tera context holding every parameter as a string, and every list parameter as an array of
+/// strings.
+#[cfg(feature = "tera")]
+fn tera_context(params: &Params) -> Context {
+    let mut ctx = Context::new();
+    for (k, v) in &params.param_map {
+        ctx.add(k, v);
+    }
+    // Tera resolves dotted names through objects, so nested tables are added as they are
+    for (k, v) in params.toml.iter().flat_map(|toml| toml) {
+        if let toml::Value::Table(_) = *v {
+            if !RESERVED_TABLES.contains(&k.as_str()) {
+                ctx.add(k, v);
+            }
+        }
+    }
+    for (k, items) in &params.lists {
+        ctx.add(k, items);
+    }
+    ctx
+}
+
+/// Handlebars context holding every parameter as a string, and every list parameter as an
+/// array of strings.
+#[cfg(feature = "handlebars")]
+fn handlebars_context(params: &Params) -> Json {
+    let mut ctx = params.param_map
+        .iter()
+        .map(|(k, v)| (k.clone(), Json::String(v.clone())))
+        .collect::<BTreeMap<_, _>>();
+    for (k, items) in &params.lists {
+        let items = items.iter().map(|item| Json::String(item.clone())).collect();
+        ctx.insert(k.clone(), Json::Array(items));
+    }
+    Json::Object(ctx)
+}
+
+/// Handlebars registry with the formatters, and `formatters` defined by users, registered as
+/// helpers, writing values without escaping.
+#[cfg(feature = "handlebars")]
+fn new_handlebars(formatters: &Arc<FormatterRegistry>) -> Handlebars {
+    let mut hbs = Handlebars::new();
+    hbs.register_escape_fn(handlebars::no_escape);
+    helpers::register(&mut hbs);
+    helpers::register_all(&mut hbs, formatters);
+    hbs
+}
+
+/// Render Liquid template `body`, including partials from `root`.
+#[cfg(feature = "liquid")]
+fn render_liquid(root: &Path,
+                 body: &str,
+                 params: &Params,
+                 formatters: &Arc<FormatterRegistry>)
+                 -> Result<String> {
+    let options = LiquidOptions {
+        template_repository: Box::new(LocalTemplateRepository::new(root.to_path_buf())),
+        ..Default::default()
+    };
+    let tpl = liquid::parse(body, options)?;
+    let mut ctx = liquid::Context::new();
+    liquid_filters::register(&mut ctx);
+    liquid_filters::register_all(&mut ctx, formatters);
+    for (k, v) in &params.param_map {
+        ctx.set_val(k, liquid::Value::Str(v.clone()));
+    }
+    for (k, items) in &params.lists {
+        let items = items.iter().map(|v| liquid::Value::Str(v.clone())).collect();
+        ctx.set_val(k, liquid::Value::Array(items));
+    }
+    Ok(tpl.render(&mut ctx)?.unwrap_or_default())
+}
+
+#[cfg(feature = "tera")]
 fn init_tera_filters(tera: &mut Tera) {
     tera.register_filter("decap", filters::decap);
     tera.register_filter("word", filters::word);
+    tera.register_filter("space", filters::space);
     tera.register_filter("hyphen", filters::hyphen);
     tera.register_filter("start", filters::start);
     tera.register_filter("Camel", filters::upper_camel);
@@ -293,4 +1793,11 @@ fn init_tera_filters(tera: &mut Tera) {
     tera.register_filter("snake", filters::snake);
     tera.register_filter("packaged", filters::packaged);
     tera.register_filter("random", filters::random);
+    // Tera does not allow hyphens in filter names, so hyphenated cases go by their aliases
+    tera.register_filter("kebab", filters::kebab_case);
+    tera.register_filter("snake_case", filters::snake_case);
+    tera.register_filter("camelCase", filters::camel_case);
+    tera.register_filter("PascalCase", filters::pascal_case);
+    tera.register_filter("SCREAMING_SNAKE_CASE", filters::screaming_snake_case);
+    tera.register_filter("train", filters::train_case);
 }
\ No newline at end of file
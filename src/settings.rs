@@ -0,0 +1,100 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::path::PathBuf;
+
+use toml;
+use toml::value::Value;
+
+use super::errors::*;
+use super::fsutils;
+
+/// Template registered by name in user configuration.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Registered {
+    /// Repository to clone, in any form `rig <repository>` accepts.
+    pub source: String,
+    /// Branch to check out.
+    pub version: Option<String>,
+}
+
+/// User wide configuration of Rig.
+///
+/// Templates can be registered either in short form or as a table:
+///
+/// ```toml
+/// [templates]
+/// scala = "typesafehub/scala-sbt.g8"
+///
+/// [templates.rust]
+/// source = "https://github.com/me/rust-template"
+/// version = "v2"
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Settings {
+    pub templates: BTreeMap<String, Registered>,
+}
+
+impl Settings {
+    /// Location of user configuration file.
+    ///
+    /// Looks up `RIG_CONFIG`, then `$XDG_CONFIG_HOME/rig/config.toml`, then falls back to
+    /// `~/.config/rig/config.toml`.
+    pub fn path() -> Option<PathBuf> {
+        if let Some(file) = env::var_os("RIG_CONFIG") {
+            return Some(PathBuf::from(file));
+        }
+        if let Some(dir) = env::var_os("XDG_CONFIG_HOME") {
+            return Some(PathBuf::from(dir).join("rig").join("config.toml"));
+        }
+        fsutils::home_dir().map(|home| home.join(".config").join("rig").join("config.toml"))
+    }
+
+    /// Read user configuration, or empty one if there is no configuration file.
+    pub fn load() -> Result<Settings> {
+        match Settings::path() {
+            Some(ref file) if fsutils::exists(file) => {
+                let s = fsutils::read_file(file)?;
+                Settings::from_toml(&s)
+            }
+            _ => Ok(Settings::default()),
+        }
+    }
+
+    pub fn from_toml(s: &str) -> Result<Settings> {
+        let tbl: toml::value::Table = toml::from_str(s).chain_err(|| ErrorKind::TomlDecodeFailure)?;
+        let mut settings = Settings::default();
+
+        if let Some(templates) = tbl.get("templates").and_then(|v| v.as_table()) {
+            for (name, value) in templates {
+                let registered = match *value {
+                    Value::String(ref source) => {
+                        Registered {
+                            source: source.clone(),
+                            version: None,
+                        }
+                    }
+                    Value::Table(ref t) => {
+                        let source = t.get("source")
+                            .and_then(|v| v.as_str())
+                            .ok_or_else(|| {
+                                Error::from(format!("template `{}` has no `source`", name))
+                            })?;
+                        Registered {
+                            source: source.to_owned(),
+                            version: t.get("version").and_then(|v| v.as_str()).map(|s| s.to_owned()),
+                        }
+                    }
+                    _ => bail!("template `{}` must be a string or a table", name),
+                };
+                settings.templates.insert(name.clone(), registered);
+            }
+        }
+
+        Ok(settings)
+    }
+
+    /// Find template registered as `name`.
+    pub fn template(&self, name: &str) -> Option<&Registered> {
+        self.templates.get(name)
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::errors::*;
+use super::fsutils;
+use super::template::{Style, Template};
+
+/// Files of template skeleton as `(path, contents)`.
+///
+/// Contents are rendered as giter8 style template with `name` and `author` of the new template,
+/// so placeholders meant for the new template are written in Tera syntax or escaped.
+type Skeleton = &'static [(&'static str, &'static str)];
+
+const RIG_SKELETON: Skeleton = &[
+    ("Rig.toml",
+     r#"# Parameters of $name$ template, with their default values.
+# Users are asked to confirm each of them when generating project.
+name = "My Project"
+description = "Project generated from $name$ template"
+author = "$author$"
+package = "com.example"
+"#),
+    (".rigignore",
+     r#"# Files in template tree which are not copied into generated project.
+# One glob pattern per line, e.g. `*.lock`, or `target/` for directories only.
+"#),
+    ("README.md",
+     r#"# {{ name }}
+
+{{ description }}
+
+Created by {{ author | start }}.
+"#),
+    ("src/$package__packaged$/$name__norm$.txt",
+     r#"File and directory names can have placeholders in `\$param__format\$` form.
+
+This file lives in `{{ package | packaged }}`.
+"#),
+];
+
+const GITER8_SKELETON: Skeleton = &[
+    ("src/main/g8/default.properties",
+     r#"# Parameters of $name$ template, with their default values.
+name = My Project
+description = Project generated from $name$ template
+author = $author$
+package = com.example
+"#),
+    ("src/main/g8/.rigignore",
+     r#"# Files in template tree which are not copied into generated project.
+# One glob pattern per line, e.g. `*.lock`, or `target/` for directories only.
+"#),
+    ("src/main/g8/README.md",
+     r#"# \$name\$
+
+\$description\$
+
+Created by \$author;format="start"\$.
+"#),
+    ("src/main/g8/src/main/scala/$package$/$name__Camel$.scala",
+     r#"package \$package\$
+
+object \$name;format="Camel"\$ extends App {
+  println("Hello, \$name\$!")
+}
+"#),
+];
+
+/// Create a new template skeleton named `name` into `dest`, which must not exist or be empty.
+///
+/// Give `giter8` to create it in giter8 layout. Returns created files.
+pub fn init(dest: &Path, name: &str, author: &str, giter8: bool) -> Result<Vec<PathBuf>> {
+    if fsutils::is_directory(dest) {
+        let mut entries = fs::read_dir(dest)
+            .chain_err(|| ErrorKind::FsFailure("reading directory", dest.to_path_buf()))?;
+        if entries.next().is_some() {
+            bail!(ErrorKind::DirectoryNotEmpty(dest.to_path_buf()));
+        }
+    }
+
+    let mut params = HashMap::new();
+    params.insert("name".to_owned(), name.to_owned());
+    params.insert("author".to_owned(), author.to_owned());
+
+    let skeleton = if giter8 { GITER8_SKELETON } else { RIG_SKELETON };
+    let mut created = Vec::new();
+    for &(path, contents) in skeleton {
+        let path = dest.join(path);
+        if let Some(parent) = path.parent() {
+            fsutils::create_dir_all(parent)?;
+        }
+
+        let rendered = Template::render_once(Style::ST, contents, &params)?;
+        fsutils::write_file(&path, &rendered)?;
+        created.push(path);
+    }
+
+    Ok(created)
+}
@@ -0,0 +1,225 @@
+//! Remote template repositories: resolving where they are, and cloning or fetching them into
+//! local cache to generate projects from.
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+use git2::{self, Config as Git2Config, FetchOptions, ObjectType, Repository};
+use git2::build::{CheckoutBuilder, RepoBuilder};
+use tempdir::TempDir;
+use url::Url;
+
+use super::cache;
+use super::errors::*;
+use super::fsutils;
+
+/// Template repository, with the branch or tag to check out.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Source {
+    pub url: Url,
+    /// Branch or tag to check out, or `None` for the default branch.
+    pub version: Option<String>,
+}
+
+/// Template checked out from its repository, either in local cache or in a temporal directory
+/// removed when this is dropped.
+#[derive(Debug)]
+pub struct Checkout {
+    temp_dir: Option<TempDir>,
+    path: PathBuf,
+}
+
+impl Checkout {
+    /// Template at `path` in `temp_dir`, e.g. extracted from an archive.
+    #[cfg(feature = "archive")]
+    pub(crate) fn in_temp_dir(temp_dir: TempDir, path: PathBuf) -> Checkout {
+        Checkout {
+            temp_dir: Some(temp_dir),
+            path: path,
+        }
+    }
+
+    /// Root of the working tree.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the template is kept in local cache after this is dropped.
+    pub fn is_cached(&self) -> bool {
+        self.temp_dir.is_none()
+    }
+}
+
+impl Source {
+    /// Source of `repository` given as URL, or as GitHub shorthand like `user/repo.g8`.
+    pub fn parse(repository: &str, version: Option<&str>) -> Result<Source> {
+        if !repository.contains('/') {
+            bail!(ErrorKind::InvalidUrlFormat(repository.to_owned()));
+        }
+        let url = Url::parse(repository)
+            .or_else(|_| Url::parse(&format!("https://github.com/{}", repository)))?;
+        Ok(Source {
+            url: url,
+            version: version.map(|v| v.to_owned()),
+        })
+    }
+
+    /// Check out the template in local cache, or in a temporal directory if there is no cache
+    /// directory available.
+    pub fn fetch(&self) -> Result<Checkout> {
+        match cache::cache_dir() {
+            Some(cache_dir) => {
+                let path = cache::entry_path(&cache_dir, &self.url);
+                self.fetch_into(&path)?;
+                Ok(Checkout {
+                    temp_dir: None,
+                    path: path,
+                })
+            }
+            None => {
+                let temp_dir = TempDir::new("rig__template")
+                    .chain_err(|| "Failed to create temporal directory")?;
+                let path = temp_dir.path().to_path_buf();
+                self.fetch_into(&path)?;
+                Ok(Checkout {
+                    temp_dir: Some(temp_dir),
+                    path: path,
+                })
+            }
+        }
+    }
+
+    /// Check out the template into `dir`, fetching the repository already cloned there if it
+    /// has the same `origin`, or cloning it over whatever is there otherwise.
+    pub fn fetch_into(&self, dir: &Path) -> Result<()> {
+        if let Some(repo) = self.open_clone(dir) {
+            info!("Fetching cached git repository: {:?} in {:?}", self.url, dir);
+            match self.update(&repo) {
+                Ok(()) => return Ok(()),
+                Err(e) => warn!("Failed to update cached template, cloning again: {}", e),
+            }
+        }
+
+        if fsutils::exists(dir) {
+            fsutils::remove_dir(dir).chain_err(|| "Failed to clean cached template")?;
+        }
+        fsutils::create_dir_all(dir)?;
+        info!("Cloning remote git repository: {:?} into {:?}", self.url, dir);
+        let mut builder = RepoBuilder::new();
+        builder.fetch_options(fetch_options());
+        let repo = builder.clone(self.url.as_str(), dir)?;
+        if let Some(ref version) = self.version {
+            checkout(&repo, version)?;
+        }
+        Ok(())
+    }
+
+    /// Check out the template already in local cache, without connecting to the remote.
+    ///
+    /// Without version, whatever was checked out last time is used as is. Fails with
+    /// `ErrorKind::NotCached` if the repository has never been fetched.
+    pub fn cached(&self) -> Result<Checkout> {
+        let not_cached = || ErrorKind::NotCached(self.url.to_string());
+        let path = cache::cache_dir()
+            .map(|dir| cache::entry_path(&dir, &self.url))
+            .ok_or_else(&not_cached)?;
+        let repo = self.open_clone(&path).ok_or_else(&not_cached)?;
+        info!("Using cached git repository: {:?} in {:?}", self.url, path);
+        if let Some(ref version) = self.version {
+            checkout(&repo, version)?;
+        }
+        Ok(Checkout {
+            temp_dir: None,
+            path: path,
+        })
+    }
+
+    /// Repository in `dir`, if it is a clone of this source.
+    fn open_clone(&self, dir: &Path) -> Option<Repository> {
+        let repo = match Repository::open(dir) {
+            Ok(repo) => repo,
+            Err(_) => return None,
+        };
+        let same_origin = repo.find_remote("origin")
+            .ok()
+            .and_then(|r| r.url().map(|url| url == self.url.as_str()))
+            .unwrap_or(false);
+        if same_origin { Some(repo) } else { None }
+    }
+
+    /// Fetch `origin` of cached `repo`, and check out the version again.
+    fn update(&self, repo: &Repository) -> Result<()> {
+        let mut origin = repo.find_remote("origin")?;
+        let refspecs = ["+refs/heads/*:refs/remotes/origin/*", "+refs/tags/*:refs/tags/*"];
+        origin.fetch(&refspecs, Some(&mut fetch_options()), None)?;
+        let version = match self.version {
+            Some(ref version) => version.clone(),
+            None => {
+                // the default branch is what was checked out by the first clone
+                let head = repo.head()?;
+                match head.shorthand() {
+                    Some(branch) if head.is_branch() => branch.to_owned(),
+                    _ => bail!("HEAD of cached template is not a branch"),
+                }
+            }
+        };
+        checkout(repo, &version)
+    }
+}
+
+/// Check out `version` of `repo`, either a branch of `origin` or a tag.
+///
+/// Branches are checked out as local branches reset to `origin`, and tags as detached `HEAD`.
+fn checkout(repo: &Repository, version: &str) -> Result<()> {
+    let branch = format!("refs/remotes/origin/{}", version);
+    let (object, is_branch) = match repo.revparse_single(&branch) {
+        Ok(object) => (object, true),
+        Err(_) => {
+            let tag = repo.revparse_single(&format!("refs/tags/{}", version))
+                .chain_err(|| format!("No branch or tag `{}` in template repository", version))?;
+            (tag, false)
+        }
+    };
+    let commit = repo.find_commit(object.peel(ObjectType::Commit)?.id())?;
+    repo.checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().force()))?;
+    if is_branch {
+        repo.branch(version, &commit, true)?;
+        repo.set_head(&format!("refs/heads/{}", version))?;
+    } else {
+        repo.set_head_detached(commit.id())?;
+    }
+    Ok(())
+}
+
+/// Options fetching through the proxy configured, if any.
+fn fetch_options<'a>() -> FetchOptions<'a> {
+    let mut fetch = FetchOptions::new();
+    if let Some(proxy_url) = proxy_url() {
+        debug!("Proxy settings found, initializing fetch options.");
+        let mut proxy = git2::ProxyOptions::new();
+        proxy.url(proxy_url.as_ref());
+        fetch.proxy_options(proxy);
+    } else {
+        debug!("No proxy settings found.")
+    }
+    fetch
+}
+
+/// Proxy of `http_proxy` environment variable, or of `http.proxy` in global git config.
+pub fn proxy_url() -> Option<Url> {
+    if let Some(env_val) = env::var_os("http_proxy") {
+        debug!("Setting proxy configuration from environment key: `http_proxy`.");
+        return Url::parse(&env_val.to_string_lossy()).ok();
+    }
+    let global_conf = match Git2Config::find_global() {
+        Ok(global_conf) => global_conf,
+        Err(_) => return None,
+    };
+    match Git2Config::open(global_conf.as_path()) {
+        Ok(config) => config.get_string("http.proxy").ok().and_then(|v| Url::parse(&v).ok()),
+        Err(_) => {
+            warn!("Cannot locate or open git global configuration");
+            None
+        }
+    }
+}
@@ -0,0 +1,85 @@
+//! `proptest` strategies generating styles, templates and parameters, for property tests of this
+//! crate and of code built on it.
+//!
+//! Only built with `test-util` feature. Generated templates are always well formed, and every
+//! value formats with every formatter they use.
+
+use std::collections::HashMap;
+
+use proptest::collection::{hash_map, vec};
+use proptest::prelude::*;
+
+use super::template::Style;
+
+/// Any style the native parser reads.
+pub fn style() -> BoxedStrategy<Style> {
+    prop_oneof![Just(Style::ST),
+                Just(Style::Path),
+                Just(Style::Tera),
+                Just(Style::Handlebars),
+                Just(Style::Liquid)]
+        .boxed()
+}
+
+/// Parameter name, never a keyword of any style, e.g. `else` of Handlebars, nor having `__`
+/// which separates formatters in `Style::Path`.
+pub fn param_name() -> BoxedStrategy<String> {
+    "p[a-z0-9]{0,8}".boxed()
+}
+
+/// Parameter value of words and spaces.
+pub fn param_value() -> BoxedStrategy<String> {
+    "[A-Za-z0-9 ]{0,16}".boxed()
+}
+
+/// Parameters given to templates, as names to values.
+pub fn params() -> BoxedStrategy<HashMap<String, String>> {
+    hash_map(param_name(), param_value(), 0..8).boxed()
+}
+
+/// Text without delimiters or escapes of `style`, which is rendered as is.
+pub fn raw_text(style: &Style) -> BoxedStrategy<String> {
+    match *style {
+        Style::ST | Style::Path => "[^$\\\\]{0,32}".boxed(),
+        _ => "[^{}\\\\]{0,32}".boxed(),
+    }
+}
+
+/// Placeholder of `style` referring to `name`, formatted with `formatter` if any.
+pub fn placeholder(style: &Style, name: &str, formatter: Option<&str>) -> String {
+    match (style, formatter) {
+        (&Style::ST, None) | (&Style::Path, None) => format!("${}$", name),
+        (&Style::ST, Some(f)) => format!("${};format=\"{}\"$", name, f),
+        (&Style::Path, Some(f)) => format!("${}__{}$", name, f),
+        (&Style::Handlebars, Some(f)) => format!("{{{{ {} {} }}}}", f, name),
+        (_, None) => format!("{{{{ {} }}}}", name),
+        (_, Some(f)) => format!("{{{{ {} | {} }}}}", name, f),
+    }
+}
+
+/// Template of `style` alternating raw text and placeholders, referring to parameters of
+/// `param_name` and formatted with total formatters, e.g. `snake`.
+pub fn template(style: Style) -> BoxedStrategy<String> {
+    let formatter = prop_oneof![Just(None), Just(Some("snake")), Just(Some("upper"))];
+    let piece = (raw_text(&style), param_name(), formatter);
+    (raw_text(&style), vec(piece, 0..8))
+        .prop_map(move |(head, pieces)| {
+            let mut body = head;
+            for (raw, name, formatter) in pieces {
+                body.push_str(&placeholder(&style, &name, formatter));
+                body.push_str(&raw);
+            }
+            body
+        })
+        .boxed()
+}
+
+/// Style paired with raw text of it.
+pub fn styled_raw_text() -> BoxedStrategy<(Style, String)> {
+    style().prop_flat_map(|style| (Just(style.clone()), raw_text(&style))).boxed()
+}
+
+/// Style paired with a template of it.
+pub fn styled_template() -> BoxedStrategy<(Style, String)> {
+    style().prop_flat_map(|style| (Just(style.clone()), template(style))).boxed()
+}
@@ -1,19 +1,98 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::hash::BuildHasher;
 use std::convert::From;
-use std::io::{self, Write};
-use std::path::Path;
+#[cfg(feature = "json")]
+use std::convert::TryFrom;
+use std::io::{self, BufRead, BufWriter, IoSlice, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
 
+use combine::ParseError;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+#[cfg(feature = "json")]
+use serde_json;
+#[cfg(feature = "yaml")]
+use serde_yaml;
 use toml::value::{Table, Value};
 
-use super::format::{self, Formatter};
-use super::fsutils;
-use super::parser;
+use super::diagnostics::{Diagnostics, Warning};
+use super::errors::*;
+use super::format::{self, Formatter, FormatterRegistry};
+use super::fsutils::{self, Text};
+use super::i18n::{Lang, Message};
+use super::parser::{self, Chunk};
+
+/// Table in TOML config selecting engines of files, e.g. `"_includes/*.html" = "liquid"`.
+pub const ENGINES_TABLE: &'static str = "engines";
+
+/// Table in TOML config generating files only where a parameter is true, e.g.
+/// `"ci/**" = "use_ci"`.
+pub const CONDITIONS_TABLE: &'static str = "conditions";
+
+/// Table in TOML config declaring parameters, see `manifest` module.
+pub const MANIFEST_TABLE: &'static str = "params";
+
+/// Table in TOML config deriving parameters from others, see `expr` module.
+pub const DERIVED_TABLE: &'static str = "derived";
+
+/// Table in TOML config leaving files out of generation, e.g. `patterns = ["target/**"]`.
+pub const IGNORE_TABLE: &'static str = "ignore";
+
+/// Table in TOML config declaring commands run after generation, see `hooks` module.
+pub const HOOKS_TABLE: &'static str = "hooks";
+
+/// Tables in TOML config configuring the template, rather than giving parameters.
+pub const RESERVED_TABLES: &'static [&'static str] = &[ENGINES_TABLE,
+                                                      CONDITIONS_TABLE,
+                                                      MANIFEST_TABLE,
+                                                      DERIVED_TABLE,
+                                                      IGNORE_TABLE,
+                                                      HOOKS_TABLE];
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Style {
     Tera,
     ST,
     Path,
+    Handlebars,
+    Liquid,
+    /// Placeholders between delimiters chosen for the template, for files clashing with those
+    /// of the other styles.
+    Custom(Delimiters),
+}
+
+/// Delimiters of `Style::Custom` placeholders, e.g. `<<` and `>>` with `|` for
+/// `<<name|snake|upper>>`.
+///
+/// Custom style has placeholders only, no `if` blocks nor `for` loops. The opening delimiter
+/// preceded by a backslash is written as is.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Delimiters {
+    pub open: String,
+    pub close: String,
+    /// Separator of the parameter name and formatters.
+    pub arg_sep: char,
+}
+
+impl Delimiters {
+    /// Fails where either delimiter is empty, or `arg_sep` could be taken as a part of names.
+    pub fn new(open: &str, close: &str, arg_sep: char) -> Result<Delimiters> {
+        if open.is_empty() || close.is_empty() {
+            bail!("Delimiters must not be empty");
+        }
+        if arg_sep.is_alphanumeric() || arg_sep.is_whitespace() || "_-.".contains(arg_sep) {
+            bail!("{:?} cannot separate formatters, as it may be a part of names", arg_sep);
+        }
+        Ok(Delimiters {
+            open: open.to_owned(),
+            close: close.to_owned(),
+            arg_sep: arg_sep,
+        })
+    }
 }
 
 impl Default for Style {
@@ -23,33 +102,240 @@ impl Default for Style {
 }
 
 impl Style {
+    /// Style of engine name as written in template config, e.g. `liquid`.
+    ///
+    /// `Style::Path` is not an engine, so it has no name.
+    pub fn from_name(name: &str) -> Option<Style> {
+        match name {
+            "tera" => Some(Style::Tera),
+            "giter8" | "st" => Some(Style::ST),
+            "handlebars" => Some(Style::Handlebars),
+            "liquid" => Some(Style::Liquid),
+            _ => None,
+        }
+    }
+
     fn arg_sep(&self) -> char {
         match self {
-            &Style::Tera => '|',
+            &Style::Tera | &Style::Liquid => '|',
             &Style::ST => ',',
             &Style::Path => '_',
+            &Style::Handlebars => ' ',
+            &Style::Custom(ref delims) => delims.arg_sep,
+        }
+    }
+
+    fn open_delim(&self) -> &str {
+        match self {
+            &Style::Tera | &Style::Handlebars | &Style::Liquid => "{{",
+            &Style::Custom(ref delims) => &delims.open,
+            _ => "$",
+        }
+    }
+
+    fn close_delim(&self) -> &str {
+        match self {
+            &Style::Tera | &Style::Handlebars | &Style::Liquid => "}}",
+            &Style::Custom(ref delims) => &delims.close,
+            _ => "$",
+        }
+    }
+
+    /// Opening delimiter of `if` blocks, if the style has them.
+    fn block_delim(&self) -> Option<&'static str> {
+        match self {
+            &Style::Tera | &Style::Liquid => Some("{%"),
+            &Style::ST => Some("$"),
+            _ => None,
         }
     }
 }
 
-#[derive(Clone, Debug, Default)]
+/// What a placeholder is rendered as where its parameter has no value.
+///
+/// Placeholders with default value of their own are never taken as missing.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MissingKeyBehavior {
+    /// Fail with `ErrorKind::MissingParams`, listing every parameter missing, before writing
+    /// anything.
+    Error,
+    /// Write the placeholder as it is written in the template.
+    KeepPlaceholder,
+    /// Write nothing.
+    Empty,
+    /// Write the default value of the placeholder, or the name of the parameter if it has none.
+    UseDefault,
+}
+
+impl Default for MissingKeyBehavior {
+    fn default() -> MissingKeyBehavior {
+        MissingKeyBehavior::UseDefault
+    }
+}
+
+/// Source of templates `include` tags refer to, by names like relative paths, e.g.
+/// `partials/header.txt`.
+pub trait Loader {
+    /// Name of the template `path` refers to where template `from` includes it, relative to the
+    /// directory of `from`.
+    fn resolve(&self, path: &str, from: &str) -> String {
+        resolve_include(path, from)
+    }
+
+    /// Body of template `name`, failing with `ErrorKind::TemplateNotFound` if there is none.
+    fn load(&self, name: &str) -> Result<String>;
+}
+
+/// Templates kept in memory by their names.
+impl<S: BuildHasher> Loader for HashMap<String, String, S> {
+    fn load(&self, name: &str) -> Result<String> {
+        self.get(name).cloned().ok_or_else(|| ErrorKind::TemplateNotFound(name.to_owned()).into())
+    }
+}
+
+impl Loader for BTreeMap<String, String> {
+    fn load(&self, name: &str) -> Result<String> {
+        self.get(name).cloned().ok_or_else(|| ErrorKind::TemplateNotFound(name.to_owned()).into())
+    }
+}
+
+/// Templates read from files under `root`.
+#[derive(Clone, Debug)]
+pub struct FileLoader {
+    pub root: PathBuf,
+}
+
+impl FileLoader {
+    pub fn new<P: AsRef<Path>>(root: P) -> FileLoader {
+        FileLoader { root: root.as_ref().to_path_buf() }
+    }
+}
+
+impl Loader for FileLoader {
+    fn load(&self, name: &str) -> Result<String> {
+        let path = self.root.join(name);
+        if !path.is_file() {
+            bail!(ErrorKind::TemplateNotFound(name.to_owned()));
+        }
+        fsutils::read_file(&path)
+    }
+}
+
+/// `path` relative to the directory of `from`, or to the root where it starts with `/`, with `.`
+/// and `..` resolved. `..` never goes above the root.
+fn resolve_include(path: &str, from: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    if !path.starts_with('/') {
+        parts.extend(from.split('/'));
+        parts.pop();
+    }
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+/// Options of rendering templates with the built-in engine.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct RenderOptions {
+    pub missing: MissingKeyBehavior,
+}
+
+/// Map of parameter values which placeholders look up by their names.
+pub trait ParamMap {
+    fn lookup(&self, name: &str) -> Option<&String>;
+
+    /// Items of list parameter `for` loops iterate over. Maps have no lists unless they tell.
+    fn lookup_list(&self, _name: &str) -> Option<&[String]> {
+        None
+    }
+
+    /// Type conditions compare the value of `name` by. Values are strings unless maps tell.
+    fn lookup_type(&self, _name: &str) -> Option<ParamType> {
+        None
+    }
+}
+
+/// Type of parameter value, as written in TOML.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ParamType {
+    String,
+    Boolean,
+    Integer,
+    Float,
+}
+
+/// Parameter value of its type. Values are kept as strings, as they are substituted, and typed
+/// when conditions compare them.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub enum ParamValue {
+    String(String),
+    Boolean(bool),
+    Integer(i64),
+    Float(f64),
+}
+
+impl ParamValue {
+    /// `s` read as value of `ty`, or as string if it is not one.
+    pub fn parse(s: &str, ty: ParamType) -> ParamValue {
+        let typed = match ty {
+            ParamType::String => None,
+            ParamType::Boolean => s.trim().parse().ok().map(ParamValue::Boolean),
+            ParamType::Integer => s.trim().parse().ok().map(ParamValue::Integer),
+            ParamType::Float => s.trim().parse().ok().map(ParamValue::Float),
+        };
+        typed.unwrap_or_else(|| ParamValue::String(s.to_owned()))
+    }
+}
+
+impl<S: BuildHasher> ParamMap for HashMap<String, String, S> {
+    fn lookup(&self, name: &str) -> Option<&String> {
+        self.get(name)
+    }
+}
+
+impl ParamMap for BTreeMap<String, String> {
+    fn lookup(&self, name: &str) -> Option<&String> {
+        self.get(name)
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
 pub struct Placeholder {
     name: String,
     args: Vec<Formatter>,
+    filters: Vec<String>,
+    /// Built-in formatter of each name of `filters`, `Formatter::Ident` if none, parsed once so
+    /// that names user-defined formatters may take are kept along with them.
+    parsed: Vec<Formatter>,
+    default: Option<String>,
 }
 
 impl Placeholder {
     pub fn new(name: &str, arg_expr: Option<String>, style: Style) -> Placeholder {
         let sep = style.arg_sep();
-        Placeholder {
-            name: name.into(),
-            args: arg_expr.map(|expr| {
-                expr.split(sep)
-                    .map(|s| Formatter::from(s.as_ref()))
-                    .filter(|f| *f != Formatter::Ident)
+        let filters: Vec<String> = arg_expr.map(|expr| {
+                split_filters(&expr, sep)
+                    .into_iter()
+                    .map(|s| s.trim().to_owned())
+                    .filter(|s| !s.is_empty())
                     .collect()
             })
-            .unwrap_or(Vec::new()),
+            .unwrap_or(Vec::new());
+        let parsed: Vec<Formatter> = filters.iter().map(|s| Formatter::from(s.as_ref())).collect();
+        Placeholder {
+            name: name.into(),
+            args: parsed.iter().filter(|f| **f != Formatter::Ident).cloned().collect(),
+            filters: filters,
+            parsed: parsed,
+            default: None,
         }
     }
 
@@ -57,35 +343,524 @@ impl Placeholder {
         Placeholder::new(name, None, Style::ST)
     }
 
-    /// Apply formatting on the placeholder with given context, and returns formatted `String`.
-    pub fn format_with(&self, params: &HashMap<String, String>) -> String {
-        if let Some(v) = params.get(&self.name) {
-            self.args.iter().fold(v.clone(), |ref s, f| format::format(&s, *f))
-        } else {
-            self.name.clone()
+    /// Same placeholder, formatting `default` where the parameter has no value.
+    pub fn with_default(mut self, default: Option<String>) -> Placeholder {
+        self.default = default;
+        self
+    }
+
+    /// Value written in the placeholder to use where the parameter has no value, e.g.
+    /// `Anonymous` of `$author;default="Anonymous"$`.
+    pub fn default_value(&self) -> Option<&str> {
+        self.default.as_ref().map(|d| d.as_str())
+    }
+
+    /// Name of the parameter this placeholder refers to.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Formatters applied to the value, in order.
+    pub fn formatters(&self) -> &[Formatter] {
+        &self.args
+    }
+
+    /// Formatter names as written in the template, including unknown ones.
+    pub fn filter_names(&self) -> &[String] {
+        &self.filters
+    }
+
+    /// Formatter names which don't match any of built-in formatters.
+    pub fn unknown_formatters(&self) -> Vec<&str> {
+        self.filters
+            .iter()
+            .zip(&self.parsed)
+            .filter(|&(_, f)| *f == Formatter::Ident)
+            .map(|(s, _)| s.as_ref())
+            .collect()
+    }
+
+    /// Apply formatting on the placeholder with given context, and returns formatted value.
+    ///
+    /// Default value of the placeholder is formatted where the parameter has no value, and the
+    /// name of the parameter is returned as is if neither is given. The value is borrowed as is
+    /// unless any formatter changes it.
+    pub fn format_with<'a, M: ParamMap>(&'a self, params: &'a M) -> Cow<'a, str> {
+        match params.lookup(&self.name).map(|v| v.as_str()).or(self.default_value()) {
+            Some(v) => {
+                let mut value = Cow::Borrowed(v);
+                for f in &self.args {
+                    value = apply(value, f.clone());
+                }
+                value
+            }
+            None => Cow::Borrowed(&self.name),
+        }
+    }
+
+    /// Same as `format_with`, applying formatters of `registry` in preference to built-in ones
+    /// of the same names. Names neither registered nor built-in leave the value as is.
+    pub fn format_with_registry<'a, M>(&'a self,
+                                       params: &'a M,
+                                       registry: &FormatterRegistry)
+                                       -> Cow<'a, str>
+        where M: ParamMap
+    {
+        if !self.uses_any(registry) {
+            return self.format_with(params);
+        }
+        match params.lookup(&self.name).map(|v| v.as_str()).or(self.default_value()) {
+            Some(v) => {
+                let mut value = Cow::Borrowed(v);
+                for (name, f) in self.filters.iter().zip(&self.parsed) {
+                    let custom = registry.apply(name, &value);
+                    value = match custom {
+                        Some(formatted) => Cow::Owned(formatted),
+                        None => apply(value, f.clone()),
+                    };
+                }
+                value
+            }
+            None => Cow::Borrowed(&self.name),
+        }
+    }
+
+    /// Same as `format_with`, but fails if any formatter cannot be applied to the value it gets.
+    pub fn try_format_with<M: ParamMap>(&self, params: &M) -> Result<String> {
+        self.try_format_value(params.lookup(&self.name))
+    }
+
+    /// Same as `try_format_with`, with the value of the parameter already looked up.
+    pub fn try_format_value(&self, value: Option<&String>) -> Result<String> {
+        self.try_format_str(value.map(|v| v.as_str())).map(Cow::into_owned)
+    }
+
+    /// Same as `try_format_value`, borrowing the value as is unless any formatter changes it.
+    pub fn try_format_str<'a>(&'a self, value: Option<&'a str>) -> Result<Cow<'a, str>> {
+        let mut value = match value.or(self.default_value()) {
+            Some(v) => Cow::Borrowed(v),
+            None => return Ok(Cow::Borrowed(&self.name)),
+        };
+        for f in &self.args {
+            value = self.try_apply(value, f.clone())?;
+        }
+        Ok(value)
+    }
+
+    /// Same as `try_format_str`, applying formatters of `registry` in preference to built-in
+    /// ones of the same names, as `format_with_registry` does.
+    pub fn try_format_str_with<'a>(&'a self,
+                                   value: Option<&'a str>,
+                                   registry: &FormatterRegistry)
+                                   -> Result<Cow<'a, str>> {
+        if !self.uses_any(registry) {
+            return self.try_format_str(value);
+        }
+        let mut value = match value.or(self.default_value()) {
+            Some(v) => Cow::Borrowed(v),
+            None => return Ok(Cow::Borrowed(&self.name)),
+        };
+        for (name, f) in self.filters.iter().zip(&self.parsed) {
+            let custom = registry.apply(name, &value);
+            value = match custom {
+                Some(formatted) => Cow::Owned(formatted),
+                None => self.try_apply(value, f.clone())?,
+            };
+        }
+        Ok(value)
+    }
+
+    /// Whether any formatter of the placeholder is registered in `registry`.
+    pub fn uses_any(&self, registry: &FormatterRegistry) -> bool {
+        !registry.is_empty() && self.filters.iter().any(|name| registry.contains(name))
+    }
+
+    fn try_apply<'a>(&self, value: Cow<'a, str>, f: Formatter) -> Result<Cow<'a, str>> {
+        if let Some(reason) = format::validate(&value, f.clone()) {
+            bail!(ErrorKind::Formatter(self.name.clone(),
+                                       value.into_owned(),
+                                       f.name().to_owned(),
+                                       reason.to_owned()));
+        }
+        Ok(apply(value, f))
+    }
+}
+
+/// Condition of `if` block, evaluated on parameters when the template is rendered.
+///
+/// Parameters not given never satisfy conditions, unless negated.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Condition {
+    /// Parameter has non-empty value, e.g. `$if(name)$` or `{% if name %}`. Boolean parameter
+    /// has to be `true`.
+    NonEmpty(String),
+    /// Parameter is `true`, `yes` or `y` ignoring case, e.g. `$if(name.truthy)$`.
+    Truthy(String),
+    /// Parameter has the value, compared by the type of the parameter, e.g.
+    /// `{% if license == "MIT" %}` or `{% if workers == 4 %}`.
+    Equals(String, String),
+    /// Parameter compares to the value, as numbers if both are, or as strings otherwise, e.g.
+    /// `{% if workers > 4 %}`.
+    Compare(String, Comparison, String),
+    /// Negation, e.g. `{% if not name %}` or `{% if license != "MIT" %}`.
+    Not(Box<Condition>),
+}
+
+/// Operator of `Condition::Compare`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Comparison::Less => ordering == Ordering::Less,
+            Comparison::LessOrEqual => ordering != Ordering::Greater,
+            Comparison::Greater => ordering == Ordering::Greater,
+            Comparison::GreaterOrEqual => ordering != Ordering::Less,
         }
     }
 }
 
+impl Condition {
+    /// Name of the parameter the condition tests.
+    pub fn name(&self) -> &str {
+        match *self {
+            Condition::NonEmpty(ref name) |
+            Condition::Truthy(ref name) |
+            Condition::Equals(ref name, _) |
+            Condition::Compare(ref name, ..) => name,
+            Condition::Not(ref cond) => cond.name(),
+        }
+    }
+
+    pub fn holds<M: ParamMap>(&self, params: &M) -> bool {
+        self.holds_with(&|name| params.lookup(name), &|name| params.lookup_type(name))
+    }
+
+    /// Same as `holds`, looking values up with `lookup`, and their types with `types`.
+    fn holds_with<'a, F, T>(&self, lookup: &F, types: &T) -> bool
+        where F: Fn(&str) -> Option<&'a String>,
+              T: Fn(&str) -> Option<ParamType>
+    {
+        match *self {
+            Condition::NonEmpty(ref name) => {
+                lookup(name).map_or(false, |v| {
+                    match ParamValue::parse(v, types(name).unwrap_or(ParamType::String)) {
+                        ParamValue::Boolean(b) => b,
+                        _ => !v.is_empty(),
+                    }
+                })
+            }
+            Condition::Truthy(ref name) => {
+                lookup(name).map_or(false, |v| {
+                    let v = v.trim().to_lowercase();
+                    v == "true" || v == "yes" || v == "y"
+                })
+            }
+            Condition::Equals(ref name, ref value) => {
+                lookup(name).map_or(false, |v| match types(name) {
+                    Some(ParamType::String) | None => v == value,
+                    // `8` equals `8.0`, as they compare in `Compare`
+                    Some(ParamType::Integer) | Some(ParamType::Float) => {
+                        match (v.trim().parse::<f64>(), value.parse::<f64>()) {
+                            (Ok(a), Ok(b)) => a == b,
+                            _ => v == value,
+                        }
+                    }
+                    Some(ty) => ParamValue::parse(v, ty) == ParamValue::parse(value, ty),
+                })
+            }
+            Condition::Compare(ref name, op, ref value) => {
+                lookup(name).map_or(false, |v| {
+                    let ordering = match (v.trim().parse::<f64>(), value.parse::<f64>()) {
+                        (Ok(a), Ok(b)) => a.partial_cmp(&b),
+                        _ => Some(v.as_str().cmp(value)),
+                    };
+                    ordering.map_or(false, |o| op.holds(o))
+                })
+            }
+            Condition::Not(ref cond) => !cond.holds_with(lookup, types),
+        }
+    }
+}
+
+/// Tag the parser found after raw text: a placeholder, or a delimiter of `if` block or `for`
+/// loop.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Tag {
+    Placeholder(Placeholder),
+    If(Condition),
+    Else,
+    EndIf,
+    /// Start of loop binding each item of the list parameter to a variable, as
+    /// `(variable, list)`.
+    For(String, String),
+    EndFor,
+    /// Template of the path rendered in place, e.g. `{% include "header.txt" %}`.
+    Include(String),
+}
+
+/// Split formatters written in a placeholder by `sep`, except where it is in arguments of a
+/// formatter, e.g. `,` of `replace("a", "b")`.
+fn split_filters(expr: &str, sep: char) -> Vec<&str> {
+    let mut filters = Vec::new();
+    let (mut start, mut depth, mut quote) = (0, 0, None);
+    for (i, c) in expr.char_indices() {
+        match (quote, c) {
+            (Some(q), _) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') if depth > 0 => depth -= 1,
+            (None, _) if c == sep && depth == 0 => {
+                filters.push(&expr[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    filters.push(&expr[start..]);
+    filters
+}
+
+/// Format `value` with `f`, keeping it as is if `f` doesn't change it.
+fn apply(value: Cow<str>, f: Formatter) -> Cow<str> {
+    let formatted = match format::format(&value, f) {
+        Cow::Owned(s) => Some(s),
+        Cow::Borrowed(_) => None,
+    };
+    match formatted {
+        Some(s) => Cow::Owned(s),
+        None => value,
+    }
+}
+
+/// Results of formatter chains applied to parameter values, shared by renders within a
+/// generation run so that each chain runs once per distinct value.
+///
+/// Chains including `random` formatter are never memoized, so each occurrence gets its own value.
+#[derive(Debug, Default)]
+pub struct FormatCache {
+    results: Mutex<HashMap<Vec<Formatter>, HashMap<String, String>>>,
+    /// User-defined formatters, applied in preference to built-in ones of the same names.
+    registry: Arc<FormatterRegistry>,
+}
+
+impl FormatCache {
+    pub fn new() -> FormatCache {
+        FormatCache::default()
+    }
+
+    /// Cache formatting placeholders with formatters of `registry` as well as built-in ones.
+    pub fn with_registry(registry: Arc<FormatterRegistry>) -> FormatCache {
+        FormatCache { registry: registry, ..FormatCache::default() }
+    }
+
+    /// Same as `Placeholder::try_format_str_with` the registry of the cache, reusing the result
+    /// of earlier calls.
+    ///
+    /// Failures are not memoized, nor values no formatter changes, nor chains including any
+    /// user-defined formatter.
+    pub fn format<'a>(&self, ph: &'a Placeholder, value: Option<&'a str>) -> Result<Cow<'a, str>> {
+        if ph.uses_any(&self.registry) {
+            return ph.try_format_str_with(value, &self.registry);
+        }
+        let value = match value {
+            Some(v) if !ph.args.is_empty() && !ph.args.contains(&Formatter::AddRandom) => v,
+            _ => return ph.try_format_str(value),
+        };
+        if let Some(done) = self.lock().get(&ph.args[..]).and_then(|m| m.get(value)) {
+            return Ok(Cow::Owned(done.clone()));
+        }
+        // format outside of the lock, so that other threads are not kept waiting
+        let formatted = ph.try_format_str(Some(value))?;
+        if let Cow::Owned(ref s) = formatted {
+            self.lock()
+                .entry(ph.args.clone())
+                .or_insert_with(HashMap::new)
+                .insert(value.to_owned(), s.clone());
+        }
+        Ok(formatted)
+    }
+
+    /// Number of distinct formatter chain and value pairs memoized.
+    pub fn len(&self) -> usize {
+        self.lock().values().map(|m| m.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> MutexGuard<HashMap<Vec<Formatter>, HashMap<String, String>>> {
+        // results are inserted whole, so a panic elsewhere never leaves them broken
+        self.results.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Line and column (both start from 1) in template body.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    /// Locate the byte `offset` of `body`.
+    pub fn at(body: &str, offset: usize) -> Position {
+        let mut offset = offset.min(body.len());
+        while !body.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let before = &body[..offset];
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        Position {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+/// Result of scanning whole template without rendering.
+#[derive(Clone, Debug, Default)]
+pub struct Scan {
+    /// Placeholders in order of appearance.
+    pub placeholders: Vec<(Position, Placeholder)>,
+    /// Where the parser gave up because of malformed placeholder, if any.
+    pub error: Option<Position>,
+}
+
+/// Result of analyzing whole template without rendering, to check it against declared parameters.
+#[derive(Clone, Debug, Default)]
+pub struct Analysis {
+    /// Placeholders referring to parameters in order of appearance, leaving out those referring
+    /// to variables of `for` loops.
+    pub placeholders: Vec<(Position, Placeholder)>,
+    /// Every parameter referred to by placeholders, conditions of `if` blocks and `for` loops, in
+    /// order of first appearance.
+    pub params: Vec<String>,
+    /// Where the parser gave up because of malformed placeholder or unbalanced block, if any.
+    /// Nothing after it is analyzed.
+    pub error: Option<Position>,
+}
+
+impl Analysis {
+    /// Names of formatters applied to parameter `name` anywhere, in order of first appearance.
+    pub fn formatters(&self, name: &str) -> Vec<&str> {
+        let mut found: Vec<&str> = Vec::new();
+        for &(_, ref ph) in self.placeholders.iter().filter(|&&(_, ref ph)| ph.name() == name) {
+            for filter in ph.filter_names() {
+                if !found.contains(&filter.as_str()) {
+                    found.push(filter);
+                }
+            }
+        }
+        found
+    }
+}
+
 /// Minimal template for any kind of plain text.
+///
+/// Behavior on degenerate inputs is the same for every style:
+///
+/// - Empty body renders into nothing.
+/// - Body without delimiters nor escapes renders verbatim, regardless of parameters.
+/// - Body of a single placeholder renders into the formatted value alone.
+/// - Placeholder without value renders as its name, so empty parameters leave every name as is.
+/// - Zero-length value renders into nothing, whatever formatters are applied.
+/// - Any other input either renders or fails with `ErrorKind::Parse`, and never panics.
 #[derive(Clone, Debug, PartialEq)]
 pub struct Template {
-    pub style: Style,
-    pub body: String,
+    style: Style,
+    body: Text,
+    /// Body split by the parser, up to malformed placeholder if any.
+    segments: Vec<Segment>,
+    /// Distinct placeholders appear in the body, with the symbol of their names.
+    placeholders: Vec<(Symbol, Placeholder)>,
+    /// Parameter names indexed by symbols.
+    names: Vec<String>,
+    /// Bytes of raw text, and number of placeholders in segments.
+    raw_len: usize,
+    placeholder_count: usize,
+    error: Option<Position>,
+}
+
+/// Length assumed for each substituted value by `Template::size_hint`.
+const VALUE_LEN_HINT: usize = 16;
+
+/// Bytes of input `Template::write_stream` gathers at least before rendering them.
+const STREAM_CHUNK_LEN: usize = 64 * 1024;
+
+/// Most segments written in a single vectored write, which is `IOV_MAX` of most platforms.
+const MAX_SLICES: usize = 1024;
+
+/// Interned parameter name, which is an index into `Template::names`.
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    pub fn index(&self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Piece of template body, as the parser split it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Segment {
+    /// Byte range of the body written as is. Escaping backslashes are left out of them.
+    Raw(Range<usize>),
+    /// Placeholder with byte offset in body where it starts, and its index in
+    /// `Template::placeholder`. Placeholders written the same way share an index.
+    Placeholder(usize, usize),
+    /// Start of `if` block with byte offset in body, its condition, and index of the segment
+    /// rendering continues after if the condition does not hold: its `Else` or `EndIf`.
+    If(usize, Condition, usize),
+    /// `else` of `if` block with byte offset in body, and index of its `EndIf`.
+    Else(usize, usize),
+    /// End of `if` block with byte offset in body.
+    EndIf(usize),
+    /// Start of `for` loop with byte offset in body, name of the loop variable, name of the list
+    /// parameter, and index of its `EndFor`.
+    For(usize, String, String, usize),
+    /// End of `for` loop with byte offset in body, and index of its `For`.
+    EndFor(usize, usize),
+    /// `include` with byte offset in body, and path of the template to include.
+    Include(usize, String),
+}
+
+/// `for` loop being rendered.
+struct Frame<'a> {
+    /// Index of the `For` segment.
+    start: usize,
+    var: &'a str,
+    items: &'a [String],
+    /// Index of the item rendered.
+    current: usize,
+}
+
+/// Value of variable `name` bound by the innermost loop binding it, if any.
+fn bound<'a>(loops: &[Frame<'a>], name: &str) -> Option<&'a String> {
+    loops.iter().rev().find(|f| f.var == name).map(|f| &f.items[f.current])
 }
 
 impl Template {
     /// Create `Template` object from given `str`.
+    ///
+    /// The body is parsed here once, and every render reuses the result.
     pub fn read_str<S: AsRef<str>>(style: Style, template: S) -> Template {
-        Template {
-            style: style,
-            body: String::from(template.as_ref()),
-        }
+        compile(style, Text::from(String::from(template.as_ref())))
     }
 
     /// Create `Template` from contents of the file at given `Path`.
-    pub fn read_file<P: AsRef<Path>>(style: Style, src: P) -> Result<Template, io::Error> {
-        fsutils::read_file(src.as_ref()).map(|s| Template::read_str(style, s))
+    ///
+    /// Large files are mapped into memory rather than read with `mmap` feature.
+    pub fn read_file<P: AsRef<Path>>(style: Style, src: P) -> Result<Template> {
+        fsutils::read_text(src.as_ref()).map(|body| compile(style, body))
     }
 
     /// Utility to create giter8 style template instantly.
@@ -93,86 +868,1009 @@ impl Template {
         Template::read_str(Style::ST, template)
     }
 
+    pub fn style(&self) -> &Style {
+        &self.style
+    }
+
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+
+    /// Segments of the body in order, up to malformed placeholder if any.
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// Placeholder of given index in `Segment::Placeholder`, with the symbol of its name.
+    pub fn placeholder(&self, index: usize) -> (Symbol, &Placeholder) {
+        let (symbol, ref ph) = self.placeholders[index];
+        (symbol, ph)
+    }
+
+    /// Names of parameters the template refers to, indexed by their symbols.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Estimated length of rendered output in bytes, to allocate buffers rendered into.
+    ///
+    /// Raw text is counted exactly, and each placeholder as 16 bytes.
+    pub fn size_hint(&self) -> usize {
+        self.raw_len + self.placeholder_count * VALUE_LEN_HINT
+    }
+
     /// Process template with given `params`, and write result into `writer`.
-    pub fn write_to<'a, W: Write>(&mut self,
-                                 writer: &'a mut W,
-                                 params: &HashMap<String, String>)
-                                 -> Result<&'a mut W, io::Error> {
+    ///
+    /// Fails with `ErrorKind::Parse` where the template has malformed placeholder or `if` block
+    /// not balanced with `endif`, and with `ErrorKind::Formatter` where a value cannot be
+    /// formatted, before writing anything. Branches of `if` blocks not taken are not formatted.
+    ///
+    /// Raw text and values are written together in vectored writes where `writer` supports them,
+    /// and buffered internally otherwise, so `writer` needs no buffering.
+    pub fn write_to<'a, W: Write, M: ParamMap>(&self,
+                                              writer: &'a mut W,
+                                              params: &M)
+                                              -> Result<&'a mut W> {
+        self.write_cached(writer, params, &FormatCache::new())
+    }
+
+    /// Same as `write_to`, returning the result as `String` rather than writing it.
+    pub fn render<M: ParamMap>(&self, params: &M) -> Result<String> {
+        self.render_with(params, &RenderOptions::default())
+    }
 
-        let mut progress = parser::parse_template(self.body.as_ref(), &self.style);
-        while let Ok((raw, maybe_ph, rest)) = progress {
+    /// Same as `render`, rendering placeholders without value as `options` tell.
+    pub fn render_with<M: ParamMap>(&self, params: &M, options: &RenderOptions) -> Result<String> {
+        let mut buf = Vec::with_capacity(self.size_hint());
+        self.write_with(&mut buf, params, &FormatCache::new(), options)?;
+        // raw text and values are both `str`, so they never split a character
+        String::from_utf8(buf).map_err(|e| Error::from(e.to_string()))
+    }
 
-            if !raw.is_empty() {
-                writer.write(raw.as_bytes()).unwrap();
+    /// Same as `write_to`, reusing formatted values in `cache` across renders.
+    pub fn write_cached<'a, W: Write, M: ParamMap>(&self,
+                                                  writer: &'a mut W,
+                                                  params: &M,
+                                                  cache: &FormatCache)
+                                                  -> Result<&'a mut W> {
+        self.write_with(writer, params, cache, &RenderOptions::default())
+    }
+
+    /// Same as `write_cached`, rendering placeholders without value as `options` tell.
+    ///
+    /// With `MissingKeyBehavior::Error`, every parameter missing in the branches taken is listed
+    /// in a single `ErrorKind::MissingParams`. Templates with `include` tags fail, as there is
+    /// nowhere to load them from.
+    pub fn write_with<'a, W: Write, M: ParamMap>(&self,
+                                                writer: &'a mut W,
+                                                params: &M,
+                                                cache: &FormatCache,
+                                                options: &RenderOptions)
+                                                -> Result<&'a mut W> {
+        self.write_segments(writer, params, cache, options, &mut |path| {
+            bail!("Cannot include `{}` without loader of templates", path)
+        })
+    }
+
+    /// Same as `write_with`, rendering `include` tags with templates of `loader`, with the paths
+    /// resolved from `name` of this template.
+    ///
+    /// Included templates are parsed in the style of this one, and rendered with the same
+    /// `params`. Fails with `ErrorKind::IncludeCycle` where templates include each other.
+    pub fn write_including<'a, W, M, L>(&self,
+                                        name: &str,
+                                        writer: &'a mut W,
+                                        params: &M,
+                                        cache: &FormatCache,
+                                        options: &RenderOptions,
+                                        loader: &L)
+                                        -> Result<&'a mut W>
+        where W: Write,
+              M: ParamMap,
+              L: Loader
+    {
+        self.write_included(writer, params, cache, options, loader, &mut vec![name.to_owned()])
+    }
+
+    /// Same as `write_including`, with names of the templates including this one in `stack`,
+    /// this one last.
+    fn write_included<'a, W: Write, M: ParamMap>(&self,
+                                                writer: &'a mut W,
+                                                params: &M,
+                                                cache: &FormatCache,
+                                                options: &RenderOptions,
+                                                loader: &Loader,
+                                                stack: &mut Vec<String>)
+                                                -> Result<&'a mut W> {
+        self.write_segments(writer, params, cache, options, &mut |path| {
+            let name = loader.resolve(path, stack.last().map(|s| s.as_str()).unwrap_or(""));
+            if stack.contains(&name) {
+                let mut cycle = stack.clone();
+                cycle.push(name);
+                bail!(ErrorKind::IncludeCycle(cycle));
             }
+            let tpl = Template::read_str(self.style.clone(), loader.load(&name)?);
+            let mut buf = Vec::with_capacity(tpl.size_hint());
+            stack.push(name);
+            let written = tpl.write_included(&mut buf, params, cache, options, loader, stack);
+            stack.pop();
+            written?;
+            String::from_utf8(buf).map_err(|e| Error::from(e.to_string()))
+        })
+    }
+
+    /// Write segments taken with `params`, with `include` rendering templates included.
+    fn write_segments<'a, W: Write, M: ParamMap>(&self,
+                                                writer: &'a mut W,
+                                                params: &M,
+                                                cache: &FormatCache,
+                                                options: &RenderOptions,
+                                                include: &mut FnMut(&str) -> Result<String>)
+                                                -> Result<&'a mut W> {
 
-            if let Some(ph) = maybe_ph {
-                let value = ph.format_with(&params);
-                writer.write(value.as_bytes()).unwrap();
+        if let Some(pos) = self.error {
+            bail!(ErrorKind::Parse(pos.line, pos.column));
+        }
+
+        let active = self.active_segments(params);
+        // format every placeholder first, so that they can be written along with raw text
+        let mut values = Vec::with_capacity(self.placeholder_count);
+        let mut missing = BTreeSet::new();
+        for &(segment, value) in &active {
+            if let Segment::Include(_, ref path) = *segment {
+                values.push(Cow::Owned(include(path)?));
+            }
+            if let Segment::Placeholder(at, i) = *segment {
+                let (symbol, ref ph) = self.placeholders[i];
+                trace_span!(TRACE, "resolve", param = %self.names[symbol.index()]);
+                if value.is_none() && ph.default_value().is_none() {
+                    match options.missing {
+                        MissingKeyBehavior::Error => {
+                            missing.insert(&self.names[symbol.index()]);
+                            continue;
+                        }
+                        MissingKeyBehavior::KeepPlaceholder => {
+                            values.push(Cow::Borrowed(self.placeholder_source(at)));
+                            continue;
+                        }
+                        MissingKeyBehavior::Empty => {
+                            values.push(Cow::Borrowed(""));
+                            continue;
+                        }
+                        MissingKeyBehavior::UseDefault => {}
+                    }
+                }
+                values.push(cache.format(ph, value)?);
             }
+        }
+        if !missing.is_empty() {
+            bail!(ErrorKind::MissingParams(missing.into_iter().cloned().collect()));
+        }
+        {
+            let mut values = values.iter();
+            let mut slices = active.iter()
+                .map(|&(segment, _)| match *segment {
+                    Segment::Raw(ref range) => IoSlice::new(self.body[range.clone()].as_bytes()),
+                    _ => IoSlice::new(values.next().map(|v| v.as_bytes()).unwrap_or(&[])),
+                })
+                .collect::<Vec<_>>();
 
-            if rest.is_empty() {
-                writer.flush().unwrap();
+            // `BufWriter` passes large writes through to `writer` if it supports vectored writes
+            let mut out = BufWriter::new(&mut *writer);
+            for batch in slices.chunks_mut(MAX_SLICES) {
+                write_all_vectored(&mut out, batch)?;
+            }
+            // `flush` does not retry on `Interrupted` by itself
+            loop {
+                match out.flush() {
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    result => result?,
+                }
                 break;
-            } else {
-                progress = parser::parse_template(rest, &self.style);
             }
         }
-        writer.flush().unwrap();
 
         Ok(writer)
     }
 
+    /// Placeholder starting at byte offset `at` of the body, as written there.
+    fn placeholder_source(&self, at: usize) -> &str {
+        let open = self.style.open_delim();
+        let close = self.style.close_delim();
+        let start = (at + open.len()).min(self.body.len());
+        let end = self.body[start..]
+            .find(close)
+            .map(|i| start + i + close.len())
+            .unwrap_or(self.body.len());
+        &self.body[at..end]
+    }
+
+    /// Raw text and placeholders rendered with `params` in order, with values of placeholders.
+    ///
+    /// Branches of `if` blocks not taken are left out, and bodies of `for` loops are repeated
+    /// for each item with their variable bound to it.
+    fn active_segments<'a, M: ParamMap>(&'a self,
+                                        params: &'a M)
+                                        -> Vec<(&'a Segment, Option<&'a str>)> {
+        // look up each name once, rather than for every placeholder
+        let lookups: Vec<Option<&str>> =
+            self.names.iter().map(|n| params.lookup(n).map(|v| v.as_str())).collect();
+        let mut active = Vec::with_capacity(self.segments.len());
+        let mut loops: Vec<Frame> = Vec::new();
+        let mut i = 0;
+        while i < self.segments.len() {
+            match self.segments[i] {
+                Segment::If(_, ref cond, next) => {
+                    let lookup = |name: &str| bound(&loops, name).or_else(|| params.lookup(name));
+                    let types = |name: &str| {
+                        if bound(&loops, name).is_some() {
+                            None
+                        } else {
+                            params.lookup_type(name)
+                        }
+                    };
+                    i = if cond.holds_with(&lookup, &types) { i + 1 } else { next + 1 };
+                }
+                // reached at the end of the branch taken
+                Segment::Else(_, end) => i = end + 1,
+                Segment::EndIf(_) => i += 1,
+                Segment::For(_, ref var, ref list, end) => {
+                    match params.lookup_list(list) {
+                        Some(items) if !items.is_empty() => {
+                            loops.push(Frame {
+                                start: i,
+                                var: var,
+                                items: items,
+                                current: 0,
+                            });
+                            i += 1;
+                        }
+                        _ => i = end + 1,
+                    }
+                }
+                Segment::EndFor(..) => {
+                    let repeat = match loops.last_mut() {
+                        Some(frame) if frame.current + 1 < frame.items.len() => {
+                            frame.current += 1;
+                            Some(frame.start)
+                        }
+                        _ => None,
+                    };
+                    i = match repeat {
+                        Some(start) => start + 1,
+                        None => {
+                            loops.pop();
+                            i + 1
+                        }
+                    };
+                }
+                Segment::Placeholder(_, index) => {
+                    let name = self.placeholders[index].0.index();
+                    let value = match bound(&loops, &self.names[name]) {
+                        Some(v) => Some(v.as_str()),
+                        None => lookups[name],
+                    };
+                    active.push((&self.segments[i], value));
+                    i += 1;
+                }
+                Segment::Raw(_) |
+                Segment::Include(..) => {
+                    active.push((&self.segments[i], None));
+                    i += 1;
+                }
+            }
+        }
+        active
+    }
+
+    /// Conditions of every `if` block in the template, in order of appearance.
+    pub fn conditions(&self) -> Vec<&Condition> {
+        self.segments
+            .iter()
+            .filter_map(|s| match *s {
+                Segment::If(_, ref cond, _) => Some(cond),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Collect every placeholder appearing in the template, in order of appearance.
+    ///
+    /// Placeholders referring to variables of `for` loops around them are not parameters, and
+    /// left out.
+    pub fn placeholders(&self) -> Vec<Placeholder> {
+        let mut vars: Vec<&str> = Vec::new();
+        let mut found = Vec::new();
+        for segment in &self.segments {
+            match *segment {
+                Segment::For(_, ref var, _, _) => vars.push(var),
+                Segment::EndFor(..) => {
+                    vars.pop();
+                }
+                Segment::Placeholder(_, i) => {
+                    let ph = &self.placeholders[i].1;
+                    if !vars.contains(&ph.name()) {
+                        found.push(ph.clone());
+                    }
+                }
+                _ => {}
+            }
+        }
+        found
+    }
+
+    /// Names of list parameters `for` loops iterate over, in order of appearance.
+    pub fn lists(&self) -> Vec<&str> {
+        self.segments
+            .iter()
+            .filter_map(|s| match *s {
+                Segment::For(_, _, ref list, _) => Some(list.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Positions of every placeholder, and where the parser gave up if it did.
+    ///
+    /// Unlike `write_to`, which fails on malformed placeholder, its position is reported in
+    /// `Scan::error`.
+    pub fn scan(&self) -> Scan {
+        let mut scan = Scan::default();
+        // count lines incrementally, as placeholders come in order
+        let mut pos = Position { line: 1, column: 1 };
+        let mut last = 0;
+        for segment in &self.segments {
+            if let Segment::Placeholder(offset, i) = *segment {
+                let advanced = Position::at(&self.body[last..], offset - last);
+                pos = if advanced.line > 1 {
+                    Position {
+                        line: pos.line + advanced.line - 1,
+                        column: advanced.column,
+                    }
+                } else {
+                    Position {
+                        line: pos.line,
+                        column: pos.column + advanced.column - 1,
+                    }
+                };
+                last = offset;
+                scan.placeholders.push((pos, self.placeholders[i].1.clone()));
+            }
+        }
+        scan.error = self.error;
+        scan
+    }
+
+    /// Placeholders, parameters and syntax error of the template, as `Analysis` describes.
+    pub fn analyze(&self) -> Analysis {
+        fn refer(params: &mut Vec<String>, vars: &[&str], name: &str) {
+            if !vars.contains(&name) && !params.iter().any(|p| p == name) {
+                params.push(name.to_owned());
+            }
+        }
+
+        let scan = self.scan();
+        // scan has every placeholder segment in order, loop variables included
+        let mut scanned = scan.placeholders.into_iter();
+        let mut placeholders = Vec::new();
+        let mut params = Vec::new();
+        let mut vars: Vec<&str> = Vec::new();
+        for segment in &self.segments {
+            match *segment {
+                Segment::Placeholder(..) => {
+                    if let Some((pos, ph)) = scanned.next() {
+                        if !vars.contains(&ph.name()) {
+                            refer(&mut params, &vars, ph.name());
+                            placeholders.push((pos, ph));
+                        }
+                    }
+                }
+                Segment::If(_, ref cond, _) => refer(&mut params, &vars, cond.name()),
+                Segment::For(_, ref var, ref list, _) => {
+                    refer(&mut params, &vars, list);
+                    vars.push(var);
+                }
+                Segment::EndFor(..) => {
+                    vars.pop();
+                }
+                _ => {}
+            }
+        }
+        Analysis {
+            placeholders: placeholders,
+            params: params,
+            error: scan.error,
+        }
+    }
+
+    /// Create template from given `str`, and instantly render it into `String`.
+    pub fn render_once<S, M>(style: Style, template: S, params: &M) -> Result<String>
+        where S: AsRef<str>,
+              M: ParamMap
+    {
+        Template::read_str(style, template).render(params)
+    }
+
+    /// Render template read from `reader` in `style`, writing into `writer` as it goes rather
+    /// than reading whole input first.
+    ///
+    /// Input is rendered in chunks of whole lines, each gathered until no placeholder nor block is
+    /// left open in it, so no more is held at once than the longest `if` block or `for` loop
+    /// needs. Positions of `ErrorKind::Parse` are those in the whole input, while
+    /// `MissingKeyBehavior::Error` reports missing parameters of the first chunk having any,
+    /// after the chunks before it are written.
+    pub fn write_stream<R, W, M>(style: Style,
+                                 reader: R,
+                                 writer: &mut W,
+                                 params: &M,
+                                 options: &RenderOptions)
+                                 -> Result<()>
+        where R: BufRead,
+              W: Write,
+              M: ParamMap
+    {
+        let cache = FormatCache::new();
+        read_chunks(style, reader, |tpl, lines_done| {
+            if let Some(pos) = tpl.error {
+                bail!(ErrorKind::Parse(lines_done + pos.line, pos.column));
+            }
+            tpl.write_with(writer, params, &cache, options).map(|_| ())
+        })
+    }
+
+    /// Same as `write_stream`, reusing formatted values in `cache`, and rendering `include` tags
+    /// with templates of `loader` as `write_including` does for template `name`.
+    pub fn write_stream_including<R, W, M, L>(style: Style,
+                                              name: &str,
+                                              reader: R,
+                                              writer: &mut W,
+                                              params: &M,
+                                              cache: &FormatCache,
+                                              options: &RenderOptions,
+                                              loader: &L)
+                                              -> Result<()>
+        where R: BufRead,
+              W: Write,
+              M: ParamMap,
+              L: Loader
+    {
+        read_chunks(style, reader, |tpl, lines_done| {
+            if let Some(pos) = tpl.error {
+                bail!(ErrorKind::Parse(lines_done + pos.line, pos.column));
+            }
+            tpl.write_including(name, writer, params, cache, options, loader).map(|_| ())
+        })
+    }
+
+    /// Analyze template read from `reader` in `style` as `analyze` does, reading it in chunks as
+    /// `write_stream` does rather than whole. Positions are those in the whole input.
+    pub fn analyze_stream<R: BufRead>(style: Style, reader: R) -> Result<Analysis> {
+        let mut analysis = Analysis::default();
+        read_chunks(style, reader, |tpl, lines_done| {
+            let chunk = tpl.analyze();
+            let at = |pos: Position| Position { line: lines_done + pos.line, ..pos };
+            analysis.placeholders
+                .extend(chunk.placeholders.into_iter().map(|(pos, ph)| (at(pos), ph)));
+            for name in chunk.params {
+                if !analysis.params.contains(&name) {
+                    analysis.params.push(name);
+                }
+            }
+            analysis.error = chunk.error.map(at);
+            Ok(())
+        })?;
+        Ok(analysis)
+    }
+
     /// Create template from given `str`, and instantly write it.
-    pub fn write_once<'a, S, W>(writer: &'a mut W,
-                                style: Style,
-                                template: S,
-                                params: &HashMap<String, String>)
-                                -> Result<&'a mut W, io::Error>
+    pub fn write_once<'a, S, W, M>(writer: &'a mut W,
+                                   style: Style,
+                                   template: S,
+                                   params: &M)
+                                   -> Result<&'a mut W>
         where S: AsRef<str>,
-              W: Write
+              W: Write,
+              M: ParamMap
     {
-        let mut template = Template::read_str(style, template);
-        Template::write_to(&mut template, writer, params)
+        Template::read_str(style, template).write_to(writer, params)
     }
 }
 
+/// Write every byte of `bufs`, retrying on partial writes and `Interrupted`.
+fn write_all_vectored<W: Write>(out: &mut W, mut bufs: &mut [IoSlice]) -> io::Result<()> {
+    // skip leading empty slices, as writing them would be taken for failure
+    IoSlice::advance_slices(&mut bufs, 0);
+    while !bufs.is_empty() {
+        match out.write_vectored(bufs) {
+            Ok(0) => {
+                return Err(io::Error::new(io::ErrorKind::WriteZero, "failed to write segments"))
+            }
+            Ok(n) => IoSlice::advance_slices(&mut bufs, n),
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Parse template read from `reader` in `style` in chunks of whole lines, each gathered until no
+/// placeholder nor block is left open in it, and pass them to `f` with the number of lines before
+/// them. Only the last chunk may have parse error, where the input ends before it is closed.
+fn read_chunks<R, F>(style: Style, mut reader: R, mut f: F) -> Result<()>
+    where R: BufRead,
+          F: FnMut(Template, usize) -> Result<()>
+{
+    let mut pending = Vec::with_capacity(STREAM_CHUNK_LEN);
+    // lines passed already, and length `pending` must reach before it is parsed again
+    let mut lines_done = 0;
+    let mut parse_at = STREAM_CHUNK_LEN;
+    loop {
+        let ended = reader.read_until(b'\n', &mut pending)? == 0;
+        if !ended && pending.len() < parse_at {
+            continue;
+        }
+        let tpl = {
+            let body = ::std::str::from_utf8(&pending).chain_err(|| {
+                format!("Template is not valid UTF-8 after line {}", lines_done)
+            })?;
+            Template::read_str(style.clone(), body)
+        };
+        if tpl.error.is_some() && !ended {
+            // wait for the rest of the block, parsing less often as it grows longer
+            parse_at = pending.len() * 2;
+            continue;
+        }
+        f(tpl, lines_done)?;
+        if ended {
+            return Ok(());
+        }
+        lines_done += pending.iter().filter(|&&b| b == b'\n').count();
+        pending.clear();
+        parse_at = STREAM_CHUNK_LEN;
+    }
+}
+
+/// Split whole `body` into segments, stopping at malformed placeholder, and intern placeholders.
+fn compile(style: Style, body: Text) -> Template {
+    trace_span!(DEBUG, "compile", style = ?style, bytes = body.len());
+    let mut segments = Vec::new();
+    let mut distinct: HashMap<Placeholder, usize> = HashMap::new();
+    let mut placeholders = Vec::new();
+    let mut symbols: HashMap<String, Symbol> = HashMap::new();
+    let mut names = Vec::new();
+    let (mut raw_len, mut placeholder_count) = (0, 0);
+    let mut error = None;
+    // blocks not closed yet, as indices of their `If` or `For` segments, and `Else` segments
+    let mut blocks: Vec<(usize, Option<usize>)> = Vec::new();
+    let is_if = |segment: &Segment| if let Segment::If(..) = *segment { true } else { false };
+    for chunk in parser::TemplateIter::new(&body, &style) {
+        match chunk {
+            Ok(Chunk::Raw(start, raw)) => {
+                segments.push(Segment::Raw(start..start + raw.len()));
+                raw_len += raw.len();
+            }
+            Ok(Chunk::Tag(at, Tag::Placeholder(ph))) => {
+                let index = match distinct.get(&ph) {
+                    Some(&i) => i,
+                    None => {
+                        let symbol = *symbols.entry(ph.name().to_owned()).or_insert_with(|| {
+                            names.push(ph.name().to_owned());
+                            Symbol(names.len() as u32 - 1)
+                        });
+                        placeholders.push((symbol, ph.clone()));
+                        distinct.insert(ph, placeholders.len() - 1);
+                        placeholders.len() - 1
+                    }
+                };
+                segments.push(Segment::Placeholder(at, index));
+                placeholder_count += 1;
+            }
+            Ok(Chunk::Tag(at, Tag::If(cond))) => {
+                blocks.push((segments.len(), None));
+                segments.push(Segment::If(at, cond, 0));
+            }
+            Ok(Chunk::Tag(at, Tag::Else)) => {
+                match blocks.last_mut() {
+                    Some(&mut (start, ref mut branch @ None)) if is_if(&segments[start]) => {
+                        *branch = Some(segments.len());
+                        segments.push(Segment::Else(at, 0));
+                    }
+                    _ => {
+                        error = Some(Position::at(&body, at));
+                        break;
+                    }
+                }
+            }
+            Ok(Chunk::Tag(at, Tag::EndIf)) => {
+                let (start, branch) = match blocks.pop() {
+                    Some(block) if is_if(&segments[block.0]) => block,
+                    _ => {
+                        error = Some(Position::at(&body, at));
+                        break;
+                    }
+                };
+                let end = segments.len();
+                segments.push(Segment::EndIf(at));
+                if let Segment::If(_, _, ref mut next) = segments[start] {
+                    *next = branch.unwrap_or(end);
+                }
+                if let Some(i) = branch {
+                    if let Segment::Else(_, ref mut next) = segments[i] {
+                        *next = end;
+                    }
+                }
+            }
+            Ok(Chunk::Tag(at, Tag::For(var, list))) => {
+                blocks.push((segments.len(), None));
+                segments.push(Segment::For(at, var, list, 0));
+            }
+            Ok(Chunk::Tag(at, Tag::EndFor)) => {
+                let start = match blocks.pop() {
+                    Some((start, _)) if !is_if(&segments[start]) => start,
+                    _ => {
+                        error = Some(Position::at(&body, at));
+                        break;
+                    }
+                };
+                let end = segments.len();
+                segments.push(Segment::EndFor(at, start));
+                if let Segment::For(_, _, _, ref mut next) = segments[start] {
+                    *next = end;
+                }
+            }
+            Ok(Chunk::Tag(at, Tag::Include(path))) => segments.push(Segment::Include(at, path)),
+            Err(e) => {
+                error = Some(error_position(&style, &body, &e));
+                break;
+            }
+        }
+    }
+    // report the innermost block left open
+    if let (None, Some(&(start, _))) = (error, blocks.last()) {
+        match segments[start] {
+            Segment::If(offset, ..) | Segment::For(offset, ..) => {
+                error = Some(Position::at(&body, offset));
+            }
+            _ => {}
+        }
+    }
+    Template {
+        style: style,
+        body: body,
+        segments: segments,
+        placeholders: placeholders,
+        names: names,
+        raw_len: raw_len,
+        placeholder_count: placeholder_count,
+        error: error,
+    }
+}
+
+/// Locate the opening delimiter of the placeholder or tag the parser failed at.
+fn error_position(style: &Style, body: &str, err: &ParseError<&str>) -> Position {
+    let mut failed = parser::error_offset(body, err).min(body.len());
+    while !body.is_char_boundary(failed) {
+        failed -= 1;
+    }
+    let at = style.block_delim()
+        .into_iter()
+        .chain(Some(style.open_delim()))
+        .filter_map(|delim| body[..failed].rfind(delim))
+        .max()
+        .unwrap_or(failed);
+    Position::at(body, at)
+}
+
 /// Wrapper arround map-type collection to use as resolved parameters in project generation.
+///
+/// Parameters are kept sorted by name, so that prompts, reports and recorded answers are
+/// reproducible.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Params {
-    pub param_map: HashMap<String, String>,
+    pub param_map: BTreeMap<String, String>,
+    /// List parameters, e.g. from arrays in TOML, which `for` loops iterate over.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub lists: BTreeMap<String, Vec<String>>,
+    /// Types of parameters of other than strings, e.g. `Integer` of `workers = 4` in TOML.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub types: BTreeMap<String, ParamType>,
     pub toml: Option<Table>,
 }
 
+impl ParamMap for Params {
+    fn lookup(&self, name: &str) -> Option<&String> {
+        self.param_map.get(name)
+    }
+
+    fn lookup_list(&self, name: &str) -> Option<&[String]> {
+        self.lists.get(name).map(|items| items.as_slice())
+    }
+
+    fn lookup_type(&self, name: &str) -> Option<ParamType> {
+        self.types.get(name).cloned()
+    }
+}
+
+/// Parameters of JSON object, as `Params::from_json` reads.
+#[cfg(feature = "json")]
+impl TryFrom<serde_json::Value> for Params {
+    type Error = Error;
+
+    fn try_from(value: serde_json::Value) -> Result<Params> {
+        Ok(Params::convert_toml(serde_json::from_value::<Table>(value)?))
+    }
+}
+
+/// Declaration of a template parameter, as derived from a struct field by `#[derive(VtolParams)]`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ParamSpec {
+    pub name: String,
+    /// Help text for the parameter, from doc comment of the field.
+    pub description: Option<String>,
+    pub default: Option<String>,
+}
+
+/// Types whose fields are template parameters, usually implemented by `#[derive(VtolParams)]`
+/// along with `From<Self> for Params`.
+pub trait VtolParams: Into<Params> {
+    /// Declarations of the parameters, in order of the fields.
+    fn param_specs() -> Vec<ParamSpec>;
+
+    /// Parameters given default values by the declarations.
+    fn defaults() -> Params {
+        Params::from_map(Self::param_specs()
+            .into_iter()
+            .filter_map(|spec| {
+                let name = spec.name;
+                spec.default.map(|default| (name, default))
+            }))
+    }
+}
+
 impl Params {
 
     pub fn minimal_req() -> Params {
-        let mut minimal = HashMap::new();
+        let mut minimal = BTreeMap::new();
         minimal.insert("name".into(), "Project Generated By Rig".into());
         Params::from_map(minimal)
     }
 
-    pub fn from_map(map: HashMap<String, String>) -> Params {
-        Params { param_map: map, toml: None }
+    pub fn from_map<M>(map: M) -> Params
+        where M: IntoIterator<Item = (String, String)>
+    {
+        Params {
+            param_map: map.into_iter().collect(),
+            lists: BTreeMap::new(),
+            types: BTreeMap::new(),
+            toml: None,
+        }
     }
 
     pub fn convert_toml(toml: Table) -> Params {
-        let mut raw_values = HashMap::new();
+        Params::convert_toml_with(toml, &mut Diagnostics::new())
+    }
+
+    /// Same as `convert_toml`, failing with `ErrorKind::UnsupportedValue` on the first entry
+    /// which cannot be used as a parameter, rather than leaving it out.
+    pub fn try_convert_toml(toml: Table) -> Result<Params> {
+        let mut diag = Diagnostics::new();
+        let params = Params::convert_toml_with(toml, &mut diag);
+        for warning in diag.warnings() {
+            if let Warning::DroppedParam(ref key) = *warning {
+                bail!(ErrorKind::UnsupportedValue(key.clone()));
+            }
+        }
+        Ok(params)
+    }
+
+    /// Same as `convert_toml`, telling entries which cannot be used as parameters to `diag`.
+    ///
+    /// Arrays of scalars become list parameters, and entries of nested tables become parameters
+    /// of dotted keys, e.g. `author.name` of `[author] name = "me"`. Tables configuring the
+    /// template, e.g. of engines selected for files, are not parameters, and are left out
+    /// silently.
+    pub fn convert_toml_with(toml: Table, diag: &mut Diagnostics) -> Params {
+        let mut params = Params::from_map(BTreeMap::new());
         for (k, tv) in &toml {
-            if let Some(v) = convert(&tv) {
-                raw_values.insert(k.clone(), v);
+            if !RESERVED_TABLES.contains(&k.as_str()) {
+                params.insert_toml(k.clone(), tv, diag);
+            }
+        }
+        params.toml = Some(toml);
+        params
+    }
+
+    /// Parameters of the fields of `value`, e.g. config struct of an application, converted as
+    /// `try_convert_toml` converts TOML tables. Fields of `None` are left out.
+    ///
+    /// Fails unless `value` is serialized as a struct or a map.
+    #[cfg(feature = "serde")]
+    pub fn from_serializable<T: Serialize + ?Sized>(value: &T) -> Result<Params> {
+        match Value::try_from(value)? {
+            Value::Table(table) => Params::try_convert_toml(table),
+            _ => bail!("Only structs and maps can be converted into parameters"),
+        }
+    }
+
+    /// Parameters of JSON object `s`, converted as `convert_toml` converts TOML tables.
+    ///
+    /// `null` is not a parameter value, and fails as TOML has no such value.
+    #[cfg(feature = "json")]
+    pub fn from_json(s: &str) -> Result<Params> {
+        Ok(Params::convert_toml(serde_json::from_str::<Table>(s)?))
+    }
+
+    /// Parameters of YAML mapping `s`, converted as `convert_toml` converts TOML tables.
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml(s: &str) -> Result<Params> {
+        Ok(Params::convert_toml(serde_yaml::from_str::<Table>(s)?))
+    }
+
+    /// Add TOML value `tv` as parameter `key`, or the entries of table as parameters of keys
+    /// dotted after `key`.
+    fn insert_toml(&mut self, key: String, tv: &Value, diag: &mut Diagnostics) {
+        match *tv {
+            Value::Table(ref table) => {
+                for (k, v) in table {
+                    self.insert_toml(format!("{}.{}", key, k), v, diag);
+                }
+            }
+            Value::Array(ref items) => {
+                match items.iter().map(convert).collect::<Option<Vec<_>>>() {
+                    Some(items) => {
+                        self.lists.insert(key, items);
+                    }
+                    None => diag.warn(Warning::DroppedParam(key)),
+                }
+            }
+            _ => {
+                match convert(tv) {
+                    Some(v) => {
+                        let ty = match *tv {
+                            Value::Boolean(_) => ParamType::Boolean,
+                            Value::Integer(_) => ParamType::Integer,
+                            Value::Float(_) => ParamType::Float,
+                            _ => ParamType::String,
+                        };
+                        if ty != ParamType::String {
+                            self.types.insert(key.clone(), ty);
+                        }
+                        self.param_map.insert(key, v);
+                    }
+                    None => diag.warn(Warning::DroppedParam(key)),
+                }
             }
         }
-        Params { param_map: raw_values, toml: Some(toml) }
     }
 
     pub fn get(&self, key: &str) -> Option<&String> {
         self.param_map.get(key)
     }
+
+    /// Value of parameter `key` of its type, or as string if it is not of the type any more,
+    /// e.g. given another value by user.
+    pub fn value(&self, key: &str) -> Option<ParamValue> {
+        self.param_map
+            .get(key)
+            .map(|v| ParamValue::parse(v, self.lookup_type(key).unwrap_or(ParamType::String)))
+    }
+
+    /// Items of list parameter `key`.
+    pub fn get_list(&self, key: &str) -> Option<&[String]> {
+        self.lookup_list(key)
+    }
+
+    /// Substitute references to other parameters in values, written as giter8 placeholders, e.g.
+    /// `com.example.$name;format="norm"$`.
+    ///
+    /// Values are resolved after those they refer to, so references can be chained. References to
+    /// parameters not given are left as written, and so are values which cannot be parsed. Fails
+    /// with `ErrorKind::CyclicParams` before changing anything if values refer to each other in a
+    /// cycle.
+    pub fn resolve_refs(&mut self) -> Result<()> {
+        let templates = self.param_map
+            .iter()
+            .filter(|&(_, v)| v.contains('$'))
+            .map(|(k, v)| (k.clone(), Template::new_g8(v)))
+            .filter(|&(_, ref tpl)| tpl.error.is_none() && tpl.placeholder_count > 0)
+            .collect::<BTreeMap<_, _>>();
+        let mut order = Vec::with_capacity(templates.len());
+        for name in templates.keys() {
+            resolution_order(name, &templates, &mut Vec::new(), &mut order)?;
+        }
+        let options = RenderOptions { missing: MissingKeyBehavior::KeepPlaceholder };
+        for name in order {
+            let resolved = templates[name].render_with(self, &options)?;
+            self.param_map.insert(name.to_owned(), resolved);
+        }
+        Ok(())
+    }
+
+    /// Ask the value of every parameter in `defaults` not given yet on stdin, showing its default
+    /// as giter8 does. Empty answer takes the default.
+    ///
+    /// `name` is asked first, and the others in order of their keys. Arrays are taken as list
+    /// parameters as they are, and other entries which cannot be parameters are skipped.
+    pub fn resolve_interactive(&mut self, defaults: &Table) -> Result<()> {
+        let stdin = io::stdin();
+        let stdout = io::stdout();
+        self.resolve_with(defaults, Lang::from_env(), &mut stdin.lock(), &mut stdout.lock())
+    }
+
+    /// Same as `resolve_interactive`, writing prompts in `lang` to `output` and reading answers
+    /// from `input`. Defaults are taken for every parameter left once `input` ends.
+    pub fn resolve_with<R: BufRead, W: Write>(&mut self,
+                                              defaults: &Table,
+                                              lang: Lang,
+                                              input: &mut R,
+                                              output: &mut W)
+                                              -> Result<()> {
+        let mut keys = defaults.keys()
+            .filter(|k| !RESERVED_TABLES.contains(&k.as_str()))
+            .collect::<Vec<_>>();
+        keys.sort_by_key(|k| *k != "name");
+
+        let mut answer = String::new();
+        let mut ended = false;
+        for key in keys {
+            if let Value::Array(ref items) = defaults[key] {
+                let items = items.iter().map(convert).collect::<Option<Vec<_>>>();
+                if let (Some(items), false) = (items, self.lists.contains_key(key)) {
+                    self.lists.insert(key.clone(), items);
+                }
+                continue;
+            }
+            let default = match convert(&defaults[key]) {
+                Some(default) => default,
+                None => continue,
+            };
+            if self.param_map.contains_key(key) {
+                continue;
+            }
+
+            answer.clear();
+            if !ended {
+                write!(output, "{}", Message::Prompt.format(lang, &[key, &default]))?;
+                output.flush()?;
+                ended = input.read_line(&mut answer)? == 0;
+            }
+            let value = if answer.trim().is_empty() { default } else { answer.trim().to_owned() };
+            self.param_map.insert(key.clone(), value);
+        }
+        Ok(())
+    }
+}
+
+/// Push `name` into `order` after every parameter its template refers to, failing on a cycle
+/// back to any of `path`.
+fn resolution_order<'a>(name: &'a str,
+                        templates: &'a BTreeMap<String, Template>,
+                        path: &mut Vec<&'a str>,
+                        order: &mut Vec<&'a str>)
+                        -> Result<()> {
+    if order.contains(&name) {
+        return Ok(());
+    }
+    if let Some(start) = path.iter().position(|n| *n == name) {
+        let mut cycle = path[start..].iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        cycle.push(name.to_owned());
+        bail!(ErrorKind::CyclicParams(cycle));
+    }
+    path.push(name);
+    for dep in templates[name].names() {
+        if templates.contains_key(dep) {
+            resolution_order(dep, templates, path, order)?;
+        }
+    }
+    path.pop();
+    order.push(name);
+    Ok(())
 }
 
-// FIXME: should return `Result<String, errors::Error>` to tell we won't accept table / array?
-fn convert(value: &Value) -> Option<String> {
+/// Scalar TOML `value` as parameter value.
+pub(crate) fn convert(value: &Value) -> Option<String> {
     match *value {
         Value::String(_) => value.as_str().map(|s| s.to_owned()),
         Value::Datetime(_) => value.as_datetime().map(|s| s.to_string()),
@@ -0,0 +1,359 @@
+//! Helpers testing templates and generation flows: fixture trees, assertions on generated
+//! files, and snapshot tests comparing rendered files or trees with ones stored along with the
+//! tests.
+//!
+//! Fixtures live in temporary directories of their own, so tests using them can run in
+//! parallel.
+//!
+//! Run tests with `RIG_UPDATE_SNAPSHOTS=1` to store what is rendered as the new snapshots,
+//! rather than comparing with them. Review the changes before committing them.
+//!
+//! Template repositories can carry their own test cases in `SUITE_DIR`, each a directory with
+//! parameters in `params.toml` and the tree expected from them in `expected`, run by
+//! `TemplateSuite` or `rig test`:
+//!
+//! ```text
+//! .rig-tests/
+//!     minimal/
+//!         params.toml
+//!         expected/
+//!             Cargo.toml
+//!             src/main.rs
+//! ```
+
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use tempdir::TempDir;
+
+use super::diff::{self, FileChange};
+use super::errors::*;
+use super::fsutils;
+use super::params::{Origin, ParamsBuilder};
+use super::project::{self, Configuration, Project};
+use super::template::Params;
+
+/// Tree of files in a temporary directory, removed when dropped.
+#[derive(Debug)]
+pub struct Fixture {
+    dir: TempDir,
+}
+
+impl Fixture {
+    pub fn builder() -> FixtureBuilder {
+        FixtureBuilder::default()
+    }
+
+    /// Root directory of the tree.
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+}
+
+/// Builder of `Fixture`, e.g. `Fixture::builder().file("Rig.toml", "name = \"app\"").build()`.
+#[derive(Clone, Debug, Default)]
+pub struct FixtureBuilder {
+    files: Vec<(PathBuf, String)>,
+    dirs: Vec<PathBuf>,
+}
+
+impl FixtureBuilder {
+    /// Add file at `path` relative to root, with its parent directories.
+    pub fn file<P: AsRef<Path>, S: Into<String>>(mut self, path: P, contents: S) -> Self {
+        self.files.push((path.as_ref().to_path_buf(), contents.into()));
+        self
+    }
+
+    /// Add directory at `path` relative to root, which may be left empty.
+    pub fn dir<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.dirs.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn build(&self) -> Result<Fixture> {
+        let dir = TempDir::new("rig-fixture")
+            .chain_err(|| "Failed to create temporal directory")?;
+        for path in &self.dirs {
+            fsutils::create_dir_all(&dir.path().join(path))?;
+        }
+        for &(ref path, ref contents) in &self.files {
+            let path = dir.path().join(path);
+            if let Some(parent) = path.parent() {
+                fsutils::create_dir_all(parent)?;
+            }
+            fsutils::write_file(&path, contents)?;
+        }
+        Ok(Fixture { dir: dir })
+    }
+}
+
+/// Assert the file at `path` has `expected` contents, panicking with unified diff otherwise.
+pub fn assert_file_eq<P: AsRef<Path>>(path: P, expected: &str) {
+    let path = path.as_ref();
+    match fsutils::read_file(path) {
+        Ok(ref actual) => {
+            if let Some(diff) = diff::unified_diff(expected, actual, "expected", "actual") {
+                panic!("{} does not have expected contents\n{}", path.display(), diff);
+            }
+        }
+        Err(e) => panic!("Failed to read {}: {}", path.display(), e),
+    }
+}
+
+/// Assert the tree under `root` has exactly the files `expected`, as paths relative to `root`
+/// and their contents, panicking with a report of every difference otherwise.
+pub fn assert_tree_matches<P: AsRef<Path>>(root: P, expected: &[(&str, &str)]) {
+    let root = root.as_ref();
+    let fixture = expected.iter()
+        .fold(Fixture::builder(), |b, &(path, contents)| b.file(path, contents))
+        .build();
+    let checked = fixture.and_then(|expected| compare_trees(root, expected.path()));
+    assert_matches(root, checked);
+}
+
+/// Report files missing in `actual`, files only in it and differences of contents, from
+/// `expected` tree.
+fn compare_trees(actual: &Path, expected: &Path) -> Result<Option<String>> {
+    let mut report = String::new();
+    if fsutils::is_directory(expected) {
+        for path in diff::missing_files(actual, expected)? {
+            report.push_str(&format!("Not rendered: {}\n", path.display()));
+        }
+    }
+    for change in diff::diff_trees(actual, expected)? {
+        match change {
+            FileChange::Added(ref path) => {
+                report.push_str(&format!("Not expected: {}\n", path.display()))
+            }
+            FileChange::Modified(_, ref diff) => report.push_str(diff),
+            FileChange::BinaryModified(ref path) => {
+                report.push_str(&format!("Binary files differ: {}\n", path.display()))
+            }
+        }
+    }
+    Ok(if report.is_empty() { None } else { Some(report) })
+}
+
+/// Environment variable turning on update mode, unless empty or `0`.
+pub const UPDATE_VAR: &'static str = "RIG_UPDATE_SNAPSHOTS";
+
+/// Whether snapshots are updated rather than compared in this run.
+pub fn updating() -> bool {
+    match env::var_os(UPDATE_VAR) {
+        Some(v) => !v.is_empty() && v != OsStr::new("0"),
+        None => false,
+    }
+}
+
+/// Compare `actual` with the snapshot file at `snapshot`, returning a report of the difference
+/// in unified diff format, or `None` if they are the same.
+///
+/// Missing snapshot is reported as difference from empty file. In update mode, `actual` is
+/// written to `snapshot` along with its parents instead, and nothing is reported.
+pub fn check_snapshot(snapshot: &Path, actual: &str) -> Result<Option<String>> {
+    if updating() {
+        if let Some(parent) = snapshot.parent() {
+            fsutils::create_dir_all(parent)?;
+        }
+        fsutils::write_file(snapshot, actual)?;
+        return Ok(None);
+    }
+
+    let expected = if fsutils::exists(snapshot) {
+        fsutils::read_file(snapshot)?
+    } else {
+        String::new()
+    };
+    let name = snapshot.to_string_lossy();
+    Ok(diff::unified_diff(&expected, actual, &name, "rendered"))
+}
+
+/// Render template `body` with `project` and `params` as `Project::render` does, and compare
+/// the result with the snapshot file at `snapshot`.
+pub fn check_render(project: &Project,
+                    body: &str,
+                    params: &Params,
+                    snapshot: &Path)
+                    -> Result<Option<String>> {
+    let name = snapshot.file_name().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let rendered = project.render(&name, body, params)?;
+    check_snapshot(snapshot, &rendered)
+}
+
+/// Generate the template at `clone_root` with `params` into a temporary directory, and compare
+/// the tree with the snapshot directory `snapshot`, returning a report of every difference.
+///
+/// In update mode, `snapshot` is replaced with the generated tree instead.
+pub fn check_tree(project: &Project,
+                  params: &Params,
+                  clone_root: &Path,
+                  snapshot: &Path)
+                  -> Result<Option<String>> {
+    let rendered = TempDir::new("rig-snapshot")
+        .chain_err(|| "Failed to create temporal directory")?;
+    let generated = rendered.path().join("tree");
+    project.generate(params, clone_root, &generated, false)?;
+
+    if updating() {
+        if fsutils::exists(snapshot) {
+            fsutils::remove_dir(snapshot)?;
+        }
+        if let Some(parent) = snapshot.parent() {
+            fsutils::create_dir_all(parent)?;
+        }
+        fsutils::copy_dir(&generated, snapshot)?;
+        return Ok(None);
+    }
+    compare_trees(&generated, snapshot)
+}
+
+/// Same as `check_render`, panicking with the report on difference or failure.
+pub fn assert_render_snapshot(project: &Project, body: &str, params: &Params, snapshot: &Path) {
+    assert_snapshot(snapshot, check_render(project, body, params, snapshot));
+}
+
+/// Same as `check_tree`, panicking with the report on difference or failure.
+pub fn assert_tree_snapshot(project: &Project,
+                            params: &Params,
+                            clone_root: &Path,
+                            snapshot: &Path) {
+    assert_snapshot(snapshot, check_tree(project, params, clone_root, snapshot));
+}
+
+fn assert_snapshot(snapshot: &Path, checked: Result<Option<String>>) {
+    if let Ok(Some(ref report)) = checked {
+        panic!("Rendered output does not match snapshot {}, run with {}=1 to update it\n{}",
+               snapshot.display(),
+               UPDATE_VAR,
+               report)
+    }
+    assert_matches(snapshot, checked);
+}
+
+fn assert_matches(path: &Path, checked: Result<Option<String>>) {
+    match checked {
+        Ok(None) => {}
+        Ok(Some(report)) => panic!("{} does not match\n{}", path.display(), report),
+        Err(e) => {
+            let msg = e.iter().map(|e| e.to_string()).collect::<Vec<_>>().join(": ");
+            panic!("Failed to check {}: {}", path.display(), msg)
+        }
+    }
+}
+
+/// Directory of test cases in template repository, never generated as a part of the template.
+pub const SUITE_DIR: &'static str = ".rig-tests";
+
+/// File of parameters in test case directory, in TOML.
+pub const CASE_PARAMS: &'static str = "params.toml";
+
+/// Directory of the tree expected in test case directory.
+pub const CASE_EXPECTED: &'static str = "expected";
+
+/// Test case of template, rendering it with `params` and comparing the tree with `expected`.
+#[derive(Clone, Debug)]
+pub struct TestCase {
+    /// Name of the case directory.
+    pub name: String,
+    /// Template defaults overridden by `params.toml` of the case. `now` is not given unless the
+    /// case gives it, so the expected tree stays the same.
+    pub params: Params,
+    pub expected: PathBuf,
+}
+
+/// Result of a test case, with the report of every difference from the expected tree, or of
+/// the failure to render, unless it passed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CaseResult {
+    pub name: String,
+    pub report: Option<String>,
+}
+
+impl CaseResult {
+    pub fn passed(&self) -> bool {
+        self.report.is_none()
+    }
+}
+
+/// Test cases of template found in `SUITE_DIR` of its repository.
+#[derive(Debug)]
+pub struct TemplateSuite<'a> {
+    project: &'a Project,
+    clone_root: PathBuf,
+    cases: Vec<TestCase>,
+}
+
+impl<'a> TemplateSuite<'a> {
+    /// Read every test case in `SUITE_DIR` of `clone_root`, in order of their names. The suite
+    /// is empty if the repository has no `SUITE_DIR`.
+    pub fn discover(project: &'a Project, clone_root: &Path) -> Result<TemplateSuite<'a>> {
+        let suite_dir = clone_root.join(SUITE_DIR);
+        let mut case_dirs = Vec::new();
+        if fsutils::is_directory(&suite_dir) {
+            for entry in fs::read_dir(&suite_dir)
+                .chain_err(|| ErrorKind::FsFailure("reading directory", suite_dir.clone()))? {
+                let path = entry?.path();
+                if fsutils::is_directory(&path) {
+                    case_dirs.push(path);
+                }
+            }
+        }
+        case_dirs.sort();
+
+        let defaults = project.default_params(clone_root).unwrap_or(Params::minimal_req());
+        let manifest = project.manifest(clone_root)?;
+        let mut cases = Vec::with_capacity(case_dirs.len());
+        for dir in case_dirs {
+            let mut builder = ParamsBuilder::new();
+            builder.defaults(&defaults);
+            let file = dir.join(CASE_PARAMS);
+            if fsutils::exists(&file) {
+                let params = project::read_params(&file, Configuration::Toml)?;
+                builder.layer(&params, Origin::File(file));
+            }
+            let mut params = builder.build();
+            params.resolve_refs()?;
+            manifest.derive(&mut params)?;
+            cases.push(TestCase {
+                name: dir.file_name().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default(),
+                params: params,
+                expected: dir.join(CASE_EXPECTED),
+            });
+        }
+        Ok(TemplateSuite {
+            project: project,
+            clone_root: clone_root.to_path_buf(),
+            cases: cases,
+        })
+    }
+
+    pub fn cases(&self) -> &[TestCase] {
+        &self.cases
+    }
+
+    /// Run every case as `check_tree` does, so expected trees are updated instead in update
+    /// mode. A case failing to render fails alone, rather than the whole suite.
+    pub fn run(&self) -> Vec<CaseResult> {
+        self.cases
+            .iter()
+            .map(|case| {
+                let report = match check_tree(self.project,
+                                              &case.params,
+                                              &self.clone_root,
+                                              &case.expected) {
+                    Ok(report) => report,
+                    Err(e) => {
+                        let msg = e.iter().map(|e| e.to_string()).collect::<Vec<_>>().join(": ");
+                        Some(format!("Template cannot be rendered: {}\n", msg))
+                    }
+                };
+                CaseResult {
+                    name: case.name.clone(),
+                    report: report,
+                }
+            })
+            .collect()
+    }
+}
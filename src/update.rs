@@ -0,0 +1,243 @@
+//! Re-applying a template to a project generated from it earlier, e.g. after the template is
+//! upgraded.
+//!
+//! Parameters a project was generated with, and a checksum of every file generated, are recorded
+//! in `STATE_FILE` of the project. The template is rendered again in memory with them, and
+//! compared with files in the project: files not changed since they were generated can be
+//! updated as they are rendered, and the others conflict with the update.
+//!
+//! ```toml
+//! template = "user/repo.g8"
+//!
+//! [params]
+//! name = "app"
+//! workers = 4
+//!
+//! [files]
+//! "src/main.rs" = "af63bd4c8601b7df"
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str;
+
+use toml;
+use toml::value::{Table, Value};
+
+use super::diagnostics::Diagnostics;
+use super::diff;
+use super::errors::*;
+use super::fsutils::{self, Outcome, Written};
+use super::params::NOW_PARAM;
+use super::project::Project;
+use super::template::{ParamValue, Params};
+
+/// Where the state of generated project is recorded, relative to the project.
+pub const STATE_FILE: &'static str = ".rig/state.toml";
+
+/// What a project was generated with, recorded in `STATE_FILE`.
+#[derive(Clone, Debug)]
+pub struct State {
+    /// Template repository or directory, as it was given.
+    pub template: Option<String>,
+    /// Branch or tag of the template checked out.
+    pub version: Option<String>,
+    pub params: Params,
+    /// Checksum of every file generated, by its path relative to the project, separated by `/`.
+    pub files: BTreeMap<String, String>,
+}
+
+impl State {
+    pub fn new(template: Option<&str>, version: Option<&str>, params: &Params) -> State {
+        State {
+            template: template.map(|s| s.to_owned()),
+            version: version.map(|s| s.to_owned()),
+            params: params.clone(),
+            files: BTreeMap::new(),
+        }
+    }
+
+    /// Read `STATE_FILE` of project `dir`.
+    pub fn read(dir: &Path) -> Result<State> {
+        let file = dir.join(STATE_FILE);
+        if !file.is_file() {
+            bail!("{} not found in {:?}, so the project cannot be updated", STATE_FILE, dir);
+        }
+        let s = fsutils::read_file(&file)?;
+        let toml = toml::from_str::<Table>(&s).chain_err(|| ErrorKind::TomlDecodeFailure)?;
+        let string = |key: &str| toml.get(key).and_then(|v| v.as_str()).map(|s| s.to_owned());
+        let params = match toml.get("params") {
+            Some(&Value::Table(ref params)) => Params::try_convert_toml(params.clone())?,
+            _ => Params::from_map(BTreeMap::new()),
+        };
+        let files = match toml.get("files") {
+            Some(&Value::Table(ref files)) => {
+                files.iter()
+                    .filter_map(|(k, v)| v.as_str().map(|v| (k.clone(), v.to_owned())))
+                    .collect()
+            }
+            _ => BTreeMap::new(),
+        };
+        Ok(State {
+            template: string("template"),
+            version: string("version"),
+            params: params,
+            files: files,
+        })
+    }
+
+    /// Write `STATE_FILE` into project `dir`. `NOW_PARAM` is left out, as it is given again
+    /// every time.
+    pub fn write(&self, dir: &Path) -> Result<Written> {
+        let mut params = Table::new();
+        for name in self.params.param_map.keys().filter(|&k| k != NOW_PARAM) {
+            let value = match self.params.value(name) {
+                Some(ParamValue::Boolean(b)) => Value::Boolean(b),
+                Some(ParamValue::Integer(i)) => Value::Integer(i),
+                Some(ParamValue::Float(f)) => Value::Float(f),
+                Some(ParamValue::String(s)) => Value::String(s),
+                None => continue,
+            };
+            params.insert(name.clone(), value);
+        }
+        for (name, items) in &self.params.lists {
+            let items = items.iter().map(|item| Value::String(item.clone())).collect();
+            params.insert(name.clone(), Value::Array(items));
+        }
+        let mut toml = Table::new();
+        if let Some(ref template) = self.template {
+            toml.insert("template".to_owned(), Value::String(template.clone()));
+        }
+        if let Some(ref version) = self.version {
+            toml.insert("version".to_owned(), Value::String(version.clone()));
+        }
+        let files = self.files.iter().map(|(k, v)| (k.clone(), Value::String(v.clone())));
+        let mut s = toml::to_string(&toml)?;
+        // tables of a table are written in order of their keys, while TOML needs them after
+        // every value, so they are written one by one
+        for (key, table) in vec![("params", params), ("files", files.collect())] {
+            let mut section = Table::new();
+            section.insert(key.to_owned(), Value::Table(table));
+            if !s.is_empty() {
+                s.push('\n');
+            }
+            s.push_str(&toml::to_string(&section)?);
+        }
+
+        let file = dir.join(STATE_FILE);
+        fsutils::create_dir_all(file.parent().unwrap_or(dir))?;
+        fsutils::write_file(&file, &s)
+    }
+
+    /// Record checksums of files `written` into project `dir`, leaving out those skipped.
+    pub fn record(&mut self, dir: &Path, written: &[Written]) -> Result<()> {
+        for file in written.iter().filter(|w| w.outcome != Outcome::Skipped) {
+            if !file.path.is_file() {
+                continue;
+            }
+            let contents = fs::read(&file.path)
+                .chain_err(|| ErrorKind::FsFailure("reading file", file.path.clone()))?;
+            let path = file.path.strip_prefix(dir).unwrap_or(&file.path);
+            self.files.insert(state_key(path), checksum(&contents));
+        }
+        Ok(())
+    }
+}
+
+/// File of the project which differs from the one rendered by the template.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileUpdate {
+    /// Path relative to the project.
+    pub path: PathBuf,
+    /// Contents rendered by the template.
+    pub rendered: Vec<u8>,
+    /// Contents in the project, if the file exists.
+    pub existing: Option<Vec<u8>>,
+    /// Whether the file in the project was changed or removed since it was generated, or was
+    /// not generated at all, so the update would lose what was done to it.
+    pub conflict: bool,
+}
+
+impl FileUpdate {
+    /// Unified diff from the file in the project to the rendered one, if both are texts.
+    pub fn diff(&self) -> Option<String> {
+        let old = match self.existing {
+            Some(ref existing) => str::from_utf8(existing).ok()?,
+            None => "",
+        };
+        let new = str::from_utf8(&self.rendered).ok()?;
+        let name = state_key(&self.path);
+        diff::unified_diff(old, new, &format!("a/{}", name), &format!("b/{}", name))
+    }
+}
+
+/// Render the template at `clone_root` with `params` in memory, and compare every file with the
+/// one in project `dir` generated as `state` records. Files the same as rendered are left out.
+pub fn changes(project: &Project,
+               params: &Params,
+               clone_root: &Path,
+               dir: &Path,
+               state: &State,
+               diag: &mut Diagnostics)
+               -> Result<Vec<FileUpdate>> {
+    let mut updates = Vec::new();
+    for (path, rendered) in project.render_tree(params, clone_root, dir, diag)? {
+        let target = dir.join(&path);
+        let existing = if target.is_file() {
+            Some(fs::read(&target).chain_err(|| ErrorKind::FsFailure("reading file", target))?)
+        } else {
+            None
+        };
+        if existing.as_ref() == Some(&rendered) {
+            continue;
+        }
+        let conflict = match (existing.as_ref(), state.files.get(&state_key(&path))) {
+            (Some(existing), Some(sum)) => checksum(existing) != *sum,
+            (None, None) => false,
+            _ => true,
+        };
+        updates.push(FileUpdate {
+            path: path,
+            rendered: rendered,
+            existing: existing,
+            conflict: conflict,
+        });
+    }
+    Ok(updates)
+}
+
+/// Write every update without conflict into project `dir`, recording their checksums in
+/// `state`. Returns files written.
+pub fn apply(dir: &Path, updates: &[FileUpdate], state: &mut State) -> Result<Vec<Written>> {
+    let mut written = Vec::new();
+    for update in updates.iter().filter(|u| !u.conflict) {
+        let target = dir.join(&update.path);
+        fsutils::create_dir_all(target.parent().unwrap_or(dir))?;
+        written.push(fsutils::write_with(&target, |f| {
+                f.write_all(&update.rendered)
+                    .chain_err(|| ErrorKind::FsFailure("writing file", target.clone()))
+            })?);
+        state.files.insert(state_key(&update.path), checksum(&update.rendered));
+    }
+    Ok(written)
+}
+
+/// Key of `path` in `State::files`, its components joined with `/`.
+fn state_key(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// FNV-1a hash of `bytes` in hex, stable across platforms and releases.
+fn checksum(bytes: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &b in bytes {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
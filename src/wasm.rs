@@ -0,0 +1,83 @@
+//! Bindings rendering templates from JavaScript, for previews in browser and web-based
+//! scaffolders.
+//!
+//! Only built with `wasm` feature. Templates are rendered by the native parser whatever style
+//! they are written in, as engine crates and filesystem are not available there. Trees of
+//! templates are read from and rendered into memory, see `output` module.
+
+use std::collections::{BTreeMap, HashMap};
+use std::result;
+
+use serde_json;
+use wasm_bindgen::prelude::*;
+
+use super::errors::*;
+use super::output::{self, MemorySink};
+use super::template::{Params, RenderOptions, Style, Template};
+
+/// Render `template` written in `style`, e.g. `tera` or `giter8`, with `params_json` giving
+/// parameters as a JSON object of strings.
+///
+/// Failures are thrown as a message joining the chain of errors.
+#[wasm_bindgen]
+pub fn render(template: &str, style: &str, params_json: &str) -> result::Result<String, JsValue> {
+    render_json(template, style, params_json).map_err(to_js)
+}
+
+/// Render every template of `files_json`, a JSON object of their bodies by their paths, e.g.
+/// `{"src/$name$.rs": "..."}`, with `params_json` as `render` does. Returns a JSON object of
+/// rendered files by their rendered paths.
+///
+/// Templates can include each other by their paths.
+#[wasm_bindgen]
+pub fn render_files(files_json: &str,
+                    style: &str,
+                    params_json: &str)
+                    -> result::Result<String, JsValue> {
+    render_files_json(files_json, style, params_json).map_err(to_js)
+}
+
+/// Message joining the chain of errors.
+fn to_js(e: Error) -> JsValue {
+    let msg = e.iter().map(|e| e.to_string()).collect::<Vec<_>>().join(": ");
+    JsValue::from_str(&msg)
+}
+
+fn render_json(template: &str, style: &str, params_json: &str) -> Result<String> {
+    let style = parse_style(style)?;
+    let params = parse_params(params_json)?;
+    Template::render_once(style, template, &params)
+}
+
+fn render_files_json(files_json: &str, style: &str, params_json: &str) -> Result<String> {
+    let style = parse_style(style)?;
+    let params = Params::from_map(parse_params(params_json)?);
+    let files = serde_json::from_str::<HashMap<String, String>>(files_json)
+        .map_err(|e| format!("Files must be a JSON object of strings: {}", e))?;
+    let mut names = files.keys().cloned().collect::<Vec<_>>();
+    names.sort();
+
+    let mut sink = MemorySink::new();
+    output::render_tree(&files, &names, style, &params, &RenderOptions::default(), &mut sink)?;
+    let rendered = sink.files
+        .iter()
+        .map(|(path, contents)| {
+            let path = path.iter().map(|c| c.to_string_lossy()).collect::<Vec<_>>().join("/");
+            (path, String::from_utf8_lossy(contents).into_owned())
+        })
+        .collect::<BTreeMap<_, _>>();
+    serde_json::to_string(&rendered).map_err(|e| Error::from(e.to_string()))
+}
+
+fn parse_style(style: &str) -> Result<Style> {
+    match Style::from_name(style) {
+        Some(style) => Ok(style),
+        None => bail!("Unknown style {:?}", style),
+    }
+}
+
+fn parse_params(params_json: &str) -> Result<HashMap<String, String>> {
+    let params = serde_json::from_str::<HashMap<String, String>>(params_json)
+        .map_err(|e| format!("Parameters must be a JSON object of strings: {}", e))?;
+    Ok(params)
+}
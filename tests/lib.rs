@@ -1,9 +1,18 @@
+#[cfg(feature = "test-util")]
+#[macro_use]
+extern crate proptest;
 extern crate rig;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde_derive;
+extern crate toml;
+extern crate url;
 
 mod format_test {
 
     use std::ascii::AsciiExt;
-    use rig::format::format;
+    use std::borrow::Cow;
+    use rig::format::{format, validate, Formatter};
 
     const W: &'static str = "Fabulous Is Rust";
 
@@ -49,6 +58,22 @@ mod format_test {
         assert_eq!(format(W, "Camel".into()), "FabulousIsRust");
     }
 
+    #[test]
+    fn blank_camel() {
+        assert_eq!(format("   ", "Camel".into()), "");
+        assert_eq!(format(" ", "camel".into()), "");
+    }
+
+    #[test]
+    fn validate_value() {
+        assert_eq!(validate(W, "Camel".into()), None);
+        assert_eq!(validate("", "word".into()), None);
+        assert!(validate("!!!", "word".into()).is_some());
+        assert!(validate("com/example", "packaged".into()).is_some());
+        assert!(validate("com.example.", "packaged".into()).is_some());
+        assert_eq!(validate("com..example", "packaged".into()), None);
+    }
+
     #[test]
     fn lower_camel() {
         assert_eq!(format(W, "camel".into()), "fabulousIsRust");
@@ -64,12 +89,124 @@ mod format_test {
         assert_eq!(format(W, "snake".into()), "Fabulous_Is_Rust");
     }
 
+    #[test]
+    fn giter8_aliases() {
+        assert_eq!(format("fab_ulous-is.rust", "word-space".into()), "fab_ulous is rust");
+        assert_eq!(format("fabulous is", "hyphenate".into()), "fabulous-is");
+        assert_eq!(Formatter::from("space"), Formatter::WordSpace);
+        for f in Formatter::all() {
+            assert_eq!(Formatter::from(f.name()), *f);
+        }
+    }
+
+    #[test]
+    fn case_conversions() {
+        let cases = [("kebab-case", "my-http-server-v2"),
+                     ("snake_case", "my_http_server_v2"),
+                     ("camelCase", "myHttpServerV2"),
+                     ("PascalCase", "MyHttpServerV2"),
+                     ("SCREAMING_SNAKE_CASE", "MY_HTTP_SERVER_V2"),
+                     ("Train-Case", "My-Http-Server-V2")];
+        for &(f, expected) in &cases {
+            assert_eq!(format("my HTTPServer_v2", f.into()), expected);
+            // every case splits into the same words, so converting back and forth round-trips
+            for &(g, other) in &cases {
+                assert_eq!(format(other, f.into()), expected, "{} to {}", g, f);
+            }
+            assert_eq!(Formatter::from(f).name(), f);
+        }
+        assert_eq!(format("été à Paris", "PascalCase".into()), "ÉtéÀParis");
+        assert_eq!(format("日本語 テキスト", "kebab".into()), "日本語-テキスト");
+        assert!(validate("-_-", "kebab".into()).is_some());
+    }
+
+    #[test]
+    fn unicode_values() {
+        // `é` written as `e` and combining acute accent
+        let nfd = "e\u{301}te\u{301} a\u{300} Paris";
+        assert_eq!(format("élan vital", "cap".into()), "Élan vital");
+        assert_eq!(format("ǆungla", "cap".into()), "ǅungla");
+        assert_eq!(format("ßig", "cap".into()), "Ssig");
+        assert_eq!(format("straße", "upper".into()), "STRASSE");
+        assert_eq!(format(nfd, "PascalCase".into()), "E\u{301}te\u{301}A\u{300}Paris");
+        assert_eq!(format(nfd, "nfc".into()), "été à Paris");
+        assert_eq!(format("été", "nfd".into()), "e\u{301}te\u{301}");
+        assert_eq!(format("été à Paris", "Camel".into()), "ÉtéÀParis");
+        assert_eq!(format("日本 語", "Camel".into()), "日本語");
+        assert_eq!(format("日本語", "upper".into()), Cow::Borrowed("日本語"));
+
+        assert_eq!(format(nfd, "truncate(2)".into()), "e\u{301}t");
+        assert_eq!(format("e\u{301}t", "pad(4)".into()), "e\u{301}t  ");
+        assert_eq!(format("日本", "pad-left(3)".into()), " 日本");
+        match format("été", "nfc".into()) {
+            Cow::Borrowed(b) => assert_eq!(b, "été"),
+            Cow::Owned(o) => panic!("{:?} is formatted", o),
+        }
+    }
+
+    #[test]
+    fn formatter_arguments() {
+        assert_eq!(Formatter::from("truncate(length=3)"), Formatter::Truncate(3));
+        assert_eq!(Formatter::from("replace('-SNAPSHOT', \"\")"),
+                   Formatter::Replace("-SNAPSHOT".to_owned(), String::new()));
+        for bad in &["truncate", "truncate(x)", "pad(1, 2)", "replace(\"a\")", "replace(\"a"] {
+            assert_eq!(Formatter::from(*bad), Formatter::Ident, "{}", bad);
+        }
+
+        assert_eq!(format("fabulous", "truncate(3)".into()), "fab");
+        assert_eq!(format("fab", "pad(5)".into()), "fab  ");
+        assert_eq!(format("fab", "pad-left(5)".into()), "  fab");
+        assert_eq!(format("", "pad(5)".into()), "     ");
+        assert_eq!(format("", "pad-left(3)".into()), "   ");
+        assert_eq!(format("", "truncate(3)".into()), "");
+        assert_eq!(format("1.0-SNAPSHOT", "replace(\"-SNAPSHOT\", \"\")".into()), "1.0");
+        match format("fab", "truncate(8)".into()) {
+            Cow::Borrowed(b) => assert_eq!(b, "fab"),
+            Cow::Owned(o) => panic!("{:?} is formatted", o),
+        }
+    }
+
+    #[test]
+    fn format_dates() {
+        let f = Formatter::from("date(\"%Y-%m-%d\")");
+        assert_eq!(f, Formatter::Date("%Y-%m-%d".to_owned()));
+        assert_eq!(format("1979-05-27T07:32:00Z", f.clone()), "1979-05-27");
+        assert_eq!(format("1979-05-27T00:32:00.999-07:00", "date('%H:%M %z')".into()),
+                   "00:32 -0700");
+        assert_eq!(format("1979-05-27T07:32:00", "date('%d/%m/%Y %H:%M')".into()),
+                   "27/05/1979 07:32");
+        assert_eq!(format("1979-05-27", "date('%B %d, %Y')".into()), "May 27, 1979");
+        assert_eq!(format("someday", f.clone()), "someday");
+        assert!(validate("someday", f).is_some());
+        assert!(validate("1979-05-27", "date('%Q')".into()).is_some());
+    }
+
     #[test]
     fn directory_path() {
         let p = "path.to.my.directory";
         assert_eq!(format(p, "packaged".into()), "path/to/my/directory");
     }
 
+    #[test]
+    fn borrow_unchanged() {
+        let unchanged = [("fabulous is rust", "lower"), ("FABULOUS", "upper"),
+                         ("Fabulous is", "cap"), ("fabulous Is", "decap"),
+                         ("Fabulous Is Rust", "start"), ("fab_ulous is", "word"),
+                         ("fabulous-is", "hyphen"), ("fab_ulous is", "space"),
+                         ("FabulousIsRust", "Camel"), ("fabulous-is", "norm"),
+                         ("Fabulous_Is", "snake"), ("path/to", "packaged")];
+        for &(s, f) in &unchanged {
+            match format(s, f.into()) {
+                Cow::Borrowed(b) => assert_eq!(b, s),
+                Cow::Owned(o) => panic!("{:?} is formatted into {:?} with {}", s, o, f),
+            }
+        }
+        match format(W, "lower".into()) {
+            Cow::Owned(o) => assert_eq!(o, "fabulous is rust"),
+            Cow::Borrowed(_) => panic!("{:?} is not formatted", W),
+        }
+    }
+
     #[test]
     fn add_random() {
         let len = W.len();
@@ -84,7 +221,11 @@ mod format_test {
 mod template_test {
 
     use std::collections::HashMap;
+    use std::io::{self, Write};
     use std::str;
+    use std::sync::Arc;
+    use rig::errors::ErrorKind;
+    use rig::format::{Formatter, FormatterRegistry};
     use rig::template::*;
 
     #[test]
@@ -121,6 +262,182 @@ mod template_test {
         assert_eq!(str::from_utf8(&out).unwrap(), "Hello, Rust!");
     }
 
+    #[test]
+    fn malformed_is_error() {
+        let params = HashMap::new();
+        let mut out = Vec::new();
+        let err = Template::write_once(&mut out, Style::ST, "ok\n  $broken", &params).unwrap_err();
+        match *err.kind() {
+            ErrorKind::Parse(line, column) => assert_eq!((line, column), (2, 3)),
+            ref other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    struct BrokenPipe;
+
+    impl Write for BrokenPipe {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"))
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_failure_is_error() {
+        let mut params = HashMap::new();
+        params.insert("name".to_owned(), "Rust".to_owned());
+        assert!(Template::write_once(&mut BrokenPipe, Style::ST, "Hello, $name$!", &params).is_err());
+    }
+
+    /// Accepts a byte at a time, being interrupted before every other write and flush.
+    struct Trickle {
+        written: Vec<u8>,
+        interrupt: bool,
+    }
+
+    impl Write for Trickle {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                return Err(io::Error::new(io::ErrorKind::Interrupted, "signal"));
+            }
+            self.written.extend(buf.iter().take(1));
+            Ok(buf.len().min(1))
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                return Err(io::Error::new(io::ErrorKind::Interrupted, "signal"));
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn short_writes_are_completed() {
+        let mut params = HashMap::new();
+        params.insert("name".to_owned(), "Rust".to_owned());
+        let mut out = Trickle {
+            written: Vec::new(),
+            interrupt: false,
+        };
+        Template::write_once(&mut out, Style::ST, "Hello, $name$!", &params).unwrap();
+        assert_eq!(str::from_utf8(&out.written).unwrap(), "Hello, Rust!");
+    }
+
+    /// Counts calls of `write`, as each of them would be a syscall on unbuffered sink.
+    #[derive(Default)]
+    struct Syscalls {
+        written: Vec<u8>,
+        writes: usize,
+        flushes: usize,
+    }
+
+    impl Write for Syscalls {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.writes += 1;
+            self.written.extend(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn buffered_writes() {
+        let mut params = HashMap::new();
+        params.insert("a".to_owned(), "x".to_owned());
+        let mut out = Syscalls::default();
+        Template::write_once(&mut out, Style::ST, "$a$-".repeat(100), &params).unwrap();
+        assert_eq!(out.written, "x-".repeat(100).into_bytes());
+        assert_eq!((out.writes, out.flushes), (1, 1));
+    }
+
+    #[test]
+    fn vectored_writes() {
+        let mut params = HashMap::new();
+        params.insert("a".to_owned(), "value".repeat(10));
+        let body = "$a;format=\"upper\"$, ".repeat(3000);
+        let expected = format!("{}, ", "VALUE".repeat(10)).repeat(3000);
+
+        let mut out = Vec::new();
+        Template::write_once(&mut out, Style::ST, &body, &params).unwrap();
+        assert_eq!(str::from_utf8(&out).unwrap(), expected);
+
+        let mut out = Trickle {
+            written: Vec::new(),
+            interrupt: false,
+        };
+        Template::write_once(&mut out, Style::ST, &body, &params).unwrap();
+        assert_eq!(str::from_utf8(&out.written).unwrap(), expected);
+    }
+
+    #[test]
+    fn invalid_formatter_is_error() {
+        let mut params = HashMap::new();
+        params.insert("name".to_owned(), "???".to_owned());
+        let mut out = Vec::new();
+        let err = Template::write_once(&mut out, Style::ST, "$name;format=\"upper,Camel\"$", &params)
+            .unwrap_err();
+        match *err.kind() {
+            ErrorKind::Formatter(ref param, ref value, ref formatter, _) => {
+                assert_eq!((param.as_ref(), value.as_ref(), formatter.as_ref()),
+                           ("name", "???", "Camel"));
+            }
+            ref other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn formatters_with_arguments() {
+        let mut params = HashMap::new();
+        params.insert("version".to_owned(), "1.0-SNAPSHOT".to_owned());
+        let cases = [(Style::ST, "$version;format=\"replace('-SNAPSHOT', ''),pad(5)\"$|"),
+                     (Style::Tera, "{{ version | replace(\"-SNAPSHOT\", \"\") | pad(5) }}|"),
+                     (Style::Liquid, "{{ version | replace: '-SNAPSHOT', '' | pad: 5 }}|")];
+        for &(ref style, body) in &cases {
+            let rendered = Template::read_str(style.clone(), body).render(&params).unwrap();
+            assert_eq!(rendered, "1.0  |", "{:?}", style);
+        }
+    }
+
+    #[test]
+    fn custom_formatters() {
+        let mut registry = FormatterRegistry::new();
+        registry.register("guard", |s: &str| format!("{}_H", s.to_uppercase().replace('-', "_")));
+        registry.register("upper", |s: &str| format!("<{}>", s));
+        assert!(registry.contains("guard") && !registry.contains("snake"));
+
+        let mut params = HashMap::new();
+        params.insert("name".to_owned(), "my-crate".to_owned());
+        let ph = Placeholder::new("name", Some("snake,guard".to_owned()), Style::ST);
+        assert_eq!(ph.format_with_registry(&params, &registry), "MY_CRATE_H");
+        let ph = Placeholder::new("name", Some("upper|oops".to_owned()), Style::Tera);
+        assert_eq!(ph.format_with_registry(&params, &registry), "<my-crate>");
+        assert_eq!(ph.format_with(&params), "MY-CRATE");
+    }
+
+    #[test]
+    fn render_custom_formatters() {
+        let mut registry = FormatterRegistry::new();
+        registry.register("guard", |s: &str| format!("{}_H", s.to_uppercase().replace('-', "_")));
+        let cache = FormatCache::with_registry(Arc::new(registry));
+        let mut params = HashMap::new();
+        params.insert("name".to_owned(), "my-crate".to_owned());
+
+        let tpl = Template::read_str(Style::Tera,
+                                     "#ifndef {{ name | guard }} // {{ name | upper }}");
+        let mut out = Vec::new();
+        tpl.write_cached(&mut out, &params, &cache).unwrap();
+        assert_eq!(str::from_utf8(&out).unwrap(), "#ifndef MY_CRATE_H // MY-CRATE");
+        // without the registry, the name is not of any formatter
+        assert_eq!(tpl.render(&params).unwrap(), "#ifndef my-crate // MY-CRATE");
+    }
+
     #[test]
     fn escape_character() {
         let mut params: HashMap<String, String> = HashMap::new();
@@ -138,6 +455,41 @@ mod template_test {
                    "It's a $DOCUMENT_NAME$".to_owned());
     }
 
+    #[test]
+    fn escape_delimiters() {
+        let mut params = HashMap::new();
+        params.insert("name".to_owned(), "rig".to_owned());
+        let cases = [(Style::ST, "$$$name$ costs $$5", "$rig costs $5"),
+                     (Style::Path, "$$_$name$", "$_rig"),
+                     (Style::Tera, "{{ \"{{\" }} name }} is {{ name }}", "{{ name }} is rig"),
+                     (Style::Tera,
+                      "{% raw %}{{ name }}{% if x %}{% endraw %} {{ name }}",
+                      "{{ name }}{% if x %} rig"),
+                     (Style::Liquid, "{{ '{{' }}{% raw %} }}{% endraw %}", "{{ }}"),
+                     (Style::Handlebars, "\\{{name}} is {{name}}", "{{name}} is rig")];
+        for &(ref style, body, expected) in &cases {
+            let rendered = Template::read_str(style.clone(), body).render(&params).unwrap();
+            assert_eq!(rendered, expected, "{:?}", style);
+        }
+
+        for body in &["{% raw %}{{ name }}", "{{ \"{{ }}", "{{ \"a\" | upper }}"] {
+            assert!(Template::read_str(Style::Tera, body).render(&params).is_err(), "{}", body);
+        }
+    }
+
+    #[test]
+    fn verbatim_regions() {
+        let mut params = HashMap::new();
+        params.insert("name".to_owned(), "rig".to_owned());
+        let body = "$name$: $--$if(x)$ $name;format=\"upper\"$ \\$--\n{% raw %}{{ x }}";
+        assert_eq!(Template::read_str(Style::ST, body).render(&params).unwrap(),
+                   "rig: $if(x)$ $name;format=\"upper\"$ \\\n{% raw %}{{ x }}");
+        let body = "{% raw %}$--{{ name }}{% endraw %}{{ name }}";
+        assert_eq!(Template::read_str(Style::Tera, body).render(&params).unwrap(),
+                   "$--{{ name }}rig");
+        assert!(Template::read_str(Style::ST, "$-- $name$").render(&params).is_err());
+    }
+
     #[test]
     fn giter8_template() {
         let mut params: HashMap<String, String> = HashMap::new();
@@ -146,22 +498,592 @@ mod template_test {
 
         let mut out = Vec::new();
 
-        let mut tpl =
+        let tpl =
             Template::new_g8(r#"trait $name;format="Camel"$[-A] extends js.Dictionary[A]"#);
         tpl.write_to(&mut out, &params).unwrap();
 
         assert_eq!(str::from_utf8(&out).unwrap(),
                    "trait AwesomeDistributedInterface[-A] extends js.Dictionary[A]".to_owned());
     }
+
+    #[test]
+    fn render_many_times() {
+        let body = "Hi $name$,\n\t終わり $name;format=\"upper\"$ \\$ $x$";
+        let tpl = Template::new_g8(body);
+        assert_eq!(tpl.segments().len(), 8);
+        match tpl.segments()[7] {
+            Segment::Placeholder(offset, i) => {
+                let (symbol, ph) = tpl.placeholder(i);
+                assert_eq!((&body[offset..], ph.name()), ("$x$", "x"));
+                assert_eq!(tpl.names()[symbol.index()], "x");
+            }
+            ref other => panic!("unexpected segment: {:?}", other),
+        }
+        let raw = tpl.segments()
+            .iter()
+            .filter_map(|s| match *s {
+                Segment::Raw(ref range) => Some(&body[range.clone()]),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(raw, vec!["Hi ", ",\n\t終わり ", " ", "$", " "]);
+
+        for name in &["rust", "ruby"] {
+            let mut params = HashMap::new();
+            params.insert("name".to_owned(), name.to_string());
+            let mut out = Vec::new();
+            tpl.write_to(&mut out, &params).unwrap();
+            assert_eq!(str::from_utf8(&out).unwrap(),
+                       format!("Hi {},\n\t終わり {} $ x", name, name.to_uppercase()));
+        }
+
+        let positions = tpl.scan().placeholders.into_iter().map(|(pos, _)| pos).collect::<Vec<_>>();
+        let expected = tpl.segments()
+            .iter()
+            .filter_map(|s| match *s {
+                Segment::Placeholder(offset, _) => Some(Position::at(body, offset)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(positions, expected);
+        assert_eq!(positions[1], Position { line: 2, column: 6 });
+    }
+
+    #[test]
+    fn intern_placeholders() {
+        let tpl = Template::new_g8("$a$ $b$ $a$ $a;format=\"upper\"$ $a$");
+        assert_eq!(tpl.names(), &["a".to_owned(), "b".to_owned()]);
+        let indices = tpl.segments()
+            .iter()
+            .filter_map(|s| match *s {
+                Segment::Placeholder(_, i) => Some(i),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(indices, vec![0, 1, 0, 2, 0]);
+        assert_eq!(tpl.placeholder(0).0, tpl.placeholder(2).0);
+        assert!(tpl.placeholder(0).0 != tpl.placeholder(1).0);
+
+        let mut params = HashMap::new();
+        params.insert("a".to_owned(), "x".to_owned());
+        let mut out = Vec::new();
+        tpl.write_to(&mut out, &params).unwrap();
+        assert_eq!(str::from_utf8(&out).unwrap(), "x b x X x");
+    }
+
+    #[test]
+    fn size_hint() {
+        assert_eq!(Template::new_g8("").size_hint(), 0);
+        assert_eq!(Template::new_g8("plain \\$ text").size_hint(), 12);
+        assert_eq!(Template::new_g8("$a$, $b;format=\"upper\"$ and $a$").size_hint(), 7 + 3 * 16);
+        assert_eq!(Template::read_str(Style::Tera, "{{ a | upper }}!").size_hint(), 1 + 16);
+    }
+
+    #[test]
+    fn memoize_formatted_values() {
+        let mut params = HashMap::new();
+        params.insert("a".to_owned(), "my name".to_owned());
+        params.insert("b".to_owned(), "other name".to_owned());
+        let cache = FormatCache::new();
+
+        let tpl = Template::new_g8("$a;format=\"snake\"$ $a$ $b;format=\"snake\"$ $a;format=\"snake\"$");
+        let mut out = Vec::new();
+        tpl.write_cached(&mut out, &params, &cache).unwrap();
+        assert_eq!(str::from_utf8(&out).unwrap(), "my_name my name other_name my_name");
+        assert_eq!(cache.len(), 2);
+
+        let mut out = Vec::new();
+        Template::new_g8("$a;format=\"snake\"$.rs").write_cached(&mut out, &params, &cache).unwrap();
+        assert_eq!(str::from_utf8(&out).unwrap(), "my_name.rs");
+        assert_eq!(cache.len(), 2);
+
+        let tpl = Template::new_g8("$a;format=\"random\"$ $a;format=\"random\"$");
+        let mut out = Vec::new();
+        tpl.write_cached(&mut out, &params, &cache).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let values = out.split(' ').collect::<Vec<_>>();
+        assert!(values[0] != values[1]);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn analyze_template() {
+        let body = "{{ a | upper }}\n{% if b %}{{ a | lower | upper }}{% endif %}\n\
+                    {% for x in c %}{{ x }}{{ d }}{% endfor %}\n{{ e";
+        let tpl = Template::read_str(Style::Tera, body);
+        let analysis = tpl.analyze();
+        assert_eq!(analysis.params, vec!["a", "b", "c", "d"]);
+        assert_eq!(analysis.placeholders
+                       .iter()
+                       .map(|&(pos, ref ph)| (pos.line, pos.column, ph.name()))
+                       .collect::<Vec<_>>(),
+                   vec![(1, 1, "a"), (2, 11, "a"), (3, 24, "d")]);
+        assert_eq!(analysis.formatters("a"), vec!["upper", "lower"]);
+        assert!(analysis.formatters("b").is_empty());
+        assert_eq!(analysis.error, Some(Position { line: 4, column: 1 }));
+    }
+
+    #[test]
+    fn render_streaming() {
+        let mut params = HashMap::new();
+        params.insert("a".to_owned(), "value".to_owned());
+        params.insert("b".to_owned(), "true".to_owned());
+        let lines = |line: &str, n: usize| (0..n).map(|_| line).collect::<String>();
+        let body = format!("{}$if(b)$\n{}$endif$\n$a;format=\"upper\"$",
+                           lines("line $a$\n", 10000),
+                           lines("in block $a$\n", 10000));
+        let options = RenderOptions::default();
+
+        let mut out = Vec::new();
+        Template::write_stream(Style::ST, body.as_bytes(), &mut out, &params, &options).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(),
+                   Template::new_g8(&body).render(&params).unwrap());
+
+        let body = format!("{}$broken\n", lines("ok\n", 30000));
+        let mut out = Vec::new();
+        let err = Template::write_stream(Style::ST, body.as_bytes(), &mut out, &params, &options)
+            .unwrap_err();
+        match *err.kind() {
+            ErrorKind::Parse(line, column) => assert_eq!((line, column), (30001, 1)),
+            ref other => panic!("unexpected error: {:?}", other),
+        }
+
+        let mut registry = FormatterRegistry::new();
+        registry.register("guard", |s: &str| format!("{}_H", s.to_uppercase()));
+        let cache = FormatCache::with_registry(Arc::new(registry));
+        let mut loader = HashMap::new();
+        loader.insert("b.st".to_owned(), "$a;format=\"guard\"$".to_owned());
+        let body = format!("{}$include(\"b.st\")$\n", lines("ok\n", 30000));
+        let mut out = Vec::new();
+        Template::write_stream_including(Style::ST,
+                                         "t.st",
+                                         body.as_bytes(),
+                                         &mut out,
+                                         &params,
+                                         &cache,
+                                         &options,
+                                         &loader)
+            .unwrap();
+        assert!(String::from_utf8(out).unwrap().ends_with("ok\nVALUE_H\n"));
+    }
+
+    #[test]
+    fn analyze_streaming() {
+        let lines = |line: &str, n: usize| (0..n).map(|_| line).collect::<String>();
+        let body = format!("{}$a$ $b;format=\"upper\"$\n$if(c)$\n{}$endif$\n$broken",
+                           lines("ok\n", 30000),
+                           lines("$d$\n", 20000));
+        let whole = Template::read_str(Style::ST, &body).analyze();
+        let streamed = Template::analyze_stream(Style::ST, body.as_bytes()).unwrap();
+        assert_eq!(streamed.placeholders, whole.placeholders);
+        assert_eq!(streamed.params, vec!["a", "b", "c", "d"]);
+        assert_eq!(streamed.error, Some(Position { line: 50004, column: 1 }));
+        assert_eq!(streamed.error, whole.error);
+    }
+
+    #[test]
+    fn custom_delimiters() {
+        let mut params = HashMap::new();
+        params.insert("name".to_owned(), "my app".to_owned());
+        let style = Style::Custom(Delimiters::new("<<", ">>", '|').unwrap());
+        let body = r"\section{<< name | upper >>} \<<name>> {{ x }} $y$ <<name|snake|upper>>";
+        assert_eq!(Template::read_str(style.clone(), body).render(&params).unwrap(),
+                   r"\section{MY APP} <<name>> {{ x }} $y$ MY_APP");
+        assert!(Template::read_str(style, "<<name").render(&params).is_err());
+
+        assert!(Delimiters::new("", ">>", '|').is_err());
+        assert!(Delimiters::new("<<", ">>", '_').is_err());
+    }
+
+    #[test]
+    fn include_templates() {
+        let mut loader = HashMap::new();
+        loader.insert("partials/a.txt".to_owned(), "[{% include \"b.txt\" %}]".to_owned());
+        loader.insert("partials/b.txt".to_owned(), "{{ name }}".to_owned());
+        // included templates are rendered in the style of the including one
+        loader.insert("partials/b.st".to_owned(), "$name$".to_owned());
+        let mut params = HashMap::new();
+        params.insert("name".to_owned(), "x".to_owned());
+        let render = |style: Style, name: &str, body: &str| {
+            let mut out = Vec::new();
+            Template::read_str(style, body)
+                .write_including(name,
+                                 &mut out,
+                                 &params,
+                                 &FormatCache::new(),
+                                 &RenderOptions::default(),
+                                 &loader)
+                .map(|_| ())
+                .map(|_| String::from_utf8(out).unwrap())
+        };
+
+        assert_eq!(render(Style::Tera, "t.txt", "{% include \"partials/a.txt\" %}!").unwrap(),
+                   "[x]!");
+        assert_eq!(render(Style::ST, "t.txt", "$include(\"partials/b.st\")$").unwrap(), "x");
+        match *render(Style::Tera, "c.txt", "{% include \"c.txt\" %}").unwrap_err().kind() {
+            ErrorKind::IncludeCycle(ref names) => assert_eq!(names.len(), 2),
+            ref other => panic!("unexpected error: {:?}", other),
+        }
+        let tpl = Template::read_str(Style::Tera, "{% include \"c.txt\" %}");
+        assert!(tpl.render(&params).is_err());
+    }
+
+    #[test]
+    fn render_missing_keys() {
+        let mut params = HashMap::new();
+        params.insert("a".to_owned(), "x".to_owned());
+        let tpl = Template::new_g8("$a$ $b;format=\"upper\"$ $c;default=\"z\"$ $d$");
+        let render = |missing| tpl.render_with(&params, &RenderOptions { missing: missing });
+
+        assert_eq!(render(MissingKeyBehavior::UseDefault).unwrap(), "x b z d");
+        assert_eq!(render(MissingKeyBehavior::KeepPlaceholder).unwrap(),
+                   "x $b;format=\"upper\"$ z $d$");
+        assert_eq!(render(MissingKeyBehavior::Empty).unwrap(), "x  z ");
+        match *render(MissingKeyBehavior::Error).unwrap_err().kind() {
+            ErrorKind::MissingParams(ref names) => {
+                assert_eq!(names, &vec!["b".to_owned(), "d".to_owned()])
+            }
+            ref other => panic!("unexpected error: {:?}", other),
+        }
+
+        let tpl = Template::read_str(Style::Tera, "{{ a }}{{ b | upper }}");
+        let options = RenderOptions { missing: MissingKeyBehavior::KeepPlaceholder };
+        assert_eq!(tpl.render_with(&params, &options).unwrap(), "x{{ b | upper }}");
+    }
+
+    #[test]
+    fn inline_tera() {
+        let mut params: HashMap<String, String> = HashMap::new();
+        params.insert("name".to_owned(), "my crate".to_owned());
+
+        let mut out = Vec::new();
+
+        Template::write_once(&mut out, Style::Tera, "fn {{ name | snake }}() {}", &params).unwrap();
+        assert_eq!(str::from_utf8(&out).unwrap(), "fn my_crate() {}");
+    }
+
+    #[test]
+    fn inline_handlebars() {
+        let mut params: HashMap<String, String> = HashMap::new();
+        params.insert("name".to_owned(), "my crate".to_owned());
+
+        let mut out = Vec::new();
+        Template::write_once(&mut out,
+                             Style::Handlebars,
+                             "{{#if x}}fn {{ snake name }}() {}{{/if}}{{! {{name}} }}{{else}}",
+                             &params)
+            .unwrap();
+        assert_eq!(str::from_utf8(&out).unwrap(),
+                   "{{#if x}}fn my_crate() {}{{/if}}{{! {{name}} }}{{else}}");
+
+        let found = Template::read_str(Style::Handlebars, "{{Camel name}} {{ else_branch }}")
+            .placeholders();
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].formatters(), &[Formatter::UpperCamel]);
+        assert_eq!(found[1].name(), "else_branch");
+    }
+
+    #[test]
+    fn inline_liquid() {
+        let mut params: HashMap<String, String> = HashMap::new();
+        params.insert("name".to_owned(), "my crate".to_owned());
+
+        let mut out = Vec::new();
+        Template::write_once(&mut out, Style::Liquid, "fn {{ name | snake }}() {}", &params)
+            .unwrap();
+        assert_eq!(str::from_utf8(&out).unwrap(), "fn my_crate() {}");
+
+        let found = Template::read_str(Style::Liquid, "{{ name | truncate: 3 }}").placeholders();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].name(), "name");
+    }
+
+    #[test]
+    fn conditional_blocks() {
+        let tera = Template::read_str(Style::Tera,
+                                      "{% if license == \"MIT\" %}MIT{% else %}{{ license }}\
+                                       {% endif %}{% if not year %}, no year{% endif %}");
+        let st = Template::new_g8("$if(name)$[$name$$if(bin.truthy)$ bin$endif$]$else$-$endif$");
+        assert_eq!(tera.conditions(),
+                   vec![&Condition::Equals("license".to_owned(), "MIT".to_owned()),
+                        &Condition::Not(Box::new(Condition::NonEmpty("year".to_owned())))]);
+
+        let render = |tpl: &Template, pairs: &[(&str, &str)]| {
+            let params = pairs.iter()
+                .map(|&(k, v)| (k.to_owned(), v.to_owned()))
+                .collect::<HashMap<_, _>>();
+            let mut out = Vec::new();
+            tpl.write_to(&mut out, &params).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(render(&tera, &[("license", "MIT")]), "MIT, no year");
+        assert_eq!(render(&tera, &[("license", "ISC"), ("year", "2017")]), "ISC");
+        assert_eq!(render(&st, &[("name", "app"), ("bin", "Yes")]), "[app bin]");
+        assert_eq!(render(&st, &[("name", "app"), ("bin", "no")]), "[app]");
+        assert_eq!(render(&st, &[("name", "")]), "-");
+
+        let unclosed = Template::read_str(Style::Tera, "a\n{% if x %}{% if y %}{% endif %}");
+        assert_eq!(unclosed.scan().error, Some(Position { line: 2, column: 1 }));
+        assert!(unclosed.write_to(&mut Vec::new(), &HashMap::new()).is_err());
+        let raw = Template::read_str(Style::Tera, "{% if x %}{% endif %}{% raw %}");
+        assert!(raw.write_to(&mut Vec::new(), &HashMap::new()).is_err());
+        let stray = Template::new_g8("$if(x)$$else$$else$$endif$");
+        assert_eq!(stray.scan().error, Some(Position { line: 1, column: 14 }));
+        let malformed = Template::new_g8("ok $if(x y)$");
+        assert_eq!(malformed.scan().error, Some(Position { line: 1, column: 4 }));
+    }
+
+    #[test]
+    fn typed_conditions() {
+        let toml = "workers = 8\nratio = 0.5\nuse_docker = false\nname = \"false\"";
+        let mut params = Params::convert_toml(::toml::from_str(toml).unwrap());
+        assert_eq!(params.value("workers"), Some(ParamValue::Integer(8)));
+        assert_eq!(params.value("use_docker"), Some(ParamValue::Boolean(false)));
+        assert_eq!(params.get("workers"), Some(&"8".to_owned()));
+
+        let tpl = Template::read_str(Style::Tera,
+                                     "{% if workers > 4 %}many{% endif %}\
+                                      {% if workers == 8.0 %} eight{% endif %}\
+                                      {% if ratio <= '0.5' %} half{% endif %}\
+                                      {% if use_docker %} docker{% endif %}\
+                                      {% if name %} {{ name }}{% endif %}");
+        assert_eq!(tpl.conditions()[0],
+                   &Condition::Compare("workers".to_owned(), Comparison::Greater, "4".to_owned()));
+        assert_eq!(tpl.render(&params).unwrap(), "many eight half false");
+
+        params.param_map.insert("workers".to_owned(), "16".to_owned());
+        params.param_map.insert("use_docker".to_owned(), "true".to_owned());
+        assert_eq!(tpl.render(&params).unwrap(), "many half docker false");
+        let bad = Template::read_str(Style::Tera, "{% if a > b %}{% endif %}");
+        assert!(bad.render(&params).is_err());
+    }
+
+    #[test]
+    fn loop_over_lists() {
+        let tpl = Template::read_str(Style::Tera,
+                                     "[deps]\n{% for dep in deps %}\
+                                      {{ dep | snake }} = \"{{ v }}\"\n\
+                                      {% if dep == \"serde\" %}# {{ deps }}\n{% endif %}\
+                                      {% endfor %}{% for x in none %}{{ x }}{% endfor %}end");
+        let found = tpl.placeholders();
+        assert_eq!(found.iter().map(|ph| ph.name()).collect::<Vec<_>>(), vec!["v", "deps"]);
+        assert_eq!(tpl.lists(), vec!["deps", "none"]);
+
+        let mut params = Params::from_map(vec![("v".to_owned(), "1".to_owned())]);
+        params.lists.insert("deps".to_owned(), vec!["serde".to_owned(), "Error Chain".to_owned()]);
+        let mut out = Vec::new();
+        tpl.write_to(&mut out, &params).unwrap();
+        assert_eq!(str::from_utf8(&out).unwrap(),
+                   "[deps]\nserde = \"1\"\n# deps\nError_Chain = \"1\"\nend");
+
+        let unbalanced = Template::read_str(Style::Tera, "{% for x in xs %}{% endif %}");
+        assert_eq!(unbalanced.scan().error, Some(Position { line: 1, column: 18 }));
+        let malformed = Template::read_str(Style::Liquid, "{% for x of xs %}{% endfor %}");
+        assert_eq!(malformed.scan().error, Some(Position { line: 1, column: 1 }));
+    }
+
+    #[test]
+    fn giter8_formats() {
+        let mut params = HashMap::new();
+        params.insert("name".to_owned(), "My App".to_owned());
+        params.insert("organization".to_owned(), "com.example".to_owned());
+
+        let st = Template::new_g8("$name; format = \"normalize , upper\"$ \
+                                   $name;format=\"snake\"$");
+        assert_eq!(st.placeholders()[0].formatters(),
+                   &[Formatter::Normalize, Formatter::UpperCase]);
+        assert_eq!(st.render(&params).unwrap(), "MY-APP My_App");
+
+        let path = Template::read_str(Style::Path, "$organization__packaged$/$name__snake__lower$");
+        assert_eq!(path.render(&params).unwrap(), "com/example/my_app");
+    }
+
+    #[test]
+    fn placeholder_defaults() {
+        let mut params = HashMap::new();
+        let st = Template::new_g8("$author;format=\"upper\";default=\"anonymous\"$ \
+                                   $license; default=\"MIT\";format=\"lower\"$ $year$");
+        let found = st.placeholders();
+        assert_eq!(found[0].default_value(), Some("anonymous"));
+        assert_eq!(found[1].formatters(), &[Formatter::LowerCase]);
+        assert_eq!(found[2].default_value(), None);
+        assert_eq!(st.render(&params).unwrap(), "ANONYMOUS mit year");
+
+        let tera = Template::read_str(Style::Tera,
+                                      "{{ author | default(value=\"Anonymous\") | upper }}");
+        assert!(tera.placeholders()[0].unknown_formatters().is_empty());
+        let liquid = Template::read_str(Style::Liquid, "{{ author | default: 'Anonymous' }}");
+        assert_eq!(tera.render(&params).unwrap(), "ANONYMOUS");
+        assert_eq!(liquid.render(&params).unwrap(), "Anonymous");
+
+        params.insert("author".to_owned(), "me".to_owned());
+        assert_eq!(st.render(&params).unwrap(), "ME mit year");
+        assert_eq!(liquid.render(&params).unwrap(), "me");
+    }
+
+    #[test]
+    fn render_to_string() {
+        let mut params = HashMap::new();
+        params.insert("name".to_owned(), "my app".to_owned());
+        let tpl = Template::new_g8("$name;format=\"snake\"$ $x$");
+        assert_eq!(tpl.render(&params).unwrap(), "my_app x");
+        assert_eq!(tpl.render(&HashMap::new()).unwrap(), "name x");
+        assert_eq!(Template::render_once(Style::Tera, "{{ name | upper }}", &params).unwrap(),
+                   "MY APP");
+        assert!(Template::render_once(Style::Tera, "{{ name", &params).is_err());
+    }
+
+    #[test]
+    fn iterate_chunks() {
+        use rig::parser::{Chunk, TemplateIter};
+
+        let style = Style::ST;
+        let mut iter = TemplateIter::new("a\\$b $x$ c $oops", &style);
+        let chunks = iter.by_ref().take_while(|c| c.is_ok()).map(|c| c.unwrap()).collect::<Vec<_>>();
+        assert_eq!(chunks,
+                   vec![Chunk::Raw(0, "a"),
+                        Chunk::Raw(2, "$"),
+                        Chunk::Raw(3, "b "),
+                        Chunk::Tag(5, Tag::Placeholder(Placeholder::no_format("x"))),
+                        Chunk::Raw(8, " c ")]);
+        assert!(iter.next().is_none());
+
+        let style = Style::Tera;
+        let tags = TemplateIter::new("{% if a %}{{ b }}{% endif %}", &style)
+            .filter_map(|c| match c {
+                Ok(Chunk::Tag(at, _)) => Some(at),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(tags, vec![0, 10, 17]);
+    }
+
+    #[test]
+    fn reject_unsupported_values() {
+        use toml::value::{Table, Value};
+
+        let mut toml = Table::new();
+        toml.insert("name".to_owned(), Value::String("app".to_owned()));
+        toml.insert("tags".to_owned(), Value::Array(vec![Value::Integer(1)]));
+        let params = Params::try_convert_toml(toml.clone()).unwrap();
+        assert_eq!(params.get_list("tags"), Some(&["1".to_owned()][..]));
+
+        toml.insert("nested".to_owned(), Value::Array(vec![Value::Table(Table::new())]));
+        match *Params::try_convert_toml(toml).unwrap_err().kind() {
+            ErrorKind::UnsupportedValue(ref key) => assert_eq!(key, "nested"),
+            ref other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn flatten_nested_tables() {
+        let config = "name = \"app\"\n[author]\nname = \"me\"\n[author.links]\nsites = [\"a\"]\n";
+        let params = Params::try_convert_toml(toml::from_str(config).unwrap()).unwrap();
+        assert_eq!(params.get("author.name"), Some(&"me".to_owned()));
+        assert_eq!(params.get_list("author.links.sites"), Some(&["a".to_owned()][..]));
+
+        let body = "$name$ by $author.name;format=\"upper\"$$if(author.name)$!$endif$";
+        assert_eq!(Template::read_str(Style::ST, body).render(&params).unwrap(), "app by ME!");
+        let body = "{{ author.name }}{% for s in author.links.sites %} {{ s }}{% endfor %}";
+        assert_eq!(Template::read_str(Style::Tera, body).render(&params).unwrap(), "me a");
+    }
+
+    #[test]
+    fn resolve_references() {
+        let config = r#"
+            name = "My App"
+            package = "com.example.$name;format=\"norm\"$"
+            main = "$package$.Main"
+            other = "$unknown$ costs $5"
+        "#;
+        let mut params = Params::try_convert_toml(toml::from_str(config).unwrap()).unwrap();
+        params.resolve_refs().unwrap();
+        assert_eq!(params.get("package"), Some(&"com.example.my-app".to_owned()));
+        assert_eq!(params.get("main"), Some(&"com.example.my-app.Main".to_owned()));
+        assert_eq!(params.get("other"), Some(&"$unknown$ costs $5".to_owned()));
+
+        let config = "a = \"$b$\"\nb = \"x$c$\"\nc = \"$a$\"\n";
+        let mut params = Params::try_convert_toml(toml::from_str(config).unwrap()).unwrap();
+        match *params.resolve_refs().unwrap_err().kind() {
+            ErrorKind::CyclicParams(ref names) => assert_eq!(names, &["a", "b", "c", "a"]),
+            ref other => panic!("unexpected error: {:?}", other),
+        }
+        assert_eq!(params.get("a"), Some(&"$b$".to_owned()));
+    }
+
+    #[test]
+    fn resolve_interactive() {
+        use rig::i18n::Lang;
+        use toml::value::{Table, Value};
+
+        let mut defaults = Table::new();
+        let strings = [("name", "app"), ("author", "me"), ("license", "MIT"), ("version", "0.1.0")];
+        for &(k, v) in &strings {
+            defaults.insert(k.to_owned(), Value::String(v.to_owned()));
+        }
+        defaults.insert("year".to_owned(), Value::Integer(2017));
+        defaults.insert("deps".to_owned(), Value::Array(vec![Value::String("serde".to_owned())]));
+
+        let mut params = Params::from_map(vec![("author".to_owned(), "you".to_owned())]);
+        let mut output = Vec::new();
+        params.resolve_with(&defaults, Lang::En, &mut &b"rig\nBSD\n"[..], &mut output).unwrap();
+        assert_eq!(str::from_utf8(&output).unwrap(),
+                   "name [app]:license [MIT]:version [0.1.0]:");
+        let resolved = params.param_map
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(resolved,
+                   vec![("author", "you"),
+                        ("license", "BSD"),
+                        ("name", "rig"),
+                        ("version", "0.1.0"),
+                        ("year", "2017")]);
+        assert_eq!(params.get_list("deps"), Some(&["serde".to_owned()][..]));
+    }
+
+    #[test]
+    fn list_placeholders() {
+        let tpl = Template::new_g8(r#"$name;format="norm,upper"$ by $author$"#);
+        let found = tpl.placeholders();
+
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].name(), "name");
+        assert_eq!(found[0].formatters(), &[Formatter::Normalize, Formatter::UpperCase]);
+        assert_eq!(found[1].name(), "author");
+        assert!(found[1].formatters().is_empty());
+    }
+
+    #[test]
+    fn scan_positions() {
+        let tpl = Template::new_g8("line one\n  $name;format=\"bogus\"$\n\nbroken $oops\n");
+        let scan = tpl.scan();
+
+        assert_eq!(scan.placeholders.len(), 1);
+        let (pos, ref ph) = scan.placeholders[0];
+        assert_eq!(pos, Position { line: 2, column: 3 });
+        assert_eq!(ph.unknown_formatters(), vec!["bogus"]);
+        assert_eq!(scan.error, Some(Position { line: 4, column: 8 }));
+    }
 }
 
 mod project_test {
 
     extern crate tempdir;
+    use std::collections::HashMap;
     use std::fs;
+    use std::path::Path;
+    use std::sync::Arc;
 
-    use rig::fsutils;
-    use rig::project::{Configuration, Project};
+    use rig::format::{Formatter, FormatterRegistry};
+    use rig::fsutils::{self, Outcome, Written};
+    use rig::hooks::Hooks;
+    use rig::output::render_path;
+    use rig::project::{read_params, write_answers, Configuration, Ignores, IssueKind,
+                       OverwritePolicy, Project};
+    use rig::diagnostics::{Diagnostics, Warning};
+    use rig::errors::ErrorKind;
+    use rig::template::{Params, Style};
 
     const G8_PROPS: &'static str = r#"
         name = value1
@@ -261,4 +1183,1843 @@ mod project_test {
             assert!(fsutils::exists(&goal));
         }
     }
+
+    #[test]
+    fn collect_vars() {
+
+        let src = tempdir::TempDir::new("rig-vars-test").unwrap();
+        let src = src.path();
+        fs::create_dir_all(src.join("src/$package$")).unwrap();
+
+        fsutils::write_file(&src.join("Rig.toml"), TOML).unwrap();
+        fsutils::write_file(&src.join("src/$package$/main.rs"),
+                            "// {{ name | Camel }} by {{ author }}")
+            .unwrap();
+
+        let project = Project::new(None as Option<&str>, Configuration::Toml, false);
+        let vars = project.collect_vars(&src).unwrap();
+
+        assert_eq!(vars.keys().collect::<Vec<_>>(), vec!["author", "name", "package"]);
+        assert!(vars["name"].formatters.contains(&vec![Formatter::UpperCamel]));
+        assert!(vars["package"].files.contains(Path::new("src/$package$")));
+    }
+
+    #[test]
+    fn read_params_file() {
+        let dir = tempdir::TempDir::new("rig-params-test").unwrap();
+        let file = dir.path().join("fixtures.properties");
+        fsutils::write_file(&file, G8_PROPS).unwrap();
+
+        let params = read_params(&file, Configuration::JavaProps).unwrap();
+        assert_eq!(params.get("bar"), Some(&"baz!".to_owned()));
+        let missing = dir.path().join("missing.toml");
+        match *read_params(&missing, Configuration::Toml).unwrap_err().kind() {
+            ErrorKind::FsFailure(operation, ref path) => {
+                assert_eq!(operation, "opening file");
+                assert_eq!(path, &missing);
+            }
+            ref other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn answers_round_trip() {
+        let dir = tempdir::TempDir::new("rig-answers-test").unwrap();
+        let file = dir.path().join("answers.toml");
+
+        let project = Project::new_g8(None);
+        fsutils::write_file(&dir.path().join("default.properties"), G8_PROPS).unwrap();
+        let params = project.default_params(dir.path()).unwrap();
+
+        write_answers(&file, &params).unwrap();
+        let answers = read_params(&file, Configuration::Toml).unwrap();
+        assert_eq!(answers.param_map, params.param_map);
+    }
+
+    #[cfg(feature = "tera")]
+    #[test]
+    fn render_failure_is_error() {
+        use rig::template::Position;
+
+        let src = tempdir::TempDir::new("rig-failure-test").unwrap();
+        let src = src.path();
+        fsutils::write_file(&src.join("Rig.toml"), TOML).unwrap();
+        fsutils::write_file(&src.join("README"), "{{ name }}\n  {{ undefined_param }}").unwrap();
+
+        let dest = tempdir::TempDir::new("generated-proj").unwrap();
+
+        let project = Project::new(None as Option<&str>, Configuration::Toml, false);
+        let params = project.default_params(&src).unwrap();
+        let err = project.generate(&params, &src, dest.path(), false).unwrap_err();
+        match *err.kind() {
+            ErrorKind::RenderFailure(ref file, position) => {
+                assert_eq!(file, Path::new("README"));
+                assert_eq!(position, Some(Position { line: 2, column: 3 }));
+            }
+            ref other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn render_single_file() {
+        let mut map = HashMap::new();
+        map.insert("name".to_owned(), "my app".to_owned());
+        let params = Params::from_map(map);
+
+        let project = Project::new(None as Option<&str>, Configuration::Toml, false);
+        assert_eq!(project.render("t", "{{ name | Camel }}!", &params).unwrap(), "MyApp!");
+        // native parser leaves placeholders without value as is, unlike Tera
+        assert_eq!(project.render("t", "{{ missing }}", &params).is_err(), cfg!(feature = "tera"));
+
+        let project = Project::new_g8(None);
+        assert_eq!(project.render("t", "$name;format=\"snake\"$", &params).unwrap(), "my_app");
+    }
+
+    #[cfg(feature = "handlebars")]
+    #[test]
+    fn render_handlebars() {
+        let src = tempdir::TempDir::new("rig-handlebars-test").unwrap();
+        let src = src.path();
+        fsutils::write_file(&src.join("Rig.toml"), "name = \"my app\"\ndeps = [\"a\", \"b\"]")
+            .unwrap();
+        fsutils::write_file(&src.join("header"), "// {{ name }} <generated>").unwrap();
+        fsutils::write_file(&src.join("main.rs"), "{{> header}}\nfn {{snake name}}() {}").unwrap();
+
+        let dest = tempdir::TempDir::new("generated-proj").unwrap();
+        let mut project = Project::new(None as Option<&str>, Configuration::Toml, false);
+        project.style = rig::template::Style::Handlebars;
+        let params = project.default_params(&src).unwrap();
+        project.generate(&params, &src, dest.path(), false).unwrap();
+        assert_eq!(fsutils::read_file(&dest.path().join("main.rs")).unwrap(),
+                   "// my app <generated>\nfn my_app() {}");
+
+        assert_eq!(project.render("t", "{{#if name}}{{Camel name}}{{/if}}!", &params).unwrap(),
+                   "MyApp!");
+        assert!(project.render("t", "{{ word missing }}", &params).is_err());
+        assert_eq!(project.render("t", "{{#each deps}}[{{this}}]{{/each}}", &params).unwrap(),
+                   "[a][b]");
+    }
+
+    #[test]
+    fn select_engines_per_glob() {
+        let src = tempdir::TempDir::new("rig-engines-test").unwrap();
+        let src = src.path();
+        fsutils::write_file(&src.join("Rig.toml"),
+                            "name = \"my app\"\n[engines]\n\"*.hbs\" = \"handlebars\"\n\
+                             \"docs/**\" = \"giter8\"\n\"docs/*.hbs\" = \"tera\"")
+            .unwrap();
+        fsutils::create_dir_all(&src.join("docs")).unwrap();
+        fsutils::write_file(&src.join("main.rs.hbs"), "fn {{snake name}}() {}").unwrap();
+        fsutils::write_file(&src.join("docs/README.md"), "# $name;format=\"Camel\"$").unwrap();
+        fsutils::write_file(&src.join("docs/index.hbs"), "{{ name | hyphen }}").unwrap();
+
+        let dest = tempdir::TempDir::new("generated-proj").unwrap();
+        let project = Project::new(None as Option<&str>, Configuration::Toml, false);
+        let params = project.default_params(&src).unwrap();
+        assert!(params.get("engines").is_none());
+        project.generate(&params, &src, dest.path(), false).unwrap();
+
+        let read = |p: &str| fsutils::read_file(&dest.path().join(p)).unwrap();
+        assert_eq!(read("main.rs.hbs"), "fn my_app() {}");
+        assert_eq!(read("docs/README.md"), "# MyApp");
+        assert_eq!(read("docs/index.hbs"), "my-app");
+
+        fsutils::write_file(&src.join("Rig.toml"), "[engines]\n\"*\" = \"mustache\"").unwrap();
+        assert!(project.generate(&params, &src, dest.path(), false).is_err());
+    }
+
+    #[test]
+    fn invalid_utf8_offset() {
+        let src = tempdir::TempDir::new("rig-utf8-test").unwrap();
+        let file = src.path().join("logo.png");
+        fs::write(&file, b"\x89PN\xffG").unwrap();
+
+        match *fsutils::read_file(&file).unwrap_err().kind() {
+            ErrorKind::InvalidUtf8(ref path, offset) => {
+                assert_eq!(path, &file);
+                assert_eq!(offset, 0);
+            }
+            ref other => panic!("unexpected error: {:?}", other),
+        }
+
+        fs::write(&file, b"caf\xc3\xa9 \xc3").unwrap();
+        match *fsutils::read_file(&file).unwrap_err().kind() {
+            ErrorKind::InvalidUtf8(_, offset) => assert_eq!(offset, 6),
+            ref other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn strict_generation() {
+        let src = tempdir::TempDir::new("rig-strict-test").unwrap();
+        let src = src.path();
+        fsutils::write_file(&src.join("default.properties"), "name = x\nempty =").unwrap();
+        fsutils::write_file(&src.join("README"), "$name$ $author$ $empty$").unwrap();
+        fsutils::write_file(&src.join("$license$.txt"), "").unwrap();
+
+        let dest = tempdir::TempDir::new("generated-proj").unwrap();
+
+        let mut project = Project::new_g8(None);
+        let params = project.default_params(&src).unwrap();
+        assert!(project.generate(&params, &src, dest.path(), false).is_ok());
+
+        project.strict = true;
+        let err = project.generate(&params, &src, dest.path(), false).unwrap_err();
+        match *err.kind() {
+            ErrorKind::MissingParams(ref names) => {
+                assert_eq!(names, &vec!["author".to_owned(), "license".to_owned()])
+            }
+            ref other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn collect_warnings() {
+        let src = tempdir::TempDir::new("rig-warnings-test").unwrap();
+        let src = src.path();
+        fsutils::write_file(&src.join("Rig.toml"), "name = \"x\"\nempty = \"\"\nlist = [[1]]\n")
+            .unwrap();
+        fsutils::write_file(&src.join("README"), "{{ name }} {{ empty }}").unwrap();
+
+        let dest = tempdir::TempDir::new("generated-proj").unwrap();
+        let project = Project::new(None as Option<&str>, Configuration::Toml, false);
+
+        let mut diag = Diagnostics::new();
+        let params = project.default_params_with(&src, &mut diag).unwrap();
+        project.generate_with(&params, &src, dest.path(), false, &mut diag).unwrap();
+        assert_eq!(diag.warnings(),
+                   &[Warning::DroppedParam("list".to_owned()),
+                     Warning::EmptyValue("empty".to_owned())]);
+
+        let dest = dest.path().join("denied");
+        diag.deny = true;
+        assert!(project.generate_with(&params, &src, &dest, false, &mut diag).is_err());
+        assert!(!dest.exists());
+    }
+
+    #[test]
+    fn generate_from_arrays() {
+        let src = tempdir::TempDir::new("rig-arrays-test").unwrap();
+        let src = src.path();
+        fsutils::write_file(&src.join("Rig.toml"),
+                            "name = \"x\"\ndependencies = [\"serde\", \"toml\"]\n")
+            .unwrap();
+        fsutils::write_file(&src.join("Cargo.toml"),
+                            "[dependencies]\n{% for dep in dependencies %}{{ dep }} = \"*\"\n\
+                             {% endfor %}")
+            .unwrap();
+
+        let dest = tempdir::TempDir::new("generated-proj").unwrap();
+        let project = Project::new(None as Option<&str>, Configuration::Toml, false);
+        let mut diag = Diagnostics::new();
+        let params = project.default_params_with(&src, &mut diag).unwrap();
+        assert!(diag.warnings().is_empty());
+        assert_eq!(params.get_list("dependencies"),
+                   Some(&["serde".to_owned(), "toml".to_owned()][..]));
+
+        project.generate(&params, &src, dest.path(), false).unwrap();
+        assert_eq!(fsutils::read_file(&dest.path().join("Cargo.toml")).unwrap(),
+                   "[dependencies]\nserde = \"*\"\ntoml = \"*\"\n");
+    }
+
+    #[test]
+    fn conditional_files() {
+        let src = tempdir::TempDir::new("rig-conditions-test").unwrap();
+        let src = src.path();
+        fsutils::write_file(&src.join("Rig.toml"),
+                            "name = \"x\"\nuse_ci = false\n\
+                             [conditions]\n\"ci/**\" = \"use_ci\"\n\"LICENSE\" = \"license\"\n")
+            .unwrap();
+        fsutils::create_dir_all(&src.join("ci")).unwrap();
+        fsutils::write_file(&src.join("ci/build.yml"), "{{ name }} {{ ci_token }}").unwrap();
+        fsutils::write_file(&src.join("LICENSE"), "MIT").unwrap();
+        fsutils::write_file(&src.join("README"), "{{ name }}").unwrap();
+
+        let mut project = Project::new(None as Option<&str>, Configuration::Toml, false);
+        project.strict = true;
+        let mut diag = Diagnostics::new();
+        let mut params = project.default_params_with(&src, &mut diag).unwrap();
+        assert!(diag.warnings().is_empty());
+        assert!(params.get("conditions.ci/**").is_none());
+        assert!(project.missing_params(&src, &params).unwrap().is_empty());
+
+        let dest = tempdir::TempDir::new("generated-proj").unwrap();
+        project.generate(&params, &src, dest.path(), false).unwrap();
+        assert!(dest.path().join("README").exists());
+        assert!(!dest.path().join("ci").exists());
+        assert!(!dest.path().join("LICENSE").exists());
+
+        params.param_map.insert("use_ci".to_owned(), "true".to_owned());
+        assert_eq!(project.missing_params(&src, &params).unwrap(), vec!["ci_token".to_owned()]);
+        params.param_map.insert("ci_token".to_owned(), "t".to_owned());
+        let dest = tempdir::TempDir::new("generated-proj").unwrap();
+        project.generate(&params, &src, dest.path(), false).unwrap();
+        assert_eq!(fsutils::read_file(&dest.path().join("ci/build.yml")).unwrap(), "x t");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keep_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let src = tempdir::TempDir::new("rig-permissions-test").unwrap();
+        let src = src.path();
+        fsutils::create_dir_all(&src.join("bin")).unwrap();
+        fsutils::write_file(&src.join("bin/$name$.sh"), "echo $name$").unwrap();
+        fs::set_permissions(src.join("bin/$name$.sh"), fs::Permissions::from_mode(0o755))
+            .unwrap();
+
+        let dest = tempdir::TempDir::new("generated-proj").unwrap();
+        let project = Project::new_g8(None);
+        let params = Params::from_map(vec![("name".to_owned(), "run".to_owned())]);
+        project.generate(&params, &src, dest.path(), false).unwrap();
+
+        let script = dest.path().join("bin/run.sh");
+        assert_eq!(fsutils::read_file(&script).unwrap(), "echo run");
+        assert_eq!(fs::metadata(&script).unwrap().permissions().mode() & 0o777, 0o755);
+    }
+
+    #[test]
+    fn parse_files_lazily() {
+        let src = tempdir::TempDir::new("rig-lazy-test").unwrap();
+        let src = src.path();
+        fsutils::write_file(&src.join("default.properties"), "name = abc").unwrap();
+        fsutils::write_file(&src.join("README"), "$name$").unwrap();
+        fs::write(src.join("broken"), b"\xff$name$").unwrap();
+
+        let dest = tempdir::TempDir::new("generated-proj").unwrap();
+        let project = Project::new_g8(None);
+        let params = project.default_params(&src).unwrap();
+        // dry runs render every file too, so they fail the same way
+        for &dry_run in &[true, false] {
+            let err = project.generate(&params, &src, dest.path(), dry_run).unwrap_err();
+            match *err.kind() {
+                ErrorKind::InvalidUtf8(ref path, 0) => assert!(path.ends_with("broken")),
+                ref other => panic!("unexpected error: {:?}", other),
+            }
+        }
+        assert!(!dest.path().join("broken").exists());
+    }
+
+    #[test]
+    fn reject_path_traversal() {
+        let src = tempdir::TempDir::new("rig-traversal-test").unwrap();
+        let src = src.path();
+        fsutils::write_file(&src.join("Rig.toml"), "name = \"..\"\npackage = \"com.example\"")
+            .unwrap();
+        fs::create_dir_all(src.join("$package__packaged$/$name$")).unwrap();
+
+        let dest = tempdir::TempDir::new("generated-proj").unwrap();
+        let mut project = Project::new(None as Option<&str>, Configuration::Toml, false);
+        let params = project.default_params(&src).unwrap();
+        match *project.generate(&params, &src, dest.path(), false).unwrap_err().kind() {
+            ErrorKind::IllegalPath(ref file, ref rendered, _) => {
+                assert_eq!(file, Path::new("$name$"));
+                assert_eq!(rendered, "..");
+            }
+            ref other => panic!("unexpected error: {:?}", other),
+        }
+
+        project.sanitize_paths = true;
+        project.generate(&params, &src, dest.path(), false).unwrap();
+        assert!(dest.path().join("com/example/_").is_dir());
+    }
+
+    #[test]
+    fn generate_with_custom_formatters() {
+        let src = tempdir::TempDir::new("rig-formatters-test").unwrap();
+        let src = src.path();
+        fsutils::write_file(&src.join("Rig.toml"),
+                            "name = \"my-crate\"\n[engines]\n\"*.hpp\" = \"tera\"")
+            .unwrap();
+        fsutils::write_file(&src.join("$name__guard$.h"), "#ifndef $name;format=\"guard\"$")
+            .unwrap();
+        fsutils::write_file(&src.join("guard.hpp"), "{{ name | guard }} {{ name | upper }}")
+            .unwrap();
+
+        let mut formatters = FormatterRegistry::new();
+        formatters.register("guard", |s: &str| format!("{}_H", s.to_uppercase().replace('-', "_")));
+        let project = Project {
+            style: Style::ST,
+            formatters: Arc::new(formatters),
+            ..Project::new(None as Option<&str>, Configuration::Toml, false)
+        };
+        let params = project.default_params(&src).unwrap();
+        assert_eq!(project.lint(&src).unwrap(), vec![]);
+        let dest = tempdir::TempDir::new("generated-proj").unwrap();
+        project.generate(&params, &src, dest.path(), false).unwrap();
+        assert_eq!(fsutils::read_file(&dest.path().join("MY_CRATE_H.h")).unwrap(),
+                   "#ifndef MY_CRATE_H");
+        assert_eq!(fsutils::read_file(&dest.path().join("guard.hpp")).unwrap(),
+                   "MY_CRATE_H MY-CRATE");
+
+        let render = |style: Style, body: &str| project.render_as(style, "t", body, &params);
+        #[cfg(feature = "handlebars")]
+        assert_eq!(render(Style::Handlebars, "{{guard name}}").unwrap(), "MY_CRATE_H");
+        #[cfg(feature = "liquid")]
+        assert_eq!(render(Style::Liquid, "{{ name | guard }}").unwrap(), "MY_CRATE_H");
+        assert_eq!(render(Style::Tera, "{{ name | guard }}").unwrap(), "MY_CRATE_H");
+    }
+
+    #[test]
+    fn render_paths() {
+        let params = Params::from_map(vec![("name".to_owned(), "con".to_owned()),
+                                           ("package".to_owned(), "com.example".to_owned()),
+                                           ("long".to_owned(), "é".repeat(200)),
+                                           ("up".to_owned(), "..".to_owned())]);
+        let rendered = render_path("src/$package__packaged$/$name$.rs", &params).unwrap();
+        let name = if cfg!(windows) { "con_.rs" } else { "con.rs" };
+        assert_eq!(rendered, Path::new("src/com/example").join(name));
+
+        let rendered = render_path("$long$", &params).unwrap();
+        assert_eq!(rendered.to_str().unwrap().len(), 254);
+
+        match *render_path("src/$up$/main.rs", &params).unwrap_err().kind() {
+            ErrorKind::IllegalPath(_, ref rendered, _) => assert_eq!(rendered, ".."),
+            ref other => panic!("unexpected error: {:?}", other),
+        }
+        assert!(render_path("../$name$", &params).is_err());
+    }
+
+    #[test]
+    fn sorted_params() {
+        let dir = tempdir::TempDir::new("rig-sorted-test").unwrap();
+        let file = dir.path().join("default.properties");
+        fsutils::write_file(&file, "zeta = z\nalpha = a\nmid = m").unwrap();
+
+        let params = read_params(&file, Configuration::JavaProps).unwrap();
+        let keys = params.param_map.keys().map(|k| k.as_ref()).collect::<Vec<&str>>();
+        assert_eq!(keys, vec!["alpha", "mid", "zeta"]);
+
+        let answers = dir.path().join("answers.toml");
+        write_answers(&answers, &params).unwrap();
+        assert_eq!(fsutils::read_file(&answers).unwrap(),
+                   "alpha = \"a\"\nmid = \"m\"\nzeta = \"z\"\n");
+
+        let mut params = params;
+        params.lists.insert("deps".to_owned(), vec!["serde".to_owned(), "toml".to_owned()]);
+        write_answers(&answers, &params).unwrap();
+        let read = read_params(&answers, Configuration::Toml).unwrap();
+        assert_eq!(read.lists.get("deps"), params.lists.get("deps"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn post_generation_hooks() {
+        let src = tempdir::TempDir::new("rig-hooks-test").unwrap();
+        let src = src.path();
+        fsutils::write_file(&src.join("Rig.toml"), r#"
+            name = "abc"
+            crate-name = "abc_rs"
+
+            [hooks]
+            post = ["echo $RIG_PARAM_name $RIG_PARAM_crate_name > hooked", "exit 3"]
+        "#)
+            .unwrap();
+
+        let dest = tempdir::TempDir::new("generated-proj").unwrap();
+        let dest = dest.path();
+        let project = Project::new(None as Option<&str>, Configuration::Toml, false);
+        let params = project.default_params(&src).unwrap();
+        assert_eq!(params.get("hooks"), None);
+        let hooks = project.hooks(&src).unwrap();
+        assert_eq!(hooks.post.len(), 2);
+
+        match *hooks.run_post(dest, &params).unwrap_err().kind() {
+            ErrorKind::HookFailed(ref command, Some(3)) => assert_eq!(command, "exit 3"),
+            ref other => panic!("unexpected error: {:?}", other),
+        }
+        assert_eq!(fsutils::read_file(&dest.join("hooked")).unwrap(), "abc abc_rs\n");
+
+        let mut toml = toml::value::Table::new();
+        toml.insert("hooks".to_owned(), toml::Value::String("git init".to_owned()));
+        assert!(Hooks::from_toml(&toml).is_err());
+    }
+
+    #[test]
+    fn ignore_rules() {
+        let src = tempdir::TempDir::new("rig-ignore-test").unwrap();
+        let src = src.path();
+        fsutils::write_file(&src.join("Rig.toml"), r#"
+            name = "abc"
+
+            [ignore]
+            patterns = ["docs/**"]
+        "#)
+            .unwrap();
+        fsutils::write_file(&src.join(".rigignore"), "# comment\n\n*.lock\nbuild/\n").unwrap();
+        for file in &["README", "Cargo.lock", "sub/Cargo.lock", "build/out", "docs/a/b.md",
+                      "sub/build"] {
+            let path = src.join(file);
+            fsutils::create_dir_all(path.parent().unwrap()).unwrap();
+            fsutils::write_file(&path, "{{ undefined }").unwrap();
+        }
+        for file in &["README", "sub/build"] {
+            fsutils::write_file(&src.join(file), "{{ name }}").unwrap();
+        }
+
+        let dest_dir = tempdir::TempDir::new("generated-proj").unwrap();
+        let project = Project::new(None as Option<&str>, Configuration::Toml, false);
+        let params = project.default_params(&src).unwrap();
+        assert_eq!(params.get("ignore"), None);
+        assert_eq!(project.lint(&src).unwrap(), vec![]);
+        let mut written = project.generate(&params, &src, dest_dir.path(), false)
+            .unwrap()
+            .into_iter()
+            .map(|w| w.path.strip_prefix(dest_dir.path()).unwrap().to_path_buf())
+            .collect::<Vec<_>>();
+        written.sort();
+        // `build/` matches only directories, so `sub/build` file is generated
+        assert_eq!(written, vec![Path::new("README"), Path::new("sub/build")]);
+        assert!(!dest_dir.path().join("docs").exists());
+
+        let mut toml = toml::value::Table::new();
+        toml.insert("ignore".to_owned(), toml::Value::String("docs/**".to_owned()));
+        assert!(Ignores::from_toml(&toml).is_err());
+    }
+
+    #[test]
+    fn copy_binary_files() {
+        let src = tempdir::TempDir::new("rig-binary-test").unwrap();
+        let src = src.path();
+        fsutils::write_file(&src.join("Rig.toml"), "name = \"abc\"").unwrap();
+        fsutils::write_file(&src.join("README"), "{{ name }}").unwrap();
+        fsutils::write_file(&src.join("logo.PNG"), "{{ name }}").unwrap();
+        fsutils::write_file(&src.join("notes.dat"), "{{ name }}").unwrap();
+        fs::write(src.join("blob"), b"{{ name }}\0\xff").unwrap();
+
+        let dest = tempdir::TempDir::new("generated-proj").unwrap();
+        let dest = dest.path();
+        let mut project = Project::new(None as Option<&str>, Configuration::Toml, false);
+        project.verbatim = vec![".dat".to_owned()];
+        let params = project.default_params(&src).unwrap();
+        let vars = project.collect_vars(&src).unwrap();
+        let readme = Some(Path::new("README").to_path_buf());
+        assert_eq!(vars["name"].files, readme.into_iter().collect());
+        project.generate(&params, &src, dest, false).unwrap();
+
+        assert_eq!(fsutils::read_file(&dest.join("README")).unwrap(), "abc");
+        assert_eq!(fsutils::read_file(&dest.join("logo.PNG")).unwrap(), "{{ name }}");
+        assert_eq!(fsutils::read_file(&dest.join("notes.dat")).unwrap(), "{{ name }}");
+        assert_eq!(fs::read(dest.join("blob")).unwrap(), b"{{ name }}\0\xff");
+        assert!(project.lint(&src).unwrap().is_empty());
+    }
+
+    #[test]
+    fn overwrite_policies() {
+        let src = tempdir::TempDir::new("rig-overwrite-test").unwrap();
+        let src = src.path();
+        fsutils::write_file(&src.join("Rig.toml"), "name = \"abc\"").unwrap();
+        fsutils::write_file(&src.join("README"), "{{ name }}").unwrap();
+        fsutils::write_file(&src.join("NOTES"), "new").unwrap();
+
+        let dest = tempdir::TempDir::new("generated-proj").unwrap();
+        let dest = dest.path();
+        let readme = dest.join("README");
+        let mut project = Project::new(None as Option<&str>, Configuration::Toml, false);
+        let params = project.default_params(&src).unwrap();
+        let outcomes = |written: Vec<Written>| {
+            let mut outcomes = written.into_iter()
+                .map(|w| (w.path.file_name().unwrap().to_string_lossy().into_owned(), w.outcome))
+                .collect::<Vec<_>>();
+            outcomes.sort_by(|a, b| a.0.cmp(&b.0));
+            outcomes
+        };
+
+        fsutils::write_file(&readme, "old").unwrap();
+        project.overwrite = OverwritePolicy::Fail;
+        match *project.generate(&params, &src, dest, false).unwrap_err().kind() {
+            ErrorKind::FileExists(ref path) => assert_eq!(path, &readme),
+            ref other => panic!("unexpected error: {:?}", other),
+        }
+        assert!(!dest.join("NOTES").exists());
+
+        project.overwrite = OverwritePolicy::Skip;
+        assert_eq!(outcomes(project.generate(&params, &src, dest, false).unwrap()),
+                   vec![("NOTES".to_owned(), Outcome::Created),
+                        ("README".to_owned(), Outcome::Skipped)]);
+        assert_eq!(fsutils::read_file(&readme).unwrap(), "old");
+
+        project.overwrite = OverwritePolicy::Backup;
+        assert_eq!(outcomes(project.preview(&params, &src, dest).unwrap()),
+                   vec![("NOTES".to_owned(), Outcome::BackedUp),
+                        ("README".to_owned(), Outcome::BackedUp)]);
+        project.generate(&params, &src, dest, false).unwrap();
+        assert_eq!(fsutils::read_file(&readme).unwrap(), "abc");
+        assert_eq!(fsutils::read_file(&fsutils::backup_path(&readme)).unwrap(), "old");
+
+        project.overwrite = OverwritePolicy::Prompt(Arc::new(|file: &Path| {
+            if file.ends_with("NOTES") {
+                OverwritePolicy::Overwrite
+            } else {
+                OverwritePolicy::Skip
+            }
+        }));
+        assert_eq!(outcomes(project.generate(&params, &src, dest, false).unwrap()),
+                   vec![("NOTES".to_owned(), Outcome::Existed),
+                        ("README".to_owned(), Outcome::Skipped)]);
+    }
+
+    #[test]
+    fn report_written_files() {
+        let src = tempdir::TempDir::new("rig-written-test").unwrap();
+        let src = src.path();
+        fsutils::write_file(&src.join("Rig.toml"), "name = \"abc\"").unwrap();
+        fsutils::write_file(&src.join("README"), "{{ name }}!").unwrap();
+        fsutils::write_file(&src.join("NOTES"), "").unwrap();
+
+        let dest = tempdir::TempDir::new("generated-proj").unwrap();
+        let dest = dest.path();
+        assert_eq!(fsutils::create_dir_all(&dest.join("a/b")).unwrap(), Outcome::Created);
+        assert_eq!(fsutils::create_dir_all(&dest.join("a")).unwrap(), Outcome::Existed);
+        fsutils::write_file(&dest.join("README"), "old contents").unwrap();
+
+        let project = Project::new(None as Option<&str>, Configuration::Toml, false);
+        let params = project.default_params(&src).unwrap();
+        let mut written = project.generate(&params, &src, dest, false).unwrap();
+        written.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(written,
+                   vec![Written {
+                            path: dest.join("NOTES"),
+                            bytes: 0,
+                            outcome: Outcome::Created,
+                        },
+                        Written {
+                            path: dest.join("README"),
+                            bytes: 4,
+                            outcome: Outcome::Existed,
+                        }]);
+
+        fs::remove_file(dest.join("NOTES")).unwrap();
+        let mut previewed = project.preview(&params, &src, dest).unwrap();
+        previewed.sort_by(|a, b| a.path.cmp(&b.path));
+        // NOTES would be created again, and README overwritten
+        assert_eq!(previewed, written);
+        assert!(!dest.join("NOTES").exists());
+    }
+
+    #[test]
+    fn parallel_generation() {
+        let src = tempdir::TempDir::new("rig-parallel-test").unwrap();
+        let src = src.path();
+        fsutils::write_file(&src.join("default.properties"), "name = abc").unwrap();
+        for i in 0..40 {
+            let dir = src.join(format!("d{}", i % 4));
+            fsutils::create_dir_all(&dir).unwrap();
+            fsutils::write_file(&dir.join(format!("f{}", i)), &"$name$.".repeat(i)).unwrap();
+        }
+
+        let mut project = Project::new_g8(None);
+        let params = project.default_params(&src).unwrap();
+        let mut reports = Vec::new();
+        for &jobs in &[1, 4, 0] {
+            project.jobs = jobs;
+            let dest = tempdir::TempDir::new("generated-proj").unwrap();
+            let written = project.generate(&params, &src, dest.path(), false).unwrap();
+            assert_eq!(fsutils::read_file(&dest.path().join("d3/f7")).unwrap(), "abc.".repeat(7));
+            reports.push(written.into_iter()
+                .map(|w| (w.path.strip_prefix(dest.path()).unwrap().to_path_buf(), w.bytes))
+                .collect::<Vec<_>>());
+        }
+        assert_eq!(reports[0].len(), 40);
+        assert_eq!(reports[0], reports[1]);
+        assert_eq!(reports[0], reports[2]);
+
+        // the first malformed file in tree order is reported, whichever thread finds it
+        fsutils::write_file(&src.join("d1/f9"), "$name").unwrap();
+        fsutils::write_file(&src.join("d2/f2"), "$name").unwrap();
+        project.jobs = 8;
+        for _ in 0..5 {
+            let dest = tempdir::TempDir::new("generated-proj").unwrap();
+            let err = project.generate(&params, &src, dest.path(), false).unwrap_err();
+            match *err.kind() {
+                ErrorKind::RenderFailure(ref file, _) => assert_eq!(file, Path::new("d1/f9")),
+                ref other => panic!("unexpected error: {:?}", other),
+            }
+        }
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn map_large_files() {
+        let src = tempdir::TempDir::new("rig-mmap-test").unwrap();
+        let src = src.path();
+        let data = "0123456789abcdef".repeat((fsutils::MMAP_THRESHOLD / 16) as usize);
+        fsutils::write_file(&src.join("default.properties"), "name = abc").unwrap();
+        fsutils::write_file(&src.join("data"), &format!("{}$name$", data)).unwrap();
+        fsutils::write_file(&src.join("broken"), &format!("{}\u{e9}", data)).unwrap();
+        let mut bytes = fs::read(src.join("broken")).unwrap();
+        bytes.pop();
+        fs::write(src.join("broken"), &bytes).unwrap();
+
+        match *fsutils::read_text(&src.join("broken")).unwrap_err().kind() {
+            ErrorKind::InvalidUtf8(_, offset) => assert_eq!(offset, data.len()),
+            ref other => panic!("unexpected error: {:?}", other),
+        }
+        fs::remove_file(src.join("broken")).unwrap();
+
+        let project = Project::new_g8(None);
+        let params = project.default_params(&src).unwrap();
+        let dest = tempdir::TempDir::new("generated-proj").unwrap();
+        project.generate(&params, &src, dest.path(), false).unwrap();
+        assert_eq!(fsutils::read_file(&dest.path().join("data")).unwrap(), data + "abc");
+    }
+
+    #[test]
+    fn find_missing_params() {
+        let src = tempdir::TempDir::new("rig-missing-test").unwrap();
+        let src = src.path();
+        fsutils::write_file(&src.join("Rig.toml"), "name = \"x\"\nlicense = \"\"").unwrap();
+        fsutils::write_file(&src.join("README"), "{{ name }} {{ author }} {{ license }}").unwrap();
+        fsutils::write_file(&src.join("NOTICE"), "{{ year | default(value=\"2017\") }}").unwrap();
+
+        let project = Project::new(None as Option<&str>, Configuration::Toml, false);
+        let mut params = project.default_params(&src).unwrap();
+        assert_eq!(project.missing_params(&src, &params).unwrap(),
+                   vec!["author".to_owned(), "license".to_owned()]);
+
+        params.param_map.insert("author".to_owned(), "me".to_owned());
+        params.param_map.insert("license".to_owned(), "MIT".to_owned());
+        assert!(project.missing_params(&src, &params).unwrap().is_empty());
+    }
+
+    #[test]
+    fn lint_template() {
+
+        let src = tempdir::TempDir::new("rig-lint-test").unwrap();
+        let src = src.path();
+        fs::create_dir_all(src.join("src/main/g8")).unwrap();
+
+        fsutils::write_file(&src.join("src/main/g8/default.properties"), G8_PROPS).unwrap();
+        fsutils::write_file(&src.join("src/main/g8/README"),
+                            "$name;format=\"shout\"$ by $author$\n$broken")
+            .unwrap();
+
+        let project = Project::new_g8(Some("src/main/g8"));
+        let issues = project.lint(&src).unwrap();
+        let kinds = issues.iter().map(|i| i.kind.clone()).collect::<Vec<_>>();
+
+        assert_eq!(kinds,
+                   vec![IssueKind::Malformed("cannot parse placeholder".to_owned()),
+                        IssueKind::UnknownFormatter {
+                            param: "name".to_owned(),
+                            formatter: "shout".to_owned(),
+                        },
+                        IssueKind::UndeclaredParam("author".to_owned()),
+                        IssueKind::UnusedParam("bar".to_owned()),
+                        IssueKind::UnusedParam("package".to_owned())]);
+        assert_eq!(issues[0].to_string(), "README:2:1: malformed placeholder: cannot parse placeholder");
+        assert_eq!(issues[1].kind.code(), "unknown-formatter");
+    }
+}
+
+mod output_test {
+
+    extern crate tempdir;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use rig::fsutils;
+    use rig::output::{render_tree, DirSink, MemorySink};
+    use rig::template::{Params, RenderOptions, Style};
+
+    #[test]
+    fn render_into_sinks() {
+        let mut files = HashMap::new();
+        files.insert("src/$name$.rs".to_owned(), "// {% include \"header\" %}".to_owned());
+        files.insert("src/header".to_owned(), "{{ name | upper }}".to_owned());
+        let names = vec!["src/$name$.rs".to_owned()];
+        let params = Params::from_map(vec![("name".to_owned(), "app".to_owned())]);
+
+        let mut sink = MemorySink::new();
+        render_tree(&files, &names, Style::Tera, &params, &RenderOptions::default(), &mut sink)
+            .unwrap();
+        assert_eq!(sink.files.get(&PathBuf::from("src/app.rs")), Some(&b"// APP".to_vec()));
+
+        let dir = tempdir::TempDir::new("rig-sink-test").unwrap();
+        let mut sink = DirSink::new(dir.path());
+        render_tree(&files, &names, Style::Tera, &params, &RenderOptions::default(), &mut sink)
+            .unwrap();
+        assert_eq!(fsutils::read_file(&dir.path().join("src/app.rs")).unwrap(), "// APP");
+    }
+}
+
+mod diff_test {
+
+    extern crate tempdir;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use rig::diff::{diff_trees, missing_files, unified_diff, FileChange};
+    use rig::fsutils;
+
+    #[test]
+    fn identical_text() {
+        assert_eq!(unified_diff("a\nb\n", "a\nb\n", "old", "new"), None);
+    }
+
+    #[test]
+    fn unified_format() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+        let new = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n";
+        assert_eq!(unified_diff(old, new, "a/f", "b/f").unwrap(),
+                   "--- a/f\n+++ b/f\n@@ -2,7 +2,7 @@\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8\n");
+    }
+
+    #[test]
+    fn compare_trees() {
+        let rendered = tempdir::TempDir::new("rig-diff-rendered").unwrap();
+        let existing = tempdir::TempDir::new("rig-diff-existing").unwrap();
+        fs::create_dir_all(rendered.path().join("src")).unwrap();
+        fs::create_dir_all(existing.path().join("src")).unwrap();
+
+        fsutils::write_file(&rendered.path().join("README"), "same").unwrap();
+        fsutils::write_file(&existing.path().join("README"), "same").unwrap();
+        fsutils::write_file(&rendered.path().join("src/lib.rs"), "new").unwrap();
+        fsutils::write_file(&existing.path().join("src/lib.rs"), "old").unwrap();
+        fsutils::write_file(&rendered.path().join("src/added.rs"), "").unwrap();
+        fsutils::write_file(&existing.path().join("src/user.rs"), "").unwrap();
+
+        let changes = diff_trees(rendered.path(), existing.path()).unwrap();
+        let paths = changes.iter().map(|c| c.path().to_path_buf()).collect::<Vec<_>>();
+        assert_eq!(paths, vec![PathBuf::from("src/added.rs"), PathBuf::from("src/lib.rs")]);
+        assert_eq!(changes[0], FileChange::Added(PathBuf::from("src/added.rs")));
+    }
+
+    #[test]
+    fn find_missing_files() {
+        let rendered = tempdir::TempDir::new("rig-missing-rendered").unwrap();
+        let expected = tempdir::TempDir::new("rig-missing-expected").unwrap();
+
+        fsutils::write_file(&rendered.path().join("a"), "").unwrap();
+        fsutils::write_file(&expected.path().join("a"), "").unwrap();
+        fsutils::write_file(&expected.path().join("b"), "").unwrap();
+
+        assert_eq!(missing_files(rendered.path(), expected.path()).unwrap(),
+                   vec![PathBuf::from("b")]);
+    }
+}
+
+mod update_test {
+
+    extern crate tempdir;
+    use std::path::{Path, PathBuf};
+
+    use rig::diagnostics::Diagnostics;
+    use rig::fsutils;
+    use rig::project::{Configuration, Project};
+    use rig::template::ParamValue;
+    use rig::update::{self, State};
+
+    #[test]
+    fn update_generated_project() {
+        let src = tempdir::TempDir::new("rig-update-template").unwrap();
+        let src = src.path();
+        fsutils::write_file(&src.join("Rig.toml"), "name = \"app\"\nworkers = 4").unwrap();
+        fsutils::write_file(&src.join("README"), "{{ name }}").unwrap();
+        fsutils::write_file(&src.join("config"), "workers = {{ workers }}").unwrap();
+        let dest = tempdir::TempDir::new("rig-update-project").unwrap();
+        let dest = dest.path();
+
+        let project = Project::new(None as Option<&str>, Configuration::Toml, false);
+        let params = project.default_params(src).unwrap();
+        let written = project.generate(&params, src, dest, false).unwrap();
+        let mut state = State::new(Some("user/repo"), None, &params);
+        state.record(dest, &written).unwrap();
+        state.write(dest).unwrap();
+
+        let state = State::read(dest).unwrap();
+        assert_eq!(state.template, Some("user/repo".to_owned()));
+        assert_eq!(state.params.value("workers"), Some(ParamValue::Integer(4)));
+        assert_eq!(state.files.len(), 2);
+
+        // template upgraded, and the project changed by user
+        fsutils::write_file(&src.join("README"), "# {{ name }}").unwrap();
+        fsutils::write_file(&src.join("config"), "workers={{ workers }}").unwrap();
+        fsutils::write_file(&src.join("NEW"), "new").unwrap();
+        fsutils::write_file(&dest.join("config"), "workers = 8").unwrap();
+
+        let mut diag = Diagnostics::new();
+        let updates = update::changes(&project, &state.params, src, dest, &state, &mut diag)
+            .unwrap();
+        let summary = updates.iter()
+            .map(|u| (u.path.clone(), u.conflict))
+            .collect::<Vec<_>>();
+        assert_eq!(summary,
+                   vec![(PathBuf::from("NEW"), false),
+                        (PathBuf::from("README"), false),
+                        (PathBuf::from("config"), true)]);
+        assert_eq!(updates[1].diff().unwrap(),
+                   "--- a/README\n+++ b/README\n@@ -1,1 +1,1 @@\n-app\n+# app\n");
+
+        let mut state = state;
+        let written = update::apply(dest, &updates, &mut state).unwrap();
+        assert_eq!(written.len(), 2);
+        assert_eq!(fsutils::read_file(&dest.join("README")).unwrap(), "# app");
+        assert_eq!(fsutils::read_file(&dest.join("config")).unwrap(), "workers = 8");
+        assert!(fsutils::exists(dest.join(Path::new(update::STATE_FILE))));
+    }
+}
+
+#[cfg(feature = "cli")]
+mod cli_test {
+
+    extern crate tempdir;
+    use std::process::Command;
+
+    use rig::fsutils;
+    use rig::template::ParamValue;
+    use rig::update::State;
+
+    #[test]
+    fn new_records_state() {
+        let src = tempdir::TempDir::new("rig-cli-template").unwrap();
+        let src = src.path();
+        fsutils::write_file(&src.join("Rig.toml"), "name = \"app\"\nworkers = 4").unwrap();
+        fsutils::write_file(&src.join("README"), "{{ name }}").unwrap();
+        let dest = tempdir::TempDir::new("rig-cli-project").unwrap();
+        let dest = dest.path().join("app");
+
+        let status = Command::new(env!("CARGO_BIN_EXE_rig"))
+            .arg("new")
+            .arg(src)
+            .arg("--output")
+            .arg(&dest)
+            .args(&["--no-input", "--no-hooks"])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        assert_eq!(fsutils::read_file(&dest.join("README")).unwrap(), "app");
+
+        let state = State::read(&dest).unwrap();
+        assert_eq!(state.template, Some(src.display().to_string()));
+        assert_eq!(state.params.value("workers"), Some(ParamValue::Integer(4)));
+        assert_eq!(state.files.keys().collect::<Vec<_>>(), vec!["README"]);
+    }
+}
+
+#[cfg(feature = "archive")]
+mod archive_test {
+
+    extern crate tempdir;
+    use std::path::Path;
+
+    use rig::archive::{self, Format};
+    use rig::fsutils;
+
+    #[test]
+    fn pack_and_open() {
+        assert_eq!(Format::of(Path::new("t.ZIP")), Some(Format::Zip));
+        assert_eq!(Format::of(Path::new("t.tar.gz")), Some(Format::TarGz));
+        assert_eq!(Format::of(Path::new("t.tar")), None);
+
+        let src = tempdir::TempDir::new("rig-archive-src").unwrap();
+        let template = src.path().join("repo.g8");
+        fsutils::create_dir_all(&template.join("src/empty")).unwrap();
+        fsutils::write_file(&template.join("src/main.rs"), "fn main() {}").unwrap();
+
+        let out = tempdir::TempDir::new("rig-archive-out").unwrap();
+        for name in &["t.zip", "t.tar.gz"] {
+            let file = out.path().join(name);
+            archive::pack(src.path(), &file, Format::of(&file).unwrap()).unwrap();
+            let checkout = archive::open(&file).unwrap();
+            assert_eq!(checkout.path().file_name().unwrap(), "repo.g8");
+            assert_eq!(fsutils::read_file(&checkout.path().join("src/main.rs")).unwrap(),
+                       "fn main() {}");
+            assert!(fsutils::is_directory(&checkout.path().join("src/empty")));
+        }
+    }
+}
+
+mod cache_test {
+
+    extern crate git2;
+    extern crate tempdir;
+    use std::fs;
+    use std::path::Path;
+
+    use url::Url;
+
+    use rig::cache;
+    use rig::fsutils;
+    use rig::source::Source;
+    use rig::settings::{Registered, Settings};
+
+    #[test]
+    fn cache_layout() {
+        let dir = tempdir::TempDir::new("rig-cache-test").unwrap();
+        let url = Url::parse("https://github.com/lettenj61/rig.g8").unwrap();
+        let path = cache::entry_path(dir.path(), &url);
+        assert_eq!(path, dir.path().join("templates/github.com/lettenj61/rig.g8"));
+
+        fs::create_dir_all(&path).unwrap();
+        let repo = git2::Repository::init(&path).unwrap();
+        repo.remote("origin", url.as_str()).unwrap();
+        fs::create_dir_all(dir.path().join("templates/not-a-repo")).unwrap();
+
+        let entries = cache::list(dir.path()).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "github.com/lettenj61/rig.g8");
+        assert_eq!(entries[0].url, Some(url.to_string()));
+        assert!(entries[0].revision.is_none());
+    }
+
+    #[test]
+    fn registered_templates() {
+        let settings = Settings::from_toml(r#"
+            [templates]
+            scala = "typesafehub/scala-sbt.g8"
+
+            [templates.rust]
+            source = "https://example.com/me/rust"
+            version = "v2"
+        "#)
+            .unwrap();
+
+        assert_eq!(settings.template("scala"),
+                   Some(&Registered {
+                       source: "typesafehub/scala-sbt.g8".to_owned(),
+                       version: None,
+                   }));
+        assert_eq!(settings.template("rust").and_then(|r| r.version.clone()),
+                   Some("v2".to_owned()));
+        assert!(Settings::from_toml("[templates]\nbroken = 1").is_err());
+    }
+
+    #[test]
+    fn template_sources() {
+        let source = Source::parse("lettenj61/rig.g8", Some("v1")).unwrap();
+        assert_eq!(source.url.as_str(), "https://github.com/lettenj61/rig.g8");
+        assert_eq!(source.version, Some("v1".to_owned()));
+        let source = Source::parse("https://example.com/me/rust", None).unwrap();
+        assert_eq!(source.url.as_str(), "https://example.com/me/rust");
+        assert!(Source::parse("rust", None).is_err());
+    }
+
+    #[test]
+    fn fetch_and_refresh() {
+        let dir = tempdir::TempDir::new("rig-source-test").unwrap();
+        let upstream = dir.path().join("upstream");
+        let repo = git2::Repository::init(&upstream).unwrap();
+        let sig = git2::Signature::now("rig", "rig@example.com").unwrap();
+        let commit = |content: &str, parent: Option<git2::Oid>| {
+            fsutils::write_file(&upstream.join("Rig.toml"), content).unwrap();
+            let mut index = repo.index().unwrap();
+            index.add_path(Path::new("Rig.toml")).unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let parents = parent.into_iter()
+                .map(|p| repo.find_commit(p).unwrap())
+                .collect::<Vec<_>>();
+            let parents = parents.iter().collect::<Vec<_>>();
+            repo.commit(Some("HEAD"), &sig, &sig, content, &tree, &parents).unwrap()
+        };
+        let first = commit("version = \"1\"", None);
+        repo.tag_lightweight("v1", &repo.find_object(first, None).unwrap(), false).unwrap();
+        let second = commit("version = \"2\"", Some(first));
+
+        let url = Url::from_directory_path(&upstream).unwrap().to_string();
+        let cached = dir.path().join("cached");
+        Source::parse(&url, Some("v1")).unwrap().fetch_into(&cached).unwrap();
+        assert_eq!(fsutils::read_file(&cached.join("Rig.toml")).unwrap(), "version = \"1\"");
+        // without version, the cached clone is brought back to the default branch
+        Source::parse(&url, None).unwrap().fetch_into(&cached).unwrap();
+        assert_eq!(fsutils::read_file(&cached.join("Rig.toml")).unwrap(), "version = \"2\"");
+
+        let entry = cache::CacheEntry::open(dir.path(), &cached).unwrap();
+        assert_eq!(entry.revision, Some(second.to_string()));
+        assert!(!entry.is_stale().unwrap());
+        let third = commit("version = \"3\"", Some(second));
+        assert!(entry.is_stale().unwrap());
+        let entry = entry.refresh().unwrap();
+        assert_eq!(entry.revision, Some(third.to_string()));
+        assert_eq!(fsutils::read_file(&cached.join("Rig.toml")).unwrap(), "version = \"3\"");
+    }
+}
+
+mod skeleton_test {
+
+    extern crate tempdir;
+
+    use rig::fsutils;
+    use rig::project::{Configuration, Project};
+    use rig::skeleton;
+
+    #[test]
+    fn init_and_generate() {
+        let dir = tempdir::TempDir::new("rig-init-test").unwrap();
+        let src = dir.path().join("my-template");
+
+        skeleton::init(&src, "my-template", "someone", false).unwrap();
+        assert!(skeleton::init(&src, "my-template", "someone", false).is_err());
+
+        let project = Project::new(None as Option<&str>, Configuration::Toml, false);
+        assert_eq!(project.lint(&src).unwrap(), vec![]);
+
+        let params = project.default_params(&src).unwrap();
+        assert_eq!(params.get("author"), Some(&"someone".to_owned()));
+
+        let dest = dir.path().join("generated");
+        project.generate(&params, &src, &dest, false).unwrap();
+        assert!(fsutils::exists(dest.join("src/com/example/my-project.txt")));
+        assert!(!fsutils::exists(dest.join(".rigignore")));
+    }
+
+    #[test]
+    fn init_giter8() {
+        let dir = tempdir::TempDir::new("rig-init-g8-test").unwrap();
+
+        skeleton::init(dir.path(), "scala-app", "someone", true).unwrap();
+
+        let project = Project::new_g8(Some("src/main/g8"));
+        assert_eq!(project.lint(dir.path()).unwrap(), vec![]);
+    }
+}
+
+mod manifest_test {
+    extern crate tempdir;
+
+    use std::collections::HashMap;
+    use rig::errors::ErrorKind;
+    use rig::fsutils;
+    use rig::manifest::Manifest;
+    use rig::project::{Configuration, Project};
+
+    const CONFIG: &'static str = "name = \"app\"\n\
+                                  [params.license]\n\
+                                  description = \"License of the project\"\n\
+                                  default = \"MIT\"\n\
+                                  choices = [\"MIT\", \"Apache-2.0\"]\n\
+                                  [params.crate_name]\n\
+                                  pattern = \"[a-z][a-z0-9_]*\"\n";
+
+    #[test]
+    fn read_declarations() {
+        let manifest = Manifest::from_toml(&toml::from_str(CONFIG).unwrap()).unwrap();
+        let license = manifest.get("license").unwrap();
+        assert_eq!(license.spec.description, Some("License of the project".to_owned()));
+        assert_eq!(manifest.defaults().get("license"), Some(&"MIT".to_owned()));
+
+        let mut params = HashMap::new();
+        params.insert("license".to_owned(), "MIT".to_owned());
+        params.insert("crate_name".to_owned(), "my_app".to_owned());
+        assert!(manifest.validate(&params).is_ok());
+        params.insert("crate_name".to_owned(), "my-app!".to_owned());
+        match *manifest.validate(&params).unwrap_err().kind() {
+            ErrorKind::InvalidValue(ref param, ref value, ref reason) => {
+                assert_eq!((param.as_ref(), value.as_ref()), ("crate_name", "my-app!"));
+                assert_eq!(reason, "must match `[a-z][a-z0-9_]*`");
+            }
+            ref other => panic!("unexpected error: {:?}", other),
+        }
+
+        for bad in &["[params.x]\ndefault = \"b\"\nchoices = [\"a\"]",
+                     "[params.x]\nrequired = true",
+                     "[params.x]\npattern = \"(\"",
+                     "params = 1"] {
+            assert!(Manifest::from_toml(&toml::from_str(bad).unwrap()).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn validate_before_generation() {
+        let src = tempdir::TempDir::new("rig-manifest-test").unwrap();
+        let src = src.path();
+        fsutils::write_file(&src.join("Rig.toml"), CONFIG).unwrap();
+        fsutils::write_file(&src.join("LICENSE"), "{{ license }}").unwrap();
+
+        let project = Project::new(None as Option<&str>, Configuration::Toml, false);
+        let mut params = project.default_params(&src).unwrap();
+        assert_eq!(params.get("license"), Some(&"MIT".to_owned()));
+        assert!(params.get("params.license.default").is_none());
+
+        params.param_map.insert("license".to_owned(), "GPL".to_owned());
+        let dest = tempdir::TempDir::new("generated-proj").unwrap();
+        match *project.generate(&params, &src, dest.path(), false).unwrap_err().kind() {
+            ErrorKind::InvalidValue(ref param, _, ref reason) => {
+                assert_eq!(param, "license");
+                assert_eq!(reason, "must be one of MIT, Apache-2.0");
+            }
+            ref other => panic!("unexpected error: {:?}", other),
+        }
+        assert!(!dest.path().join("LICENSE").exists());
+    }
+}
+
+mod expr_test {
+
+    use rig::errors::ErrorKind;
+    use rig::expr::Expr;
+    use rig::manifest::Manifest;
+    use rig::template::Params;
+
+    fn given() -> Params {
+        Params::from_map(vec![("name".to_owned(), "My-Crate".to_owned()),
+                              ("group".to_owned(), "com.example".to_owned())])
+    }
+
+    #[test]
+    fn evaluate_expressions() {
+        let eval = |s: &str| Expr::parse(s).unwrap().eval(&given()).unwrap();
+        assert_eq!(eval("lower(replace(name, '-', '_'))"), "my_crate");
+        assert_eq!(eval("concat(group, \":\", kebab-case(name))"), "com.example:my-crate");
+        assert_eq!(eval("truncate(name, 2)"), "My");
+        assert_eq!(eval("'literal'"), "literal");
+
+        let expr = Expr::parse("upper( concat(name , version) )").unwrap();
+        assert_eq!(expr.params(), vec!["name", "version"]);
+        match *expr.eval(&given()).unwrap_err().kind() {
+            ErrorKind::InvalidExpression(_, ref reason) => {
+                assert_eq!(reason, "parameter `version` is not given")
+            }
+            ref other => panic!("unexpected error: {:?}", other),
+        }
+
+        for bad in &["lower(name", "name)", "'open", "unknown(name)", "truncate(name, 'x')",
+                     "lower()"] {
+            assert!(Expr::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn derive_params() {
+        let config = "[derived]\n\
+                      module_name = \"lower(replace(crate_name, '-', '_'))\"\n\
+                      crate_name = \"kebab-case(name)\"\n";
+        let manifest = Manifest::from_toml(&toml::from_str(config).unwrap()).unwrap();
+        let mut params = given();
+        manifest.derive(&mut params).unwrap();
+        assert_eq!(params.get("crate_name"), Some(&"my-crate".to_owned()));
+        assert_eq!(params.get("module_name"), Some(&"my_crate".to_owned()));
+
+        let mut params = given();
+        params.param_map.insert("crate_name".to_owned(), "given".to_owned());
+        manifest.derive(&mut params).unwrap();
+        assert_eq!(params.get("module_name"), Some(&"given".to_owned()));
+
+        let cyclic = "[derived]\na = \"lower(b)\"\nb = \"upper(a)\"\n";
+        let manifest = Manifest::from_toml(&toml::from_str(cyclic).unwrap()).unwrap();
+        match *manifest.derive(&mut given()).unwrap_err().kind() {
+            ErrorKind::CyclicParams(ref names) => assert_eq!(names.join(" -> "), "a -> b -> a"),
+            ref other => panic!("unexpected error: {:?}", other),
+        }
+    }
+}
+
+mod i18n_test {
+
+    use std::path::PathBuf;
+    use rig::diagnostics::Warning;
+    use rig::i18n::{self, Lang, Message};
+
+    #[test]
+    fn parse_locale() {
+        assert_eq!(Lang::from_locale("ja_JP.UTF-8"), Some(Lang::Ja));
+        assert_eq!(Lang::from_locale("en-GB"), Some(Lang::En));
+        assert_eq!(Lang::from_locale("C"), Some(Lang::En));
+        assert_eq!(Lang::from_locale("fr_FR"), None);
+    }
+
+    #[test]
+    fn format_messages() {
+        assert_eq!(Message::Prompt.format(Lang::Ja, &["name", "{1}"]), "name [{1}]:");
+        assert_eq!(Message::Created.format(Lang::Ja, &["README.md"]), "README.md を作成しました");
+        assert_eq!(Message::Created.format(Lang::En, &[]), "Created {0}");
+
+        let warning = Warning::SuspiciousPath {
+            file: PathBuf::from("$name$"),
+            rendered: "a:b".to_owned(),
+        };
+        assert_eq!(i18n::warning(Lang::En, &warning), warning.to_string());
+        assert!(i18n::warning(Lang::Ja, &warning).starts_with("$name$ は `a:b` として"));
+    }
+}
+
+mod testing_test {
+
+    extern crate tempdir;
+    use rig::fsutils;
+    use rig::project::{Configuration, Project};
+    use rig::template::Params;
+    use rig::testing::{assert_file_eq, assert_render_snapshot, assert_tree_matches, check_render,
+                       check_tree, Fixture, TemplateSuite};
+
+    #[test]
+    fn compare_snapshots() {
+        let dir = tempdir::TempDir::new("rig-snapshot-test").unwrap();
+        let snapshot = dir.path().join("main.rs.snap");
+        fsutils::write_file(&snapshot, "fn my_app() {}\n").unwrap();
+
+        let project = Project::new(None as Option<&str>, Configuration::Toml, false);
+        let params = Params::from_map(vec![("name".to_owned(), "my app".to_owned())]);
+        assert_render_snapshot(&project, "fn {{ name | snake }}() {}\n", &params, &snapshot);
+
+        let report = check_render(&project, "fn {{ name | Camel }}() {}\n", &params, &snapshot)
+            .unwrap()
+            .unwrap();
+        assert!(report.contains("-fn my_app() {}\n+fn MyApp() {}\n"), "{}", report);
+    }
+
+    #[test]
+    fn compare_tree_snapshots() {
+        let src = tempdir::TempDir::new("rig-snapshot-test").unwrap();
+        fsutils::write_file(&src.path().join("Rig.toml"), "name = \"my app\"").unwrap();
+        fsutils::write_file(&src.path().join("README.md"), "# {{ name }}").unwrap();
+        fsutils::write_file(&src.path().join("LICENSE"), "MIT").unwrap();
+
+        let snapshot = tempdir::TempDir::new("rig-snapshot").unwrap();
+        fsutils::write_file(&snapshot.path().join("README.md"), "# my app").unwrap();
+        fsutils::write_file(&snapshot.path().join("CHANGES.md"), "").unwrap();
+
+        let project = Project::new(None as Option<&str>, Configuration::Toml, false);
+        let params = project.default_params(src.path()).unwrap();
+        let report = check_tree(&project, &params, src.path(), snapshot.path()).unwrap().unwrap();
+        assert_eq!(report, "Not rendered: CHANGES.md\nNot expected: LICENSE\n");
+    }
+
+    #[test]
+    fn generate_fixture() {
+        let src = Fixture::builder()
+            .file("Rig.toml", "name = \"my app\"")
+            .file("src/$name__snake$.rs", "// {{ name | Camel }}")
+            .dir("empty")
+            .build()
+            .unwrap();
+        let dest = Fixture::builder().build().unwrap();
+
+        let project = Project::new(None as Option<&str>, Configuration::Toml, false);
+        let params = project.default_params(src.path()).unwrap();
+        project.generate(&params, src.path(), dest.path(), false).unwrap();
+        assert_file_eq(dest.path().join("src/my_app.rs"), "// MyApp");
+        assert_tree_matches(dest.path(), &[("src/my_app.rs", "// MyApp")]);
+        assert!(fsutils::is_directory(dest.path().join("empty")));
+    }
+
+    #[test]
+    #[should_panic(expected = "Not rendered: README.md")]
+    fn tree_mismatch() {
+        let tree = Fixture::builder().file("LICENSE", "MIT").build().unwrap();
+        assert_tree_matches(tree.path(), &[("LICENSE", "MIT"), ("README.md", "")]);
+    }
+
+    #[test]
+    fn run_template_suite() {
+        let src = Fixture::builder()
+            .file("Rig.toml", "name = \"my app\"")
+            .file("README.md", "# {{ name }}")
+            .file(".rig-tests/default/expected/README.md", "# my app")
+            .file(".rig-tests/named/params.toml", "name = \"other\"")
+            .file(".rig-tests/named/expected/README.md", "# my app")
+            .build()
+            .unwrap();
+
+        let project = Project::new(None as Option<&str>, Configuration::Toml, false);
+        let suite = TemplateSuite::discover(&project, src.path()).unwrap();
+        let names = suite.cases().iter().map(|c| c.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["default", "named"]);
+
+        let results = suite.run();
+        assert!(results[0].passed());
+        let report = results[1].report.as_ref().unwrap();
+        assert!(report.contains("+# other"), "{}", report);
+
+        let dest = Fixture::builder().build().unwrap();
+        let params = project.default_params(src.path()).unwrap();
+        project.generate(&params, src.path(), dest.path(), false).unwrap();
+        assert_tree_matches(dest.path(), &[("README.md", "# my app")]);
+    }
+}
+
+#[cfg(feature = "pretty-diagnostics")]
+mod pretty_test {
+
+    use std::path::PathBuf;
+    use rig::pretty::Diagnostic;
+    use rig::project::{Issue, IssueKind};
+    use rig::template::Position;
+
+    fn issue(line: usize, column: usize) -> Issue {
+        Issue {
+            file: PathBuf::from("README.md"),
+            position: Some(Position {
+                line: line,
+                column: column,
+            }),
+            kind: IssueKind::UndeclaredParam("nope".to_owned()),
+        }
+    }
+
+    #[test]
+    fn underline_placeholder() {
+        let source = "# Title\n\tHello, {{ nope | upper }}!\n";
+        let rendered = Diagnostic::from_issue(&issue(2, 9)).render(Some(source));
+        assert_eq!(rendered,
+                   "warning: parameter `nope` is not declared\n \
+                    --> README.md:2:9\n  \
+                    |\n\
+                    2 | \tHello, {{ nope | upper }}!\n  \
+                    | \t       ^^^^^^^^^^^^^^^^^^ not declared\n  \
+                    |\n  \
+                    = help: declare `nope` with its default value in template config\n");
+    }
+
+    #[test]
+    fn unclosed_placeholder() {
+        let rendered = Diagnostic::from_issue(&issue(1, 3)).render(Some("a $nope"));
+        assert!(rendered.contains("1 | a $nope\n  |   ^^^^^ not declared\n"), "{}", rendered);
+    }
+
+    #[test]
+    fn without_source() {
+        let rendered = Diagnostic::from_issue(&issue(12, 9)).render(None);
+        assert_eq!(rendered,
+                   "warning: parameter `nope` is not declared\n\
+                    --> README.md:12:9\n\
+                    \x20= note: not declared\n\
+                    \x20= help: declare `nope` with its default value in template config\n");
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_test {
+
+    extern crate serde_json;
+    use std::path::PathBuf;
+    use rig::fsutils::{Outcome, Written};
+    use rig::template::Params;
+
+    #[test]
+    fn params_round_trip() {
+        let params = Params::from_map(vec![("name".to_owned(), "my crate".to_owned())]);
+        let json = serde_json::to_string(&params).unwrap();
+        let decoded: Params = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.param_map, params.param_map);
+        assert!(decoded.toml.is_none());
+    }
+
+    #[test]
+    fn params_from_struct() {
+        #[derive(Serialize)]
+        struct Author {
+            name: String,
+        }
+        #[derive(Serialize)]
+        struct Config {
+            name: String,
+            port: u16,
+            license: Option<String>,
+            features: Vec<String>,
+            author: Author,
+        }
+
+        let config = Config {
+            name: "app".to_owned(),
+            port: 8080,
+            license: None,
+            features: vec!["a".to_owned()],
+            author: Author { name: "me".to_owned() },
+        };
+        let params = Params::from_serializable(&config).unwrap();
+        assert_eq!(params.get("port"), Some(&"8080".to_owned()));
+        assert_eq!(params.get("license"), None);
+        assert_eq!(params.get("author.name"), Some(&"me".to_owned()));
+        assert_eq!(params.get_list("features"), Some(&["a".to_owned()][..]));
+        assert!(Params::from_serializable(&vec![1, 2]).is_err());
+    }
+
+    #[test]
+    fn written_round_trip() {
+        let written = Written {
+            path: PathBuf::from("out/README.md"),
+            bytes: 42,
+            outcome: Outcome::Existed,
+        };
+        let json = serde_json::to_string(&written).unwrap();
+        assert_eq!(serde_json::from_str::<Written>(&json).unwrap(), written);
+    }
+}
+
+#[cfg(any(feature = "json", feature = "yaml"))]
+mod params_format_test {
+
+    use rig::template::Params;
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn params_from_json() {
+        extern crate serde_json;
+        use std::convert::TryFrom;
+
+        let json = r#"{"name": "app", "port": 8080, "features": ["a", "b"],
+                       "author": {"name": "me"}}"#;
+        let params = Params::from_json(json).unwrap();
+        assert_eq!(params.get("port"), Some(&"8080".to_owned()));
+        assert_eq!(params.get("author.name"), Some(&"me".to_owned()));
+        assert_eq!(params.get_list("features"), Some(&["a".to_owned(), "b".to_owned()][..]));
+
+        let value = serde_json::from_str::<serde_json::Value>(json).unwrap();
+        assert_eq!(Params::try_from(value).unwrap().param_map, params.param_map);
+        assert!(Params::from_json("[1, 2]").is_err());
+        assert!(Params::from_json(r#"{"name": null}"#).is_err());
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn params_from_yaml() {
+        let params = Params::from_yaml("name: app\ndebug: true\nauthor:\n  name: me\n").unwrap();
+        assert_eq!(params.get("name"), Some(&"app".to_owned()));
+        assert_eq!(params.get("debug"), Some(&"true".to_owned()));
+        assert_eq!(params.get("author.name"), Some(&"me".to_owned()));
+    }
+}
+
+mod params_builder_test {
+
+    use rig::params::{param_env_name, stamp_now, Origin, ParamsBuilder, NOW_PARAM};
+    use rig::template::Params;
+
+    #[test]
+    fn layered_params() {
+        let mut defaults = Params::minimal_req();
+        defaults.param_map.insert("crate-name".to_owned(), "demo".to_owned());
+        defaults.param_map.insert("license".to_owned(), "MIT".to_owned());
+        assert_eq!(param_env_name("crate-name"), "RIG_PARAM_crate_name");
+
+        let mut builder = ParamsBuilder::new();
+        builder.define("name=cli")
+            .defaults(&defaults)
+            .env_vars(vec![("RIG_PARAM_crate_name".to_owned(), "env".to_owned()),
+                           ("RIG_PARAM_name".to_owned(), "env".to_owned()),
+                           ("RIG_PARAM_extra".to_owned(), "more".to_owned()),
+                           ("PATH".to_owned(), "/bin".to_owned())]);
+        let params = builder.build();
+
+        assert_eq!(params.get("name"), Some(&"cli".to_owned()));
+        assert_eq!(params.get("crate-name"), Some(&"env".to_owned()));
+        assert_eq!(params.get("license"), Some(&"MIT".to_owned()));
+        assert_eq!(params.get("extra"), Some(&"more".to_owned()));
+        assert_eq!(params.get("PATH"), None);
+
+        assert_eq!(builder.origin("name"), Some(&Origin::Override));
+        assert_eq!(builder.origin("crate-name"),
+                   Some(&Origin::Env("RIG_PARAM_crate_name".to_owned())));
+        assert_eq!(builder.origin("license"), Some(&Origin::Default));
+        assert_eq!(builder.origin("missing"), None);
+
+        let mut params = builder.build();
+        stamp_now(&mut params);
+        assert!(params.get(NOW_PARAM).map_or(false, |now| now.contains('T')));
+        builder.set(NOW_PARAM, "1979-05-27");
+        let mut params = builder.build();
+        stamp_now(&mut params);
+        assert_eq!(params.get(NOW_PARAM), Some(&"1979-05-27".to_owned()));
+    }
+}
+
+#[cfg(feature = "tokio")]
+mod generator_test {
+
+    extern crate tempdir;
+    extern crate tokio;
+    use rig::fsutils::{self, Outcome};
+    use rig::generator::Generator;
+    use rig::project::Project;
+
+    #[test]
+    fn generate_async() {
+        let src = tempdir::TempDir::new("rig-async-test").unwrap();
+        let src = src.path();
+        fsutils::write_file(&src.join("default.properties"), "name = abc").unwrap();
+        for i in 0..20 {
+            let dir = src.join(format!("$name$/d{}", i % 3));
+            fsutils::create_dir_all(&dir).unwrap();
+            fsutils::write_file(&dir.join(format!("f{}", i)), &"$name$.".repeat(i)).unwrap();
+        }
+
+        let project = Project::new_g8(None);
+        let params = project.default_params(&src).unwrap();
+        let dest = tempdir::TempDir::new("generated-proj").unwrap();
+        fsutils::create_dir_all(&dest.path().join("abc/d1")).unwrap();
+        fsutils::write_file(&dest.path().join("abc/d1/f1"), "old").unwrap();
+
+        let mut generator = Generator::new(project.clone());
+        generator.concurrency = 4;
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let written = runtime.block_on(generator.generate(&params, &src, dest.path(), false))
+            .unwrap();
+        assert_eq!(fsutils::read_file(&dest.path().join("abc/d2/f5")).unwrap(), "abc.".repeat(5));
+        assert_eq!(written.iter().filter(|w| w.outcome == Outcome::Existed).count(), 1);
+
+        // reported the same as synchronous generation
+        let expected = project.generate(&params, &src, dest.path(), false).unwrap();
+        assert_eq!(written.iter().map(|w| (&w.path, w.bytes)).collect::<Vec<_>>(),
+                   expected.iter().map(|w| (&w.path, w.bytes)).collect::<Vec<_>>());
+    }
+}
+
+#[cfg(feature = "derive")]
+mod derive_test {
+
+    use rig::template::{ParamSpec, Params, VtolParams};
+    use rig::VtolParams;
+
+    #[derive(VtolParams)]
+    struct Crate {
+        /// Name of the crate,
+        /// as published.
+        #[vtol(default = "my-crate")]
+        name: String,
+        #[vtol(rename = "edition_year")]
+        edition: u16,
+        license: Option<String>,
+    }
+
+    #[test]
+    fn derive_params() {
+        let params: Params = Crate {
+                name: "rig".to_owned(),
+                edition: 2015,
+                license: None,
+            }
+            .into();
+        assert_eq!(params.get("name").unwrap(), "rig");
+        assert_eq!(params.get("edition_year").unwrap(), "2015");
+        assert!(params.get("license").is_none());
+
+        let specs = Crate::param_specs();
+        assert_eq!(specs[0],
+                   ParamSpec {
+                       name: "name".to_owned(),
+                       description: Some("Name of the crate, as published.".to_owned()),
+                       default: Some("my-crate".to_owned()),
+                   });
+        assert_eq!(specs.iter().map(|s| &*s.name).collect::<Vec<_>>(),
+                   ["name", "edition_year", "license"]);
+        assert_eq!(Crate::defaults().param_map.len(), 1);
+    }
+}
+
+#[cfg(feature = "test-util")]
+mod property_test {
+
+    use std::str;
+    use rig::strategies::{params, styled_raw_text, styled_template};
+    use rig::template::Template;
+
+    proptest! {
+        #[test]
+        fn raw_text_is_verbatim((style, text) in styled_raw_text(), values in params()) {
+            let mut out = Vec::new();
+            Template::write_once(&mut out, style, &text, &values).unwrap();
+            prop_assert_eq!(str::from_utf8(&out).unwrap(), &*text);
+        }
+
+        #[test]
+        fn generated_templates_render((style, body) in styled_template(), values in params()) {
+            let tpl = Template::read_str(style, &body);
+            prop_assert!(tpl.scan().error.is_none(), "{:?}", body);
+            let mut out = Vec::new();
+            prop_assert!(tpl.write_to(&mut out, &values).is_ok());
+        }
+    }
+}
+
+#[cfg(feature = "ffi")]
+mod ffi_test {
+
+    use std::ffi::{CStr, CString};
+    use std::ptr;
+    use rig::ffi::*;
+
+    #[test]
+    fn render_into_buffer() {
+        let style = CString::new("tera").unwrap();
+        let body = CString::new("fn {{ name | snake }}() {}").unwrap();
+        let name = CString::new("name").unwrap();
+        let value = CString::new("my crate").unwrap();
+        unsafe {
+            let tpl = rig_template_compile(style.as_ptr(), body.as_ptr());
+            let params = rig_params_new();
+            assert_eq!(rig_params_set(params, name.as_ptr(), value.as_ptr()), 0);
+
+            assert_eq!(rig_template_render(tpl, params, ptr::null_mut(), 0), 16);
+            let mut buf = [1 as ::std::os::raw::c_char; 8];
+            assert_eq!(rig_template_render(tpl, params, buf.as_mut_ptr(), buf.len()), 16);
+            assert_eq!(CStr::from_ptr(buf.as_ptr()).to_str().unwrap(), "fn my_c");
+
+            let mut buf = [0; 32];
+            assert_eq!(rig_template_render(tpl, params, buf.as_mut_ptr(), buf.len()), 16);
+            assert_eq!(CStr::from_ptr(buf.as_ptr()).to_str().unwrap(), "fn my_crate() {}");
+
+            rig_template_free(tpl);
+            rig_params_free(params);
+        }
+    }
+
+    #[test]
+    fn report_last_error() {
+        let style = CString::new("mustache").unwrap();
+        unsafe {
+            assert!(rig_template_compile(style.as_ptr(), ptr::null()).is_null());
+            let msg = CStr::from_ptr(rig_last_error()).to_str().unwrap();
+            assert_eq!(msg, "Unknown style \"mustache\"");
+        }
+    }
+}
+
+mod degenerate_test {
+
+    extern crate rand;
+    use std::collections::HashMap;
+    use std::str;
+    use self::rand::{thread_rng, Rng};
+    use rig::format::format;
+    use rig::parser::parse_template;
+    use rig::template::*;
+
+    const STYLES: &'static [Style] =
+        &[Style::ST, Style::Path, Style::Tera, Style::Handlebars, Style::Liquid];
+    const FORMATTERS: &'static [&'static str] = &["lower", "upper", "cap", "decap", "word", "hyphen",
+                                                  "space", "start", "Camel", "camel", "norm",
+                                                  "snake", "packaged", "random", "kebab-case",
+                                                  "camelCase", "SCREAMING_SNAKE_CASE", "unknown"];
+
+    fn render(style: &Style, body: &str, params: &HashMap<String, String>) -> Option<String> {
+        let mut out = Vec::new();
+        match Template::write_once(&mut out, style.clone(), body, params) {
+            Ok(_) => Some(String::from_utf8(out).unwrap()),
+            Err(_) => None,
+        }
+    }
+
+    fn random_string(alphabet: &[char], max_len: usize) -> String {
+        let mut rng = thread_rng();
+        let len = rng.gen_range(0, max_len + 1);
+        (0..len).map(|_| *rng.choose(alphabet).unwrap()).collect()
+    }
+
+    fn single(style: &Style, name: &str) -> String {
+        match *style {
+            Style::Tera | Style::Handlebars | Style::Liquid => format!("{{{{ {} }}}}", name),
+            _ => format!("${}$", name),
+        }
+    }
+
+    #[test]
+    fn empty_template() {
+        let mut params = HashMap::new();
+        for style in STYLES {
+            assert_eq!(render(style, "", &params), Some(String::new()));
+        }
+        params.insert("name".to_owned(), "value".to_owned());
+        for style in STYLES {
+            assert_eq!(render(style, "", &params), Some(String::new()));
+            assert!(Template::read_str(style.clone(), "").placeholders().is_empty());
+        }
+    }
+
+    #[test]
+    fn single_placeholder() {
+        let mut params = HashMap::new();
+        for style in STYLES {
+            assert_eq!(render(style, &single(style, "name"), &params), Some("name".to_owned()));
+        }
+        params.insert("name".to_owned(), "value".to_owned());
+        for style in STYLES {
+            assert_eq!(render(style, &single(style, "name"), &params), Some("value".to_owned()));
+        }
+    }
+
+    #[test]
+    fn zero_length_values() {
+        let mut params = HashMap::new();
+        params.insert("name".to_owned(), String::new());
+        for style in STYLES {
+            assert_eq!(render(style, &single(style, "name"), &params), Some(String::new()));
+        }
+        for f in FORMATTERS {
+            assert_eq!(format("", (*f).into()), "");
+        }
+        assert_eq!(render(&Style::ST, "$name;format=\"random,Camel\"$", &params),
+                   Some(String::new()));
+    }
+
+    #[test]
+    fn plain_text_is_verbatim() {
+        let alphabet = ['a', 'Z', '0', '_', ' ', '\n', '\t', ';', '"', '=', '|', '}', 'é', '語'];
+        let mut params = HashMap::new();
+        params.insert("a".to_owned(), "value".to_owned());
+        for _ in 0..500 {
+            let body = random_string(&alphabet, 24);
+            for style in STYLES {
+                assert_eq!(render(style, &body, &params).as_ref(), Some(&body), "{:?}", body);
+            }
+        }
+    }
+
+    #[test]
+    fn mostly_raw_input() {
+        let mut params = HashMap::new();
+        params.insert("a".to_owned(), "value".to_owned());
+        let js = "function(e){return{a:e,b:[{}]}};".repeat(2000);
+        assert_eq!(render(&Style::Tera, &format!("{}{{{{ a }}}}{}", js, js), &params),
+                   Some(format!("{}value{}", js, js)));
+        let escaped = "cost: \\$5, path: C:\\\\dir; ".repeat(2000);
+        assert_eq!(render(&Style::ST, &format!("{}$a$", escaped), &params),
+                   Some(format!("{}value", "cost: $5, path: C:\\dir; ".repeat(2000))));
+    }
+
+    #[test]
+    fn arbitrary_input_never_panics() {
+        let alphabet = ['$', '\\', '{', '}', '|', ';', '_', '=', '"', ' ', '\n', 'a', 'é'];
+        let mut params = HashMap::new();
+        params.insert("a".to_owned(), "value".to_owned());
+        for _ in 0..2000 {
+            let body = random_string(&alphabet, 16);
+            for style in STYLES {
+                let rendered = render(style, &body, &params);
+                let scan = Template::read_str(style.clone(), &body).scan();
+                // rendering fails exactly when scanning finds malformed placeholder
+                assert_eq!(rendered.is_none(), scan.error.is_some(), "{:?} {:?}", style, body);
+            }
+        }
+    }
+
+    /// Apply `parse_template` until input is exhausted, checking each step makes progress.
+    fn parse_steps(style: &Style, body: &str) -> usize {
+        let mut rest = body;
+        let mut steps = 0;
+        while !rest.is_empty() {
+            match parse_template(rest, style) {
+                Ok((_, _, next)) => {
+                    assert!(next.len() < rest.len(), "{:?} {:?}", style, rest);
+                    rest = next;
+                    steps += 1;
+                }
+                Err(_) => break,
+            }
+        }
+        steps
+    }
+
+    #[test]
+    fn parser_always_progresses() {
+        let alphabet = ['$', '\\', '{', '}', '|', '(', ')', ';', '"', ' ', 'a', '語'];
+        for _ in 0..2000 {
+            let body = random_string(&alphabet, 16);
+            for style in STYLES {
+                assert!(parse_steps(style, &body) <= body.len());
+            }
+        }
+    }
+
+    #[test]
+    fn adversarial_delimiters() {
+        let runs = ["$", "$$", "\\", "\\$", "{", "{{", "}}", "{{ a", "{{ a |", "$a;format=\""];
+        for run in &runs {
+            let body = run.repeat(20000);
+            for style in STYLES {
+                assert!(parse_steps(style, &body) <= body.len());
+                let _ = Template::read_str(style.clone(), &body).scan();
+            }
+        }
+    }
 }